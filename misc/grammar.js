@@ -299,6 +299,7 @@ module.exports = grammar({
 			$.program,
 			$.library,
 			$.unit,
+			$.package,
 			$._definitions // For include files
 		)),
 
@@ -318,6 +319,13 @@ module.exports = grammar({
 			$.kEndDot
 		),
 
+		package:            $ => seq(
+			$.kPackage, $.moduleName, ';',
+			optional($.declRequires),
+			optional($.declContains),
+			$.kEnd, $.kEndDot
+		),
+
 		unit:               $ => seq(
 			$.kUnit, $.moduleName, ';',
 			repeat(choice(
@@ -642,6 +650,8 @@ module.exports = grammar({
 		// Declaration sections
 
 		declUses:        $ => seq($.kUses, delimited($.moduleName), ';'),
+		declRequires:    $ => seq($.kRequires, delimited($.moduleName), ';'),
+		declContains:    $ => seq($.kContains, delimited($.moduleName), ';'),
 		declExports:     $ => seq($.kExports, delimited($.declExport), ';'),
 
 		declTypes:       $ => seq(
@@ -1019,6 +1029,9 @@ module.exports = grammar({
 		kLibrary:          $ => /library/i,
 		kUnit:             $ => /unit/i,
 		kUses:             $ => /uses/i,
+		kPackage:          $ => /package/i,
+		kRequires:         $ => /requires/i,
+		kContains:         $ => /contains/i,
 		kInterface:        $ => /interface/i,
 		kDispInterface:    $ => /dispinterface/i,
 		kImplementation:   $ => /implementation/i,
@@ -1,7 +1,13 @@
 // Handles CLI argument parsing and related types for dfixxer
-use crate::dfixxer_error::DFixxerError;
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use dfixxer::DFixxerError;
+use std::collections::BTreeSet;
 use std::env;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -33,88 +39,469 @@ impl LogLevel {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ColorMode {
+    /// Color the diff only when stdout is a terminal
+    #[default]
+    Auto,
+    /// Always color the diff
+    Always,
+    /// Never color the diff
+    Never,
+}
+
+impl ColorMode {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Auto => std::io::stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Unified diffs for people
+    #[default]
+    Text,
+    /// One JSON object listing the changes in every file, for tools
+    Json,
+}
+
+/// Transformations that can be selected from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransformationKind {
+    /// Uses clause sorting and formatting
+    Uses,
+    /// Unit and program headers
+    Unit,
+    /// Interface, implementation, initialization, and finalization keywords
+    Keyword,
+    /// Procedure and function headers
+    Procedure,
+    /// Text spacing rules
+    Text,
+}
+
+/// Transformation selection made with `--only` or `--skip`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransformationFilter {
+    /// Run exactly these transformations
+    Only(Vec<TransformationKind>),
+    /// Run what the configuration enables, except these transformations
+    Skip(Vec<TransformationKind>),
+}
+
+/// Context lines around each diff hunk unless `--diff-context` says otherwise
+pub const DEFAULT_DIFF_CONTEXT: usize = 3;
+
 #[derive(Debug)]
 pub enum Command {
     UpdateFile,
     CheckFile,
+    /// Report like `CheckFile`, then write the same result like `UpdateFile`
+    CheckAndUpdate,
     InitConfig,
     Parse,
     ParseDebug,
+    PrintConfig,
     Version,
+    Completions(Shell),
+    Serve,
 }
 
 pub struct Arguments {
     pub command: Command,
+    /// The file used for config lookup: the first of `filenames`, or the stdin path
     pub filename: String,
+    /// Every filename or glob pattern given on the command line
+    pub filenames: Vec<String>,
     pub config_path: Option<String>,
     pub log_level: Option<LogLevel>,
+    /// Print the performance summary to stderr whatever the log level
+    pub verbose_timing: bool,
     pub multi: bool,
+    /// An empty match set in multi mode exits cleanly instead of failing
+    pub allow_empty: bool,
+    /// Read the source from stdin; `filename` is then only used for config lookup
+    pub stdin: bool,
+    /// Byte range (start, end) to restrict formatting to
+    pub range: Option<(usize, usize)>,
+    /// Print only the paths of files that would change (check only)
+    pub list_different: bool,
+    /// 1-based inclusive line range (first, last) to restrict parse-debug output to
+    pub line_range: Option<(usize, usize)>,
+    /// Print byte offsets next to each node's line:col span (parse only)
+    pub show_bytes: bool,
+    /// Whether to color diff output (check only)
+    pub color: ColorMode,
+    /// Unchanged lines shown around each diff hunk (check only)
+    pub diff_context: usize,
+    /// Print why each change is suggested after the diff (check only)
+    pub explain: bool,
+    /// Print lines whose indentation does not fit `indentation` (check only)
+    pub report_indentation: bool,
+    /// How suggested changes are reported (check only)
+    pub format: OutputFormat,
+    /// Transformations selected with `--only` or `--skip`, overriding the configuration
+    /// (update and check)
+    pub transformation_filter: Option<TransformationFilter>,
+    /// Overwrite an existing configuration file (init-config only)
+    pub force: bool,
+    /// Write the commented configuration template instead of serialized defaults (init-config only)
+    pub template: bool,
+    /// Only process files changed since this git ref, or listed in `@<path>` (update and check)
+    pub since: Option<String>,
+    /// Also process files pulled in with `{$I}` / `{$INCLUDE}` (update and check)
+    pub follow_includes: bool,
+    /// Show a progress bar on stderr in multi mode (update and check)
+    pub progress: bool,
+    /// Files processed at once; output keeps the input order whatever the count
+    /// (update and check)
+    pub jobs: usize,
+    /// Fail files with parse errors, overriding `parse_policy` (update and check)
+    pub strict_parse: bool,
+    /// Directory the upward `dfixxer.toml` search stops at (serve only; the other commands
+    /// look up `config_path` while their arguments are parsed)
+    pub config_search_root: Option<PathBuf>,
+}
+
+impl Arguments {
+    /// Arguments for `command` with every command-specific option left at its default
+    fn new(command: Command, log_level: Option<LogLevel>, verbose_timing: bool) -> Self {
+        Arguments {
+            command,
+            filename: String::new(),
+            filenames: Vec::new(),
+            config_path: None,
+            log_level,
+            verbose_timing,
+            multi: false,
+            allow_empty: false,
+            stdin: false,
+            range: None,
+            list_different: false,
+            line_range: None,
+            show_bytes: false,
+            color: ColorMode::default(),
+            diff_context: DEFAULT_DIFF_CONTEXT,
+            explain: false,
+            report_indentation: false,
+            format: OutputFormat::default(),
+            transformation_filter: None,
+            force: false,
+            template: false,
+            since: None,
+            follow_includes: false,
+            progress: false,
+            jobs: 1,
+            strict_parse: false,
+            config_search_root: None,
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
-#[command(name = "dfixxer", about = "Fix Delphi/Pascal files", version)]
+#[command(
+    name = "dfixxer",
+    about = "Fix Delphi/Pascal files",
+    version,
+    subcommand_negates_reqs = true,
+    group(ArgGroup::new("mode").args(["check", "write"]).required(true)),
+    group(
+        ArgGroup::new("check_options")
+            .args(["list_different", "color", "diff_context", "explain", "report_indentation", "format", "apply"])
+            .multiple(true)
+            .conflicts_with("write")
+    )
+)]
 struct Cli {
     /// Set the logging level
     #[arg(long = "log-level", short = 'l', value_enum, global = true)]
     log_level: Option<LogLevel>,
 
+    /// Print the performance summary to stderr without raising the log level
+    #[arg(long = "verbose-timing", global = true)]
+    verbose_timing: bool,
+
+    /// Stop the upward search for dfixxer.toml at this directory (defaults to $DFIXXER_ROOT)
+    #[arg(long = "config-search-root", global = true)]
+    config_search_root: Option<PathBuf>,
+
+    /// Check the given files without a subcommand, like `dfixxer check <FILENAME>...`
+    #[arg(long = "check")]
+    check: bool,
+
+    /// Update the given files without a subcommand, like `dfixxer update <FILENAME>...`
+    #[arg(long = "write")]
+    write: bool,
+
+    #[command(flatten)]
+    args: CheckArgs,
+
     #[command(subcommand)]
-    command: CliCommand,
+    command: Option<CliCommand>,
+}
+
+impl Cli {
+    /// Parse like `try_parse_from`, rejecting the top-level options when a subcommand is given.
+    /// `args_conflicts_with_subcommands` would reject the global options as well, so the
+    /// conflict is checked here.
+    fn try_parse_checked(args: &[String]) -> Result<Self, clap::Error> {
+        let mut command = Cli::command();
+        let matches = command.try_get_matches_from_mut(args)?;
+        let top_level = command.get_arguments().find(|arg| {
+            !arg.is_global_set()
+                && matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        });
+        if matches.subcommand().is_some()
+            && let Some(arg) = top_level.map(ToString::to_string)
+        {
+            return Err(command.error(
+                ErrorKind::ArgumentConflict,
+                format!("the argument '{}' cannot be used with a subcommand", arg),
+            ));
+        }
+        Cli::from_arg_matches(&matches)
+    }
+
+    /// The explicit subcommand, or the one implied by `--check` / `--write`
+    fn resolved_command(self) -> CliCommand {
+        match self.command {
+            Some(command) => command,
+            None if self.write => CliCommand::Update(self.args.files),
+            None => CliCommand::Check(self.args),
+        }
+    }
+}
+
+/// Options shared by `update` and `check`
+#[derive(Args, Debug)]
+struct FileArgs {
+    /// The filenames to process, or glob patterns with --multi
+    #[arg(value_name = "FILENAME", required_unless_present = "stdin_filename")]
+    filename: Vec<String>,
+    /// Path to the configuration file
+    #[arg(long = "config")]
+    config: Option<String>,
+    /// Process multiple files using glob patterns
+    #[arg(long = "multi")]
+    multi: bool,
+    /// With --multi, exit successfully when the patterns match no files
+    #[arg(long = "allow-empty", requires = "multi")]
+    allow_empty: bool,
+    /// Read the source from stdin and use this path only for config lookup and file patterns
+    #[arg(long = "stdin-filename", conflicts_with_all = ["filename", "multi"])]
+    stdin_filename: Option<String>,
+    /// Only format changes starting at or after this byte offset
+    #[arg(long = "range-start", conflicts_with = "multi")]
+    range_start: Option<usize>,
+    /// Only format changes ending at or before this byte offset
+    #[arg(long = "range-end", conflicts_with = "multi")]
+    range_end: Option<usize>,
+    /// Run only these transformations, overriding the configuration
+    #[arg(long = "only", value_enum, value_delimiter = ',', value_name = "KINDS")]
+    only: Option<Vec<TransformationKind>>,
+    /// Skip these transformations, overriding the configuration
+    #[arg(
+        long = "skip",
+        value_enum,
+        value_delimiter = ',',
+        value_name = "KINDS",
+        conflicts_with = "only"
+    )]
+    skip: Option<Vec<TransformationKind>>,
+    /// Only process files changed since this git ref, or listed in the file given as @<path>
+    #[arg(long = "since", conflicts_with = "stdin_filename")]
+    since: Option<String>,
+    /// Also process the files included with {$I} or {$INCLUDE} directives
+    #[arg(long = "follow-includes", conflicts_with = "stdin_filename")]
+    follow_includes: bool,
+    /// With --multi, show a progress bar on stderr when it is a terminal
+    #[arg(long = "progress")]
+    progress: bool,
+    /// Number of files processed at once (defaults to $RAYON_NUM_THREADS, or 1)
+    #[arg(long = "jobs", short = 'j', value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+    /// Fail files with parse errors instead of formatting around them
+    #[arg(long = "strict-parse")]
+    strict_parse: bool,
+}
+
+impl FileArgs {
+    /// Fill the file, config, and transformation fields of `base` from these options
+    fn into_arguments(self, base: Arguments, config_search_root: Option<&Path>) -> Arguments {
+        let filenames = self.filename;
+        let filename = self
+            .stdin_filename
+            .clone()
+            .or_else(|| filenames.first().cloned())
+            .unwrap_or_default();
+
+        // If --config was not provided, try to find dfixxer.toml upward from the file's directory
+        let config_path = self
+            .config
+            .or_else(|| find_config_for_filename(&filename, config_search_root));
+
+        Arguments {
+            filename,
+            filenames,
+            config_path,
+            multi: self.multi,
+            allow_empty: self.allow_empty,
+            stdin: self.stdin_filename.is_some(),
+            range: byte_range(self.range_start, self.range_end),
+            transformation_filter: transformation_filter(self.only, self.skip),
+            since: self.since,
+            follow_includes: self.follow_includes,
+            progress: self.progress,
+            jobs: resolve_jobs(self.jobs),
+            strict_parse: self.strict_parse,
+            ..base
+        }
+    }
+}
+
+/// `check` options, which `--check` accepts as well
+#[derive(Args, Debug)]
+struct CheckArgs {
+    #[command(flatten)]
+    files: FileArgs,
+    /// Print only the paths of files that would change; exit code is 1 if any would
+    #[arg(long = "list-different")]
+    list_different: bool,
+    /// Color removed and added lines in the diff
+    #[arg(long = "color", value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+    /// Number of unchanged lines to show around each diff hunk
+    #[arg(long = "diff-context", value_name = "N", default_value_t = DEFAULT_DIFF_CONTEXT)]
+    diff_context: usize,
+    /// Print the reason for each suggested change after the diff
+    #[arg(long = "explain", conflicts_with = "list_different")]
+    explain: bool,
+    /// Also print lines indented with tabs and spaces mixed, or with the character
+    /// `indentation` does not use; nothing is changed for them
+    #[arg(long = "report-indentation", conflicts_with = "list_different")]
+    report_indentation: bool,
+    /// Report changes as diffs or as a single JSON object
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        conflicts_with_all = ["stdin_filename", "list_different", "explain", "report_indentation"]
+    )]
+    format: OutputFormat,
+    /// After reporting, write the changes to the files like `update`
+    #[arg(long = "apply", conflicts_with = "stdin_filename")]
+    apply: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum CliCommand {
     /// Update a file using configuration rules
-    Update {
-        /// The filename to update
-        filename: String,
-        /// Path to the configuration file
-        #[arg(long = "config")]
-        config: Option<String>,
-        /// Process multiple files using glob patterns
-        #[arg(long = "multi")]
-        multi: bool,
-    },
+    Update(FileArgs),
     /// Check a file and show what would be changed without modifying it
-    Check {
-        /// The filename to check
-        filename: String,
-        /// Path to the configuration file
-        #[arg(long = "config")]
-        config: Option<String>,
-        /// Process multiple files using glob patterns
-        #[arg(long = "multi")]
-        multi: bool,
-    },
+    Check(CheckArgs),
     /// Initialize configuration for a file
     InitConfig {
         /// The filename to initialize configuration for
         filename: String,
+        /// Overwrite the configuration file if it already exists
+        #[arg(long = "force", visible_alias = "overwrite-config")]
+        force: bool,
+        /// Write a commented template instead of every default value
+        #[arg(long = "template")]
+        template: bool,
     },
     /// Parse a file and print its AST
     Parse {
-        /// The filename to parse
-        filename: String,
+        /// The filenames to parse, or glob patterns with --multi
+        #[arg(required = true)]
+        filename: Vec<String>,
+        /// Path to the configuration file
+        #[arg(long = "config")]
+        config: Option<String>,
         /// Process multiple files using glob patterns
         #[arg(long = "multi")]
         multi: bool,
+        /// With --multi, exit successfully when the patterns match no files
+        #[arg(long = "allow-empty", requires = "multi")]
+        allow_empty: bool,
+        /// Also print each node's byte offsets
+        #[arg(long = "show-bytes")]
+        show_bytes: bool,
     },
     /// Parse a file and print detailed debug information
     ParseDebug {
-        /// The filename to parse with debug output
-        filename: String,
+        /// The filenames to parse with debug output, or glob patterns with --multi
+        #[arg(required = true)]
+        filename: Vec<String>,
+        /// Path to the configuration file
+        #[arg(long = "config")]
+        config: Option<String>,
         /// Process multiple files using glob patterns
         #[arg(long = "multi")]
         multi: bool,
+        /// With --multi, exit successfully when the patterns match no files
+        #[arg(long = "allow-empty", requires = "multi")]
+        allow_empty: bool,
+        /// Only print sections that end on or after this 1-based line
+        #[arg(long = "from-line")]
+        from_line: Option<usize>,
+        /// Only print sections that start on or before this 1-based line
+        #[arg(long = "to-line")]
+        to_line: Option<usize>,
+    },
+    /// Print the configuration that applies to a file as TOML
+    PrintConfig {
+        /// The file whose configuration should be printed
+        filename: String,
+        /// Path to the configuration file
+        #[arg(long = "config")]
+        config: Option<String>,
     },
     /// Print version information
     Version,
+    /// Answer LSP formatting requests over stdin and stdout
+    Serve {
+        /// Path to the configuration file
+        #[arg(long = "config")]
+        config: Option<String>,
+    },
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions {
+        /// The shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Environment variable that sets the config search root when `--config-search-root` is absent
+pub const CONFIG_SEARCH_ROOT_ENV: &str = "DFIXXER_ROOT";
+
+/// Environment variable that sets the job count when `--jobs` is absent
+pub const JOBS_ENV: &str = "RAYON_NUM_THREADS";
+
+/// The `--jobs` value, else a positive `$RAYON_NUM_THREADS`, else one file at a time
+fn resolve_jobs(jobs: Option<NonZeroUsize>) -> usize {
+    jobs.or_else(|| env::var(JOBS_ENV).ok()?.trim().parse().ok())
+        .map_or(1, NonZeroUsize::get)
+}
+
+fn is_same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
 }
 
 /// Find a configuration file named 'dfixxer.toml' starting from the
 /// directory of the provided filename and walking up parent directories.
+/// The walk stops after `search_root` or after a directory containing `.git`.
 /// Returns the first matching absolute or relative path as a String if found.
-pub fn find_config_for_filename(filename: &str) -> Option<String> {
+pub fn find_config_for_filename(filename: &str, search_root: Option<&Path>) -> Option<String> {
     let file_path = Path::new(filename);
     // Start from the file's directory if available, else current working directory
     let mut dir: PathBuf = file_path
@@ -128,6 +515,10 @@ pub fn find_config_for_filename(filename: &str) -> Option<String> {
         if candidate.is_file() {
             return Some(candidate.to_string_lossy().to_string());
         }
+        // Never escape the configured root or the enclosing repository
+        if search_root.is_some_and(|root| is_same_dir(&dir, root)) || dir.join(".git").exists() {
+            break;
+        }
         // Walk up to parent; stop if at filesystem root or no parent
         if let Some(parent) = dir.parent() {
             // If parent is the same as current (possible at root), break to avoid infinite loop
@@ -142,15 +533,119 @@ pub fn find_config_for_filename(filename: &str) -> Option<String> {
     None
 }
 
+/// Combine optional range bounds; a missing bound extends to the start or end of the file.
+fn byte_range(range_start: Option<usize>, range_end: Option<usize>) -> Option<(usize, usize)> {
+    if range_start.is_none() && range_end.is_none() {
+        return None;
+    }
+    Some((range_start.unwrap_or(0), range_end.unwrap_or(usize::MAX)))
+}
+
+/// Combine optional 1-based line bounds; a missing bound extends to the first or last line.
+fn line_range(from_line: Option<usize>, to_line: Option<usize>) -> Option<(usize, usize)> {
+    if from_line.is_none() && to_line.is_none() {
+        return None;
+    }
+    Some((from_line.unwrap_or(1), to_line.unwrap_or(usize::MAX)))
+}
+
+/// Read the newline-separated entries of a response file.
+/// Blank lines are skipped and a trailing `\r` is dropped.
+fn read_response_file(path: &str) -> Result<Vec<String>, DFixxerError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        DFixxerError::IoError(std::io::Error::new(
+            e.kind(),
+            format!("Failed to read response file '{}': {}", path, e),
+        ))
+    })?;
+    Ok(contents
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Replace each `@path` argument with the entries of that response file.
+/// Returns whether any file was read.
+fn expand_response_files(args: Vec<String>) -> Result<(Vec<String>, bool), DFixxerError> {
+    let mut expanded: Vec<String> = Vec::with_capacity(args.len());
+    let mut read_any = false;
+    for (index, arg) in args.into_iter().enumerate() {
+        // The program name is never a response file, and `--since @path` names a change list
+        let Some(path) = arg.strip_prefix('@').filter(|path| {
+            index > 0 && !path.is_empty() && expanded.last().is_none_or(|prev| prev != "--since")
+        }) else {
+            expanded.push(arg);
+            continue;
+        };
+        expanded.extend(read_response_file(path)?);
+        read_any = true;
+    }
+    Ok((expanded, read_any))
+}
+
+/// Paths changed since a git ref, relative to the current directory, from
+/// `git diff --name-only --relative <ref>`. A `@<path>` value reads the paths from that file
+/// instead, in the response file format, so git is not needed.
+pub fn changed_files_since(since: &str) -> Result<Vec<String>, DFixxerError> {
+    if let Some(path) = since.strip_prefix('@') {
+        return read_response_file(path);
+    }
+    let output = std::process::Command::new("git")
+        .args(["diff", "--name-only", "--relative", since])
+        .output()
+        .map_err(|e| {
+            DFixxerError::InvalidArgs(format!(
+                "--since requires git, which could not be run: {}",
+                e
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(DFixxerError::InvalidArgs(format!(
+            "git diff --name-only {} failed: {}",
+            since,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
 pub fn parse_args(args: Vec<String>) -> Result<Arguments, DFixxerError> {
+    let (args, from_response_file) = expand_response_files(args)?;
+
+    let mut arguments = parse_cli(args)?;
+    // Paths read from a response file are processed like --multi patterns
+    if from_response_file {
+        match arguments.command {
+            Command::UpdateFile
+            | Command::CheckFile
+            | Command::CheckAndUpdate
+            | Command::Parse
+            | Command::ParseDebug => {
+                if arguments.stdin || arguments.range.is_some() {
+                    return Err(DFixxerError::InvalidArgs(
+                        "Response files cannot be combined with --stdin-filename, --range-start, or --range-end".to_string(),
+                    ));
+                }
+                arguments.multi = true;
+            }
+            _ => {}
+        }
+    }
+    Ok(arguments)
+}
+
+fn parse_cli(args: Vec<String>) -> Result<Arguments, DFixxerError> {
     // Parse arguments using clap
-    let cli = match Cli::try_parse_from(&args) {
+    let cli = match Cli::try_parse_checked(&args) {
         Ok(cli) => cli,
         Err(e) => {
             // Check if this is a help or version request (which should exit with code 0)
-            if e.kind() == clap::error::ErrorKind::DisplayHelp
-                || e.kind() == clap::error::ErrorKind::DisplayVersion
-            {
+            if e.kind() == ErrorKind::DisplayHelp || e.kind() == ErrorKind::DisplayVersion {
                 // Print the help/version and exit successfully
                 print!("{}", e);
                 std::process::exit(0);
@@ -161,119 +656,262 @@ pub fn parse_args(args: Vec<String>) -> Result<Arguments, DFixxerError> {
         }
     };
 
-    match cli.command {
-        CliCommand::Update {
-            filename,
-            config,
-            multi,
-        } => {
-            // If --config was not provided, try to find dfixxer.toml upward from the file's directory
-            let config_path = match config {
-                Some(path) => Some(path),
-                None => find_config_for_filename(&filename),
-            };
+    let log_level = cli.log_level.clone();
+    let verbose_timing = cli.verbose_timing;
+    let config_search_root = cli
+        .config_search_root
+        .clone()
+        .or_else(|| env::var_os(CONFIG_SEARCH_ROOT_ENV).map(PathBuf::from));
+    let base = |command| Arguments::new(command, log_level.clone(), verbose_timing);
 
-            Ok(Arguments {
-                command: Command::UpdateFile,
-                filename,
-                config_path,
-                log_level: cli.log_level,
-                multi,
-            })
+    match cli.resolved_command() {
+        CliCommand::Update(files) => {
+            Ok(files.into_arguments(base(Command::UpdateFile), config_search_root.as_deref()))
         }
-        CliCommand::Check {
-            filename,
-            config,
-            multi,
-        } => {
-            // If --config was not provided, try to find dfixxer.toml upward from the file's directory
-            let config_path = match config {
-                Some(path) => Some(path),
-                None => find_config_for_filename(&filename),
+        CliCommand::Check(check) => {
+            let command = if check.apply {
+                Command::CheckAndUpdate
+            } else {
+                Command::CheckFile
             };
-
             Ok(Arguments {
-                command: Command::CheckFile,
-                filename,
-                config_path,
-                log_level: cli.log_level,
-                multi,
+                list_different: check.list_different,
+                color: check.color,
+                diff_context: check.diff_context,
+                explain: check.explain,
+                report_indentation: check.report_indentation,
+                format: check.format,
+                ..check
+                    .files
+                    .into_arguments(base(command), config_search_root.as_deref())
             })
         }
-        CliCommand::InitConfig { filename } => Ok(Arguments {
-            command: Command::InitConfig,
+        CliCommand::InitConfig {
             filename,
-            config_path: None,
-            log_level: cli.log_level,
-            multi: false, // InitConfig doesn't support multi
+            force,
+            template,
+        } => Ok(Arguments {
+            filenames: vec![filename.clone()],
+            filename,
+            force,
+            template,
+            ..base(Command::InitConfig)
         }),
-        CliCommand::Parse { filename, multi } => Ok(Arguments {
-            command: Command::Parse,
+        CliCommand::Parse {
             filename,
-            config_path: None,
-            log_level: cli.log_level,
+            config,
             multi,
+            allow_empty,
+            show_bytes,
+        } => Ok(Arguments {
+            config_path: config
+                .or_else(|| find_config_for_filename(&filename[0], config_search_root.as_deref())),
+            filename: filename[0].clone(),
+            filenames: filename,
+            multi,
+            allow_empty,
+            show_bytes,
+            ..base(Command::Parse)
         }),
-        CliCommand::ParseDebug { filename, multi } => Ok(Arguments {
-            command: Command::ParseDebug,
+        CliCommand::ParseDebug {
             filename,
-            config_path: None,
-            log_level: cli.log_level,
+            config,
+            multi,
+            allow_empty,
+            from_line,
+            to_line,
+        } => Ok(Arguments {
+            config_path: config
+                .or_else(|| find_config_for_filename(&filename[0], config_search_root.as_deref())),
+            filename: filename[0].clone(),
+            filenames: filename,
             multi,
+            allow_empty,
+            line_range: line_range(from_line, to_line),
+            ..base(Command::ParseDebug)
         }),
-        CliCommand::Version => Ok(Arguments {
-            command: Command::Version,
-            filename: String::new(), // No filename needed for version command
-            config_path: None,
-            log_level: cli.log_level,
-            multi: false,
+        CliCommand::PrintConfig { filename, config } => Ok(Arguments {
+            config_path: config
+                .or_else(|| find_config_for_filename(&filename, config_search_root.as_deref())),
+            filenames: vec![filename.clone()],
+            filename,
+            ..base(Command::PrintConfig)
+        }),
+        CliCommand::Version => Ok(base(Command::Version)),
+        // Documents are named by the client, so the config is looked up per request
+        CliCommand::Serve { config } => Ok(Arguments {
+            config_path: config,
+            config_search_root,
+            ..base(Command::Serve)
         }),
+        CliCommand::Completions { shell } => Ok(base(Command::Completions(shell))),
     }
 }
 
-/// Expand a filename pattern using glob if needed
-/// If multi is false, returns the filename as-is in a vector
-/// If multi is true, expands the pattern using glob and returns all matching files
-pub fn expand_filename_pattern(filename: &str, multi: bool) -> Result<Vec<String>, DFixxerError> {
-    if !multi {
-        // Single file mode - return as-is
-        return Ok(vec![filename.to_string()]);
+/// `--only` and `--skip` conflict, so at most one of them is set
+fn transformation_filter(
+    only: Option<Vec<TransformationKind>>,
+    skip: Option<Vec<TransformationKind>>,
+) -> Option<TransformationFilter> {
+    only.map(TransformationFilter::Only)
+        .or(skip.map(TransformationFilter::Skip))
+}
+
+/// Write the completion script for `shell` to `out`
+pub fn write_completions(shell: Shell, out: &mut dyn std::io::Write) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
+
+/// Expand the first `{a,b}` group in a pattern into one pattern per alternative, recursively,
+/// since the glob crate has no brace support. Unbalanced braces are left as written.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    let mut depth = 0usize;
+    let mut alternative_start = open + 1;
+    let mut alternatives = Vec::new();
+    for (idx, ch) in pattern[open..].char_indices() {
+        let idx = open + idx;
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    alternatives.push(&pattern[alternative_start..idx]);
+                    let (prefix, suffix) = (&pattern[..open], &pattern[idx + 1..]);
+                    return alternatives
+                        .into_iter()
+                        .flat_map(|alternative| {
+                            expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+                        })
+                        .collect();
+                }
+            }
+            ',' if depth == 1 => {
+                alternatives.push(&pattern[alternative_start..idx]);
+                alternative_start = idx + 1;
+            }
+            _ => {}
+        }
     }
+    vec![pattern.to_string()]
+}
 
-    // Multi mode - use glob to expand pattern
-    match glob::glob(filename) {
-        Ok(paths) => {
-            let mut files = Vec::new();
-            for entry in paths {
-                match entry {
-                    Ok(path) => {
-                        if let Some(path_str) = path.to_str() {
-                            files.push(path_str.to_string());
-                        }
-                    }
-                    Err(e) => {
-                        return Err(DFixxerError::IoError(std::io::Error::other(format!(
-                            "Error processing glob pattern '{}': {}",
-                            filename, e
-                        ))));
+/// Expand one glob pattern (after brace expansion) into the matching file paths
+fn expand_glob(filename: &str, files: &mut BTreeSet<String>) -> Result<(), DFixxerError> {
+    for pattern in expand_braces(filename) {
+        let paths = glob::glob(&pattern).map_err(|e| {
+            DFixxerError::IoError(std::io::Error::other(format!(
+                "Invalid glob pattern '{}': {}",
+                filename, e
+            )))
+        })?;
+        for entry in paths {
+            match entry {
+                Ok(path) => {
+                    if let Some(path_str) = path.to_str() {
+                        files.insert(path_str.to_string());
                     }
                 }
+                Err(e) => {
+                    return Err(DFixxerError::IoError(std::io::Error::other(format!(
+                        "Error processing glob pattern '{}': {}",
+                        filename, e
+                    ))));
+                }
             }
+        }
+    }
+    Ok(())
+}
 
-            if files.is_empty() {
-                return Err(DFixxerError::IoError(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    format!("No files found matching pattern: {}", filename),
-                )));
-            }
+/// Expand filename patterns using glob if needed
+/// If multi is false, returns the filenames as given
+/// If multi is true, expands every pattern (including `{a,b}` braces) using glob and returns
+/// the sorted, deduplicated union of all matching files. Matching nothing is an error unless
+/// `allow_empty` is set, in which case an empty list is returned.
+pub fn expand_filename_patterns(
+    filenames: &[String],
+    multi: bool,
+    allow_empty: bool,
+) -> Result<Vec<String>, DFixxerError> {
+    if !multi {
+        // Single file mode - return as-is
+        return Ok(filenames.to_vec());
+    }
 
-            // Sort files for consistent ordering
-            files.sort();
-            Ok(files)
-        }
-        Err(e) => Err(DFixxerError::IoError(std::io::Error::other(format!(
-            "Invalid glob pattern '{}': {}",
-            filename, e
-        )))),
+    // Multi mode - a BTreeSet keeps files sorted and drops paths matched by several patterns
+    let mut files = BTreeSet::new();
+    for filename in filenames {
+        expand_glob(filename, &mut files)?;
+    }
+
+    if files.is_empty() && allow_empty {
+        log::info!("No files found matching pattern: {}", filenames.join(" "));
+    } else if files.is_empty() {
+        return Err(DFixxerError::IoError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("No files found matching pattern: {}", filenames.join(" ")),
+        )));
+    }
+
+    Ok(files.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_response_files_reads_one_path_per_line() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("dfixxer_response_file_{}", std::process::id()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let response_file = temp_dir.join("files.txt");
+        std::fs::write(&response_file, "a.pas\r\nsrc/b.pas\n\nc d.pas\n").unwrap();
+
+        let args = vec![
+            "dfixxer".to_string(),
+            "check".to_string(),
+            format!("@{}", response_file.display()),
+            "--list-different".to_string(),
+        ];
+        let (expanded, read_any) = expand_response_files(args).unwrap();
+        assert!(read_any);
+        assert_eq!(
+            expanded,
+            vec![
+                "dfixxer",
+                "check",
+                "a.pas",
+                "src/b.pas",
+                "c d.pas",
+                "--list-different"
+            ]
+        );
+
+        let missing = format!("@{}", temp_dir.join("missing.txt").display());
+        assert!(expand_response_files(vec!["dfixxer".to_string(), missing]).is_err());
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(expand_braces("src/*.pas"), vec!["src/*.pas"]);
+        assert_eq!(expand_braces("{a,b}.pas"), vec!["a.pas", "b.pas"]);
+        assert_eq!(
+            expand_braces("{src,lib}/*.{pas,dpr}"),
+            vec!["src/*.pas", "src/*.dpr", "lib/*.pas", "lib/*.dpr"]
+        );
+        assert_eq!(
+            expand_braces("{a,{b,c}}.pas"),
+            vec!["a.pas", "b.pas", "c.pas"]
+        );
+        assert_eq!(expand_braces("{a,b.pas"), vec!["{a,b.pas"]);
     }
 }
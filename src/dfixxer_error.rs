@@ -5,7 +5,84 @@ pub enum DFixxerError {
     InvalidArgs(String),
     IoError(std::io::Error),
     ParseError(String),
+    /// Syntax error at a 1-based line/column, optionally tied to a file
+    SyntaxError {
+        filename: Option<String>,
+        byte_offset: usize,
+        line: usize,
+        column: usize,
+        near: String,
+    },
     ConfigError(String),
+    /// Invalid configuration file content at a 1-based line/column
+    InvalidConfig {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Source the configuration is set to reject, at a 1-based line, optionally tied to a file
+    PolicyError {
+        filename: Option<String>,
+        line: usize,
+        message: String,
+    },
+}
+
+/// Exit code when nothing needs to change
+pub const EXIT_CLEAN: i32 = 0;
+/// Exit code when `check` would change files
+pub const EXIT_CHANGES: i32 = 1;
+/// Exit code for invalid arguments or configuration
+pub const EXIT_USAGE_ERROR: i32 = 2;
+/// Exit code for failures reading or writing files
+pub const EXIT_IO_ERROR: i32 = 3;
+/// Exit code for sources that cannot be parsed
+pub const EXIT_PARSE_ERROR: i32 = 4;
+/// Exit code when a run that would otherwise exit with `EXIT_CLEAN` loaded a configuration
+/// file with deprecated keys, so CI notices before the keys are removed
+pub const EXIT_DEPRECATED_CONFIG: i32 = 5;
+/// Exit code for sources that parse but break a rule the configuration enforces, such as
+/// `uses_comment_policy = "Error"`
+pub const EXIT_POLICY_ERROR: i32 = 6;
+
+impl DFixxerError {
+    /// Process exit code for this error, so failures are distinct from "changes needed".
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DFixxerError::InvalidArgs(_)
+            | DFixxerError::ConfigError(_)
+            | DFixxerError::InvalidConfig { .. } => EXIT_USAGE_ERROR,
+            DFixxerError::IoError(_) => EXIT_IO_ERROR,
+            DFixxerError::ParseError(_) | DFixxerError::SyntaxError { .. } => EXIT_PARSE_ERROR,
+            DFixxerError::PolicyError { .. } => EXIT_POLICY_ERROR,
+        }
+    }
+
+    /// Attach a filename to errors that carry a source position.
+    pub fn with_filename(self, filename: &str) -> Self {
+        match self {
+            DFixxerError::SyntaxError {
+                byte_offset,
+                line,
+                column,
+                near,
+                ..
+            } => DFixxerError::SyntaxError {
+                filename: Some(filename.to_string()),
+                byte_offset,
+                line,
+                column,
+                near,
+            },
+            DFixxerError::PolicyError { line, message, .. } => DFixxerError::PolicyError {
+                filename: Some(filename.to_string()),
+                line,
+                message,
+            },
+            other => other,
+        }
+    }
 }
 
 impl fmt::Display for DFixxerError {
@@ -14,7 +91,35 @@ impl fmt::Display for DFixxerError {
             DFixxerError::InvalidArgs(msg) => write!(f, "{}", msg),
             DFixxerError::IoError(err) => write!(f, "Failed to read file: {}", err),
             DFixxerError::ParseError(msg) => write!(f, "{}", msg),
+            DFixxerError::SyntaxError {
+                filename,
+                line,
+                column,
+                near,
+                ..
+            } => {
+                if let Some(filename) = filename {
+                    write!(f, "{}:", filename)?;
+                }
+                write!(f, "{}:{}: parse error near '{}'", line, column, near)
+            }
             DFixxerError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            DFixxerError::InvalidConfig {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path, line, column, message),
+            DFixxerError::PolicyError {
+                filename,
+                line,
+                message,
+            } => {
+                if let Some(filename) = filename {
+                    write!(f, "{}:", filename)?;
+                }
+                write!(f, "{}: {}", line, message)
+            }
         }
     }
 }
@@ -26,3 +131,46 @@ impl From<std::io::Error> for DFixxerError {
         DFixxerError::IoError(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_per_variant() {
+        assert_eq!(
+            DFixxerError::InvalidArgs("bad".to_string()).exit_code(),
+            EXIT_USAGE_ERROR
+        );
+        assert_eq!(
+            DFixxerError::ConfigError("bad".to_string()).exit_code(),
+            EXIT_USAGE_ERROR
+        );
+        assert_eq!(
+            DFixxerError::IoError(std::io::Error::from(std::io::ErrorKind::NotFound)).exit_code(),
+            EXIT_IO_ERROR
+        );
+        assert_eq!(
+            DFixxerError::ParseError("bad".to_string()).exit_code(),
+            EXIT_PARSE_ERROR
+        );
+        let syntax_error = DFixxerError::SyntaxError {
+            filename: None,
+            byte_offset: 0,
+            line: 1,
+            column: 1,
+            near: "x".to_string(),
+        };
+        assert_eq!(syntax_error.exit_code(), EXIT_PARSE_ERROR);
+        let policy_error = DFixxerError::PolicyError {
+            filename: None,
+            line: 3,
+            message: "bad".to_string(),
+        };
+        assert_eq!(policy_error.exit_code(), EXIT_POLICY_ERROR);
+        assert_eq!(
+            policy_error.with_filename("a.pas").to_string(),
+            "a.pas:3: bad"
+        );
+    }
+}
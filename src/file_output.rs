@@ -0,0 +1,187 @@
+// Crash-safe replacement of formatted files
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Temporary file next to `path`, so the final rename stays on one file system
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".tmp-{}", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Move `from` over the existing file `to`
+#[cfg(not(windows))]
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::rename(from, to)
+}
+
+/// Move `from` over the existing file `to`. Windows renames over an existing file too, but
+/// fails while another process such as a virus scanner briefly holds `to` open, so a few
+/// retries are made before giving up.
+#[cfg(windows)]
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    let mut retries = 0;
+    loop {
+        match std::fs::rename(from, to) {
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied && retries < 5 => {
+                retries += 1;
+                std::thread::sleep(std::time::Duration::from_millis(20 * retries));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Replace the contents of `path` with what `write` streams into a buffered writer.
+/// The output goes to `<filename>.tmp-<pid>` in the same directory, which takes over the
+/// original's permissions and is renamed over `path` only once it is complete and synced,
+/// so a failed or interrupted write leaves the original file untouched. The temporary file
+/// is removed when writing fails. A read-only `path` is refused, as a direct write would be.
+/// A symbolic link is resolved first, so its target is replaced and the link is kept.
+pub fn write_file_atomically<F>(path: &Path, write: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+{
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) => path,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(err) => return Err(err),
+    };
+    let path = path.as_path();
+    let permissions = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions().readonly() => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", path.display()),
+            ));
+        }
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+    let temp_path = temp_path_for(path);
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(&temp_path)?);
+        write(&mut out)?;
+        out.flush()?;
+        out.get_ref().sync_all()?;
+        drop(out);
+        if let Some(permissions) = permissions {
+            std::fs::set_permissions(&temp_path, permissions)?;
+        }
+        replace_file(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements::{TextReplacement, apply_replacements_to_string, output_pieces};
+    use crate::source_encoding::{FileEncoding, encode_source, write_source_pieces};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dfixxer_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_streamed_output_matches_in_memory_encoding() {
+        let path = temp_dir("atomic_write_ok").join("A.pas");
+        std::fs::write(&path, "unit Old;\n").unwrap();
+        let source = "unit Ünïcode;\r\nend.";
+        let replacements = [
+            TextReplacement {
+                start: 5,
+                end: 14,
+                text: "Ärger".to_string(),
+                reason: None,
+                is_final: false,
+            },
+            TextReplacement {
+                start: source.len(),
+                end: source.len(),
+                text: "\r\n".to_string(),
+                reason: None,
+                is_final: true,
+            },
+        ];
+        let file_encoding = FileEncoding {
+            encoding: encoding_rs::UTF_16LE,
+            has_bom: true,
+        };
+
+        write_file_atomically(&path, |out| {
+            write_source_pieces(out, output_pieces(source, &replacements), file_encoding)
+        })
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            encode_source(
+                &apply_replacements_to_string(source, &replacements),
+                file_encoding
+            )
+        );
+        assert!(!temp_path_for(&path).exists());
+    }
+
+    #[test]
+    fn test_interrupted_write_leaves_original_file() {
+        let path = temp_dir("atomic_write_interrupted").join("A.pas");
+        std::fs::write(&path, "unit Original;\n").unwrap();
+
+        let result = write_file_atomically(&path, |out| {
+            out.write_all(b"unit Par")?;
+            Err(std::io::Error::other("simulated crash"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "unit Original;\n");
+        assert!(!temp_path_for(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_replaced_file_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_dir("atomic_write_permissions").join("A.pas");
+        std::fs::write(&path, "unit Old;\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        write_file_atomically(&path, |out| out.write_all(b"unit New;\n")).unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "unit New;\n");
+        assert!(!temp_path_for(&path).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_is_kept_and_its_target_replaced() {
+        let dir = temp_dir("atomic_write_symlink");
+        let target = dir.join("Target.pas");
+        let link = dir.join("Link.pas");
+        std::fs::write(&target, "unit Old;\n").unwrap();
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_file_atomically(&link, |out| out.write_all(b"unit New;\n")).unwrap();
+
+        assert!(
+            std::fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "unit New;\n");
+        assert!(!temp_path_for(&target).exists());
+    }
+}
@@ -0,0 +1,64 @@
+// Report-only indentation check for `check --report-indentation`
+
+/// A line whose leading whitespace does not fit the configured `indentation`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndentationIssue {
+    /// 1-based line number
+    pub line: usize,
+    pub message: &'static str,
+}
+
+/// Find non-blank lines whose leading whitespace mixes tabs and spaces, or uses tabs where
+/// `indentation` is spaces (or spaces where it is tabs). Nothing is rewritten; the width of
+/// the indentation is not checked, since continuation lines are aligned freely.
+pub fn indentation_issues(source: &str, indentation: &str) -> Vec<IndentationIssue> {
+    let expects_tabs = !indentation.is_empty() && indentation.chars().all(|ch| ch == '\t');
+    let expects_spaces = !indentation.is_empty() && indentation.chars().all(|ch| ch == ' ');
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(index, line)| {
+            let leading = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            let has_tabs = leading.contains('\t');
+            let has_spaces = leading.contains(' ');
+            let message = if has_tabs && has_spaces {
+                "indentation mixes tabs and spaces"
+            } else if has_tabs && expects_spaces {
+                "indented with tabs, but indentation is spaces"
+            } else if has_spaces && expects_tabs {
+                "indented with spaces, but indentation is tabs"
+            } else {
+                return None;
+            };
+            Some(IndentationIssue {
+                line: index + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tab_indented_line_under_spaces_config() {
+        let source = "begin\n  Foo;\n\tBar;\n \tBaz;\n\t\nend;\n";
+        assert_eq!(
+            indentation_issues(source, "  "),
+            vec![
+                IndentationIssue {
+                    line: 3,
+                    message: "indented with tabs, but indentation is spaces",
+                },
+                IndentationIssue {
+                    line: 4,
+                    message: "indentation mixes tabs and spaces",
+                },
+            ]
+        );
+        assert_eq!(indentation_issues(source, "\t").len(), 2);
+    }
+}
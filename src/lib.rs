@@ -0,0 +1,881 @@
+//! Library interface to the dfixxer formatter.
+//!
+//! ```
+//! use dfixxer::{Options, format_source};
+//!
+//! let source = "unit A;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n";
+//! let formatted = format_source(source, &Options::default()).unwrap();
+//! assert!(formatted.contains("Foo(a, b);"));
+//! ```
+
+pub mod dfixxer_error;
+pub mod file_output;
+pub mod indentation_report;
+pub mod options;
+pub mod parser;
+pub mod performance;
+pub mod replacements;
+pub mod source_encoding;
+mod suppression;
+mod transform_annotate_end;
+mod transform_comment_style;
+mod transform_control_statement_body_wrapping;
+mod transform_indentation;
+mod transform_inherited_calls;
+mod transform_inline_local_var_definitions;
+mod transform_keyword_case;
+mod transform_local_routine_indentation;
+mod transform_local_routine_spacing;
+mod transform_one_statement_per_line;
+mod transform_procedure_section;
+mod transform_redundant_semicolons;
+mod transform_single_keyword_sections;
+mod transform_text;
+mod transform_unit_program_section;
+mod transform_uses_section;
+mod transform_var_alignment;
+mod transformer_utility;
+
+pub use dfixxer_error::DFixxerError;
+pub use options::Options;
+pub use parser::{new_parser, parse, parse_with};
+pub use performance::PerformanceCollector;
+pub use replacements::TextReplacement;
+
+use options::{CommentStyle, KeywordCase, ParsePolicy, SpaceOperation, TextChangeOptions};
+use parser::{
+    ControlStatementBodyWrappingContext, ControlStatementKind, ParseWithContextsAndTimingsResult,
+    parse_with_contexts_and_timings,
+};
+use performance::TEXT_TRANSFORMATION_STAGE;
+use replacements::{
+    apply_replacements_to_string, clamp_range_to_source, compute_source_sections,
+    is_noop_replacement, is_replacement_within_range, output_pieces, replace_output_prefix,
+    replace_output_suffix, retain_valid_replacements,
+};
+use std::time::Instant;
+use suppression::{SuppressionMarkers, collect_suppression_context, has_skip_file_marker};
+use transform_annotate_end::transform_annotate_end;
+use transform_comment_style::transform_comment_style;
+use transform_control_statement_body_wrapping::transform_control_statement_body_wrapping;
+use transform_indentation::transform_indentation;
+use transform_inherited_calls::transform_inherited_calls;
+use transform_inline_local_var_definitions::transform_inline_local_var_definitions;
+use transform_keyword_case::transform_keyword_case;
+use transform_local_routine_indentation::transform_local_routine_indentation;
+use transform_local_routine_spacing::transform_local_routine_spacing;
+use transform_one_statement_per_line::transform_one_statement_per_line;
+use transform_procedure_section::transform_procedure_section;
+use transform_redundant_semicolons::transform_redundant_semicolons;
+use transform_single_keyword_sections::transform_single_keyword_section;
+use transform_unit_program_section::transform_unit_program_section;
+use transform_uses_section::transform_uses_section;
+use transform_var_alignment::transform_var_alignment;
+
+/// Format Pascal source with `options` and return the updated text.
+///
+/// Runs the same pipeline as `dfixxer update` on the whole source, without reading
+/// configuration files or touching the file system.
+pub fn format_source(source: &str, options: &Options) -> Result<String, DFixxerError> {
+    let mut timing = PerformanceCollector::new();
+    let (_, updated_source, _) = process_source(
+        "<source>",
+        source.to_string(),
+        options.clone(),
+        None,
+        &mut timing,
+    )?;
+    Ok(updated_source)
+}
+
+fn filtered_control_statement_context<F>(
+    context: &ControlStatementBodyWrappingContext,
+    predicate: F,
+) -> ControlStatementBodyWrappingContext
+where
+    F: Fn(&ControlStatementKind) -> bool,
+{
+    ControlStatementBodyWrappingContext {
+        candidates: context
+            .candidates
+            .iter()
+            .filter(|candidate| predicate(&candidate.kind))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Whether an enabled text rule reads the parser's spacing context: `<` / `>` tell generics
+/// from comparisons, `+` / `-` tell signs from operators, and `:` tells labels from
+/// declarations. Without the parse tree they would space every such character as a binary
+/// operator, so the parse is only skipped when all of them are `NoChange`.
+fn text_changes_need_parse_tree(text_changes: &TextChangeOptions) -> bool {
+    [
+        &text_changes.lt,
+        &text_changes.gt,
+        &text_changes.add,
+        &text_changes.sub,
+        &text_changes.unary_sign,
+        &text_changes.colon,
+        &text_changes.label_colon,
+    ]
+    .into_iter()
+    .any(|operation| *operation != SpaceOperation::NoChange)
+}
+
+/// Whether any enabled transformation reads the parse tree, or the strict parse policy needs
+/// it to find errors. The remaining text rules are lexical, so a lenient config that only
+/// enables those never runs tree-sitter.
+fn needs_parse_tree(options: &Options) -> bool {
+    let transformations = &options.transformations;
+    options.parse_policy == ParsePolicy::Strict
+        || (transformations.enable_text_transformations
+            && text_changes_need_parse_tree(&options.text_changes))
+        || transformations.enable_uses_section
+        || transformations.enable_unit_program_section
+        || transformations.enable_single_keyword_sections
+        || transformations.enable_procedure_section
+        || transformations.enable_local_routine_spacing
+        || transformations.enable_local_routine_indentation
+        || transformations.enable_inline_local_var_definitions
+        || transformations.enable_for_body_wrapping
+        || transformations.enable_while_body_wrapping
+        || transformations.enable_if_body_wrapping
+        || transformations.enable_inherited_call_expansion
+        || transformations.align_declarations
+        || transformations.reindent_blocks
+        || transformations.annotate_end
+        || transformations.remove_redundant_semicolons
+        || transformations.one_statement_per_line
+        || transformations.preserve_unparsed_regions
+        || transformations.keyword_case != KeywordCase::Preserve
+        || transformations.comment_style != CommentStyle::Preserve
+}
+
+/// 1-based line and column (in characters) of byte `offset` in `source`
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Process source text; `filename` is used for config pattern matching and messages.
+/// When `range` is given, only changes inside that byte range are kept.
+/// Returns the source, the updated source, and the replacements applied to the source.
+pub fn process_source(
+    filename: &str,
+    source: String,
+    options: Options,
+    range: Option<(usize, usize)>,
+    timing: &mut PerformanceCollector,
+) -> Result<(String, String, Vec<TextReplacement>), DFixxerError> {
+    let (source, replacements) =
+        process_source_replacements(filename, source, options, range, timing)?;
+    let updated_source = if replacements.is_empty() {
+        source.clone()
+    } else {
+        timing.time_operation("Applying replacements (in-memory)", || {
+            apply_replacements_to_string(&source, &replacements)
+        })
+    };
+    Ok((source, updated_source, replacements))
+}
+
+/// Process source text like `process_source`, returning only the source and the sorted,
+/// non-overlapping replacements that turn it into the updated source. File-level changes such
+/// as the trailing newline are among them, so the updated source can be written out piece by
+/// piece with `replacements::output_pieces` instead of being built in memory.
+pub fn process_source_replacements(
+    filename: &str,
+    source: String,
+    mut options: Options,
+    range: Option<(usize, usize)>,
+    timing: &mut PerformanceCollector,
+) -> Result<(String, Vec<TextReplacement>), DFixxerError> {
+    if has_skip_file_marker(&source, &options.skip_file_marker) {
+        log::info!(
+            "Skipping '{}': it starts with the '{}' marker",
+            filename,
+            options.skip_file_marker
+        );
+        return Ok((source, Vec::new()));
+    }
+
+    // `Auto` follows the line ending already used by the file
+    options.line_ending = options.line_ending.resolve_for_source(&source);
+
+    let suppression_markers = SuppressionMarkers {
+        off: &options.format_off_marker,
+        on: &options.format_on_marker,
+    };
+    let suppression_context = timing.time_operation("Inline suppression scan", || {
+        collect_suppression_context(&source, &suppression_markers)
+    });
+    for warning in &suppression_context.warnings {
+        let message = format!(
+            "{}:{}: {}",
+            filename,
+            warning.line,
+            warning.message(&suppression_markers)
+        );
+        log::warn!("{}", message);
+        eprintln!("Warning: {}", message);
+    }
+
+    // Time parsing; without it every context is empty and the text pass covers the whole source
+    let parsed = needs_parse_tree(&options);
+    let ParseWithContextsAndTimingsResult {
+        parse_result,
+        spacing_context,
+        inherited_expansion_context,
+        local_routine_spacing_context,
+        control_statement_body_wrapping_context,
+        inline_local_var_definition_context,
+        keyword_context,
+        block_indentation_context,
+        routine_end_context,
+        redundant_semicolon_context,
+        comment_context,
+        timings: parse_context_timings,
+    } = if parsed {
+        timing
+            .time_operation_result("Parsing", || parse_with_contexts_and_timings(&source))
+            .map_err(|err| DFixxerError::ParseError(format!("{}: {}", filename, err)))?
+    } else {
+        log::debug!(
+            "Skipping parsing of '{}': only text changes are enabled",
+            filename
+        );
+        Default::default()
+    };
+    if parsed {
+        timing.record_parse_timings(&parse_context_timings);
+    }
+    // Without a parse tree the text pass uses its lexical rules
+    let text_spacing_context = parsed.then_some(&spacing_context);
+    if !spacing_context.error_ranges.is_empty() && options.parse_policy == ParsePolicy::Strict {
+        // Only the failure path parses again, to report where the first error is
+        return Err(match parser::first_syntax_error(&source) {
+            Ok(Some(err)) | Err(err) => err.with_filename(filename),
+            Ok(None) => DFixxerError::ParseError(format!("{}: source has parse errors", filename)),
+        });
+    }
+    if !spacing_context.error_ranges.is_empty() {
+        let message = format!(
+            "Parser recovered with {} error span(s) in '{}'; text changes are skipped inside error spans.",
+            spacing_context.error_ranges.len(),
+            filename
+        );
+        log::warn!("{}", message);
+        eprintln!("Warning: {}", message);
+    }
+
+    let mut text_stats = transform_text::TextTransformationStats::default();
+
+    let transformation_start = Instant::now();
+    let mut replacements: Vec<TextReplacement> = Vec::new();
+
+    if options.transformations.enable_uses_section {
+        let uses_sections: Vec<_> = parse_result
+            .code_sections
+            .iter()
+            .filter(|code_section| parser::is_module_list_kind(&code_section.keyword.kind))
+            .collect();
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> = uses_sections
+            .iter()
+            .map(|code_section| transform_uses_section(code_section, &options, &source))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.with_filename(filename))?
+            .into_iter()
+            .flatten()
+            .collect();
+        timing.record_rule_timing(
+            "uses_section",
+            uses_sections.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_unit_program_section {
+        let unit_program_sections: Vec<_> = parse_result
+            .code_sections
+            .iter()
+            .filter(|code_section| {
+                matches!(
+                    code_section.keyword.kind,
+                    parser::Kind::Unit | parser::Kind::Program
+                )
+            })
+            .collect();
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> = unit_program_sections
+            .iter()
+            .filter_map(|code_section| {
+                transform_unit_program_section(code_section, &options, &source)
+            })
+            .collect();
+        timing.record_rule_timing(
+            "unit_program_section",
+            unit_program_sections.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_single_keyword_sections {
+        let single_keyword_sections: Vec<_> = parse_result
+            .code_sections
+            .iter()
+            .filter(|code_section| {
+                matches!(
+                    code_section.keyword.kind,
+                    parser::Kind::Interface
+                        | parser::Kind::Implementation
+                        | parser::Kind::Initialization
+                        | parser::Kind::Finalization
+                )
+            })
+            .collect();
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> = single_keyword_sections
+            .iter()
+            .filter_map(|code_section| {
+                transform_single_keyword_section(&source, code_section, &options)
+            })
+            .collect();
+        timing.record_rule_timing(
+            "single_keyword_sections",
+            single_keyword_sections.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_procedure_section {
+        let procedure_sections: Vec<_> = parse_result
+            .code_sections
+            .iter()
+            .filter(|code_section| {
+                matches!(
+                    code_section.keyword.kind,
+                    parser::Kind::ProcedureDeclaration | parser::Kind::FunctionDeclaration
+                )
+            })
+            .collect();
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> = procedure_sections
+            .iter()
+            .filter_map(|code_section| transform_procedure_section(code_section, &options, &source))
+            .collect();
+        timing.record_rule_timing(
+            "procedure_section",
+            procedure_sections.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_inherited_call_expansion {
+        let rule_start = Instant::now();
+        let rule_replacements = transform_inherited_calls(&inherited_expansion_context);
+        timing.record_rule_timing(
+            "inherited_call_expansion",
+            inherited_expansion_context.candidates.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_local_routine_indentation {
+        let rule_start = Instant::now();
+        let rule_replacements =
+            transform_local_routine_indentation(&source, &local_routine_spacing_context, &options);
+        timing.record_rule_timing(
+            "local_routine_indentation",
+            local_routine_spacing_context.blocks.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_local_routine_spacing {
+        let rule_start = Instant::now();
+        let rule_replacements =
+            transform_local_routine_spacing(&source, &local_routine_spacing_context, &options);
+        timing.record_rule_timing(
+            "local_routine_spacing",
+            local_routine_spacing_context.gaps.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_inline_local_var_definitions {
+        let rule_start = Instant::now();
+        let rule_replacements = transform_inline_local_var_definitions(
+            &source,
+            &inline_local_var_definition_context,
+            &options,
+        );
+        timing.record_rule_timing(
+            "inline_local_var_definitions",
+            inline_local_var_definition_context.routines.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_for_body_wrapping {
+        let for_context =
+            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
+                matches!(
+                    kind,
+                    ControlStatementKind::For | ControlStatementKind::Foreach
+                )
+            });
+        let rule_start = Instant::now();
+        let rule_replacements =
+            transform_control_statement_body_wrapping(&source, &for_context, &options);
+        timing.record_rule_timing(
+            "control_body_wrapping.for_foreach",
+            for_context.candidates.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_while_body_wrapping {
+        let while_context =
+            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
+                matches!(kind, ControlStatementKind::While)
+            });
+        let rule_start = Instant::now();
+        let rule_replacements =
+            transform_control_statement_body_wrapping(&source, &while_context, &options);
+        timing.record_rule_timing(
+            "control_body_wrapping.while",
+            while_context.candidates.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.enable_if_body_wrapping {
+        let if_else_context =
+            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
+                matches!(
+                    kind,
+                    ControlStatementKind::IfThen | ControlStatementKind::Else
+                )
+            });
+        let rule_start = Instant::now();
+        let rule_replacements =
+            transform_control_statement_body_wrapping(&source, &if_else_context, &options);
+        timing.record_rule_timing(
+            "control_body_wrapping.if_else",
+            if_else_context.candidates.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.align_declarations {
+        let declaration_sections: Vec<_> = parse_result
+            .code_sections
+            .iter()
+            .filter(|code_section| {
+                matches!(
+                    code_section.keyword.kind,
+                    parser::Kind::Var | parser::Kind::Const
+                )
+            })
+            .collect();
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> = declaration_sections
+            .iter()
+            .flat_map(|code_section| transform_var_alignment(code_section, &options, &source))
+            // A routine's var section rewritten by inline var definitions is not aligned
+            .filter(|alignment| {
+                !replacements.iter().any(|replacement| {
+                    replacement.start < alignment.end && alignment.start < replacement.end
+                })
+            })
+            .collect();
+        timing.record_rule_timing(
+            "var_alignment",
+            declaration_sections.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.remove_redundant_semicolons {
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> =
+            transform_redundant_semicolons(&redundant_semicolon_context)
+                .into_iter()
+                .filter(|removal| {
+                    !replacements.iter().any(|replacement| {
+                        replacement.start < removal.end && removal.start < replacement.end
+                    })
+                })
+                .collect();
+        timing.record_rule_timing(
+            "redundant_semicolons",
+            redundant_semicolon_context.ranges.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.one_statement_per_line {
+        let rule_start = Instant::now();
+        let rule_replacements: Vec<_> =
+            transform_one_statement_per_line(&source, &block_indentation_context, &options)
+                .into_iter()
+                .filter(|split| {
+                    !replacements.iter().any(|replacement| {
+                        replacement.start < split.end && split.start < replacement.end
+                    })
+                })
+                .collect();
+        timing.record_rule_timing(
+            "one_statement_per_line",
+            block_indentation_context.blocks.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.reindent_blocks {
+        let rule_start = Instant::now();
+        // Lines already rewritten by a structural rule keep that rule's indentation.
+        let rule_replacements: Vec<_> =
+            transform_indentation(&source, &block_indentation_context, &options)
+                .into_iter()
+                .filter(|indentation| {
+                    !replacements.iter().any(|replacement| {
+                        replacement.start <= indentation.end && indentation.start <= replacement.end
+                    })
+                })
+                .collect();
+        timing.record_rule_timing(
+            "indentation",
+            block_indentation_context.blocks.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.annotate_end {
+        let rule_start = Instant::now();
+        // A line end already rewritten by a structural rule is left to that rule.
+        let rule_replacements: Vec<_> = transform_annotate_end(&source, &routine_end_context)
+            .into_iter()
+            .filter(|annotation| {
+                !replacements.iter().any(|replacement| {
+                    replacement.start <= annotation.end && annotation.start <= replacement.end
+                })
+            })
+            .collect();
+        timing.record_rule_timing(
+            "annotate_end",
+            routine_end_context.routines.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.keyword_case != KeywordCase::Preserve {
+        let rule_start = Instant::now();
+        // Keywords already covered by a structural rewrite keep that rule's output.
+        let rule_replacements: Vec<_> = keyword_context
+            .reserved_word_ranges
+            .iter()
+            .filter(|(start, end)| {
+                !replacements
+                    .iter()
+                    .any(|replacement| replacement.start < *end && *start < replacement.end)
+            })
+            .filter_map(|range| {
+                transform_keyword_case(&source, *range, &options.transformations.keyword_case)
+            })
+            .collect();
+        timing.record_rule_timing(
+            "keyword_case",
+            keyword_context.reserved_word_ranges.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    if options.transformations.comment_style != CommentStyle::Preserve {
+        let rule_start = Instant::now();
+        // Comments inside a structural rewrite keep that rule's output. The converted comment
+        // still gets the text pass's comment spacing and trimming.
+        let rule_replacements: Vec<_> = comment_context
+            .ranges
+            .iter()
+            .filter(|(start, end)| {
+                !replacements
+                    .iter()
+                    .any(|replacement| replacement.start < *end && *start < replacement.end)
+            })
+            .filter_map(|range| {
+                transform_comment_style(&source, *range, &options.transformations.comment_style)
+            })
+            .collect();
+        timing.record_rule_timing(
+            "comment_style",
+            comment_context.ranges.len(),
+            rule_replacements.len(),
+            rule_start.elapsed(),
+        );
+        replacements.extend(rule_replacements);
+    }
+
+    timing.record_stage_duration("Transformation", transformation_start.elapsed());
+    retain_valid_replacements(&source, &mut replacements);
+    replacements.retain(|replacement| {
+        !suppression_context.suppresses_replacement(replacement.start, replacement.end)
+    });
+
+    // Structural rewrites cannot be partially applied, so keep only those fully in range;
+    // text transformations are clipped by excluding everything outside the range.
+    let mut text_exclusion_ranges = suppression_context.text_exclusion_ranges();
+    if options.transformations.preserve_unparsed_regions {
+        text_exclusion_ranges.extend(parser::unparsed_regions(&parse_result, source.len()));
+    }
+    let range = range.map(|(start, end)| clamp_range_to_source(&source, start, end));
+    if let Some(range) = range {
+        replacements.retain(|replacement| is_replacement_within_range(replacement, range));
+        text_exclusion_ranges.push((0, range.0));
+        text_exclusion_ranges.push((range.1, source.len()));
+    }
+
+    // Apply text transformations if enabled
+    if options.transformations.enable_text_transformations {
+        timing.time_operation(TEXT_TRANSFORMATION_STAGE, || {
+            transform_text::apply_text_transformation_to_rewrites(
+                &mut replacements,
+                &options.text_changes,
+                options.dialect,
+                text_spacing_context,
+                &mut text_stats,
+            );
+
+            // Calculate sections (gaps + existing replacements)
+            let sections = compute_source_sections(&source, &replacements, &text_exclusion_ranges);
+
+            // Apply text transformation to each section and add to replacements if there's a change
+            for section in sections {
+                let text = &source[section.start..section.end];
+                if let Some(transformation) =
+                    transform_text::apply_text_transformation_with_context_and_stats(
+                        section.start,
+                        section.end,
+                        text,
+                        &options.text_changes,
+                        options.dialect,
+                        text_spacing_context,
+                        &mut text_stats,
+                    )
+                {
+                    replacements.push(transformation);
+                } else if options.text_changes.normalize_line_endings
+                    && let Some(normalized) = transform_text::normalize_line_endings_with_stats(
+                        text,
+                        &options.line_ending,
+                        &mut text_stats,
+                    )
+                {
+                    replacements.push(TextReplacement {
+                        start: section.start,
+                        end: section.end,
+                        text: normalized,
+                        reason: Some("line endings".to_string()),
+                        is_final: true,
+                    });
+                }
+            }
+
+            // Sections and replacements cover everything but the excluded regions, which keep
+            // their line endings
+            if options.text_changes.normalize_line_endings {
+                for replacement in replacements.iter_mut() {
+                    if let Some(normalized) = transform_text::normalize_line_endings_with_stats(
+                        &replacement.text,
+                        &options.line_ending,
+                        &mut text_stats,
+                    ) {
+                        replacement.text = normalized;
+                    }
+                }
+            }
+        });
+    }
+    replacements.retain(|replacement| {
+        !suppression_context.suppresses_replacement(replacement.start, replacement.end)
+    });
+    // Identity replacements only shield their span from the text pass; they are not changes
+    replacements.retain(|replacement| !is_noop_replacement(&source, replacement));
+    replacements.sort_by_key(|replacement| replacement.start);
+
+    // File-level changes touch the end of the file, which is only allowed when it is in range.
+    // They only read the last lines of the updated source, so only those are built.
+    let file_end_in_range = range.is_none_or(|(_, end)| end == source.len());
+    if options.transformations.enable_text_transformations
+        && !options.transformations.preserve_unparsed_regions
+        && file_end_in_range
+    {
+        timing.time_operation("File-level text transformations", || {
+            let tail = output_tail(&source, &replacements);
+            if let Some(updated_tail) = transform_text::apply_file_level_text_changes_with_stats(
+                &tail,
+                &options.text_changes,
+                &options.line_ending,
+                &mut text_stats,
+            ) {
+                let unchanged = common_prefix_len(&tail, &updated_tail);
+                replace_output_suffix(
+                    &source,
+                    &mut replacements,
+                    tail.len() - unchanged,
+                    &updated_tail[unchanged..],
+                    "final newline",
+                );
+            }
+        });
+    }
+
+    // Leading blank lines sit at the start of the file, so that must be in range instead
+    let file_start_in_range = range.is_none_or(|(start, _)| start == 0);
+    if options.transformations.enable_text_transformations
+        && !options.transformations.preserve_unparsed_regions
+        && file_start_in_range
+    {
+        let head = output_head(&source, &replacements);
+        if let Some(trimmed) = transform_text::trim_leading_blank_lines_with_stats(
+            &head,
+            &options.text_changes,
+            &mut text_stats,
+        ) {
+            replace_output_prefix(
+                &source,
+                &mut replacements,
+                head.len(),
+                &trimmed,
+                "leading blank lines",
+            );
+        }
+    }
+
+    if options.text_changes.verify_string_literals {
+        for offset in transform_text::unterminated_string_offsets(&source, options.dialect) {
+            let (line, column) = line_and_column(&source, offset);
+            let message = format!(
+                "{}:{}:{}: string literal is not terminated before the end of the line",
+                filename, line, column
+            );
+            log::warn!("{}", message);
+            eprintln!("Warning: {}", message);
+        }
+    }
+
+    timing.record_text_stats(text_stats);
+
+    Ok((source, replacements))
+}
+
+/// End of the text `replacements` make from `source`, from the line break before its last
+/// non-blank line, or all of it when there is none. The trailing newline rule only reads that
+/// far back: it trims the line breaks after that line and keeps the line ending in front of it.
+fn output_tail(source: &str, replacements: &[TextReplacement]) -> String {
+    let pieces = output_pieces(source, replacements);
+    let mut chars = pieces
+        .iter()
+        .rev()
+        .flat_map(|piece| piece.chars().rev())
+        .peekable();
+    let mut len = 0;
+    let mut seen_content = false;
+    while let Some(ch) = chars.next() {
+        len += ch.len_utf8();
+        if ch == '\n' && seen_content {
+            if chars.peek() == Some(&'\r') {
+                len += 1;
+            }
+            break;
+        }
+        seen_content |= ch != '\r' && ch != '\n';
+    }
+    output_edge(pieces.iter().rev(), len, |piece, len| {
+        &piece[piece.len() - len..]
+    })
+    .into_iter()
+    .rev()
+    .collect()
+}
+
+/// Start of the text `replacements` make from `source`, up to and including its first
+/// character other than whitespace or a leading byte-order mark, or all of it when there is none
+fn output_head(source: &str, replacements: &[TextReplacement]) -> String {
+    let pieces = output_pieces(source, replacements);
+    let mut len = 0;
+    for (index, ch) in pieces.iter().flat_map(|piece| piece.chars()).enumerate() {
+        len += ch.len_utf8();
+        if !(ch.is_whitespace() || (index == 0 && ch == '\u{feff}')) {
+            break;
+        }
+    }
+    output_edge(pieces.iter(), len, |piece, len| &piece[..len]).concat()
+}
+
+/// The first `len` bytes of `pieces`, cut from each piece with `cut`
+fn output_edge<'a>(
+    pieces: impl Iterator<Item = &'a &'a str>,
+    mut len: usize,
+    cut: impl Fn(&'a str, usize) -> &'a str,
+) -> Vec<&'a str> {
+    let mut edge = Vec::new();
+    for piece in pieces {
+        if len == 0 {
+            break;
+        }
+        let taken = len.min(piece.len());
+        edge.push(cut(piece, taken));
+        len -= taken;
+    }
+    edge
+}
+
+/// Length of the common prefix of `a` and `b`, ending on a character boundary of both
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    let mut len = a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count();
+    while !a.is_char_boundary(len) || !b.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
@@ -1,593 +1,521 @@
-mod dfixxer_error;
-use dfixxer_error::DFixxerError;
 mod arguments;
-use arguments::{Command, expand_filename_pattern, parse_args};
-use diffy::create_patch;
-mod options;
-use options::{Options, find_custom_config_for_file, should_exclude_file};
-mod replacements;
-mod transform_control_statement_body_wrapping;
-mod transform_inherited_calls;
-mod transform_inline_local_var_definitions;
-mod transform_local_routine_indentation;
-mod transform_local_routine_spacing;
-mod transform_procedure_section;
-mod transform_single_keyword_sections;
-mod transform_text;
-mod transform_unit_program_section;
-mod transform_uses_section;
-mod transformer_utility;
-use replacements::{TextReplacement, apply_replacements_to_string, compute_source_sections};
-mod parser;
-use parser::{
-    ControlStatementBodyWrappingContext, ControlStatementKind, ParseContextTimings, parse,
-    parse_with_contexts_and_timings,
+mod progress;
+mod serve;
+use arguments::{
+    Arguments, Command, LogLevel, OutputFormat, TransformationFilter, TransformationKind,
+    changed_files_since, expand_filename_patterns, parse_args, write_completions,
 };
-mod suppression;
-
-use crate::suppression::collect_suppression_context;
-use crate::transform_control_statement_body_wrapping::transform_control_statement_body_wrapping;
-use crate::transform_inherited_calls::transform_inherited_calls;
-use crate::transform_inline_local_var_definitions::transform_inline_local_var_definitions;
-use crate::transform_local_routine_indentation::transform_local_routine_indentation;
-use crate::transform_local_routine_spacing::transform_local_routine_spacing;
-use crate::transform_procedure_section::transform_procedure_section;
-use crate::transform_single_keyword_sections::transform_single_keyword_section;
-use crate::transform_unit_program_section::transform_unit_program_section;
-use crate::transform_uses_section::transform_uses_section;
-use std::collections::BTreeMap;
-use std::time::{Duration, Instant};
-
-#[derive(Debug, Clone, Default)]
-struct RulePerformanceSummary {
-    candidates: usize,
-    replacements: usize,
-    duration: Duration,
+use dfixxer::dfixxer_error::{EXIT_CHANGES, EXIT_CLEAN, EXIT_DEPRECATED_CONFIG};
+use dfixxer::file_output::write_file_atomically;
+use dfixxer::indentation_report::indentation_issues;
+use dfixxer::options::{
+    CommentStyle, CustomConfig, IGNORE_FILE_NAME, KeywordCase, ParsePolicy, TransformationOptions,
+    find_custom_config_for_file, should_exclude_by_ignore_file, should_exclude_file,
+    should_include_file,
+};
+use dfixxer::parser::{self, parse};
+use dfixxer::replacements::{apply_replacements_to_string, output_pieces};
+use dfixxer::source_encoding::{FileEncoding, decode_source, write_source_pieces};
+use dfixxer::{
+    DFixxerError, Options, PerformanceCollector, TextReplacement, process_source_replacements,
+};
+use diffy::{DiffOptions, PatchFormatter};
+use progress::ProgressBar;
+use std::collections::{HashSet, VecDeque};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Load a file and decode it to text without a leading byte-order mark
+fn load_file(filename: &str, options: &Options) -> Result<(String, FileEncoding), DFixxerError> {
+    decode_source(&std::fs::read(filename)?, options.encoding)
 }
 
-/// Collects top-level timings plus fine-grained parser, rule, and text-rule metrics.
-struct PerformanceCollector {
-    stage_timings: BTreeMap<String, Duration>,
-    parse_timings: BTreeMap<String, Duration>,
-    rule_timings: BTreeMap<String, RulePerformanceSummary>,
-    text_stats: transform_text::TextTransformationStats,
+/// Ranges are given against the input bytes, which include the byte-order mark and may be
+/// UTF-16 or Latin-1, so they are mapped onto the decoded text
+fn range_in_text(
+    range: Option<(usize, usize)>,
+    text: &str,
+    file_encoding: FileEncoding,
+) -> Option<(usize, usize)> {
+    range.map(|(start, end)| {
+        (
+            file_encoding.text_offset(text, start),
+            file_encoding.text_offset(text, end),
+        )
+    })
 }
 
-impl PerformanceCollector {
-    fn new() -> Self {
-        Self {
-            stage_timings: BTreeMap::new(),
-            parse_timings: BTreeMap::new(),
-            rule_timings: BTreeMap::new(),
-            text_stats: transform_text::TextTransformationStats::default(),
-        }
+/// Load the configuration file, failing with its position when it exists but is invalid.
+/// Also returns whether the file uses deprecated keys.
+fn load_config(config_path: &str) -> Result<(Options, bool), DFixxerError> {
+    if Path::new(config_path).exists() {
+        Options::load_from_file_with_deprecations(config_path)
+    } else {
+        Ok((Options::default(), false))
     }
+}
 
-    fn record_stage_duration(&mut self, operation_name: &str, duration: Duration) {
-        self.stage_timings
-            .entry(operation_name.to_string())
-            .and_modify(|total| *total += duration)
-            .or_insert(duration);
+/// `EXIT_DEPRECATED_CONFIG` for an otherwise clean run whose configuration uses deprecated
+/// keys; any other exit code is kept
+fn with_deprecated_config_exit(exit_code: i32, deprecated_config: bool) -> i32 {
+    if exit_code == EXIT_CLEAN && deprecated_config {
+        EXIT_DEPRECATED_CONFIG
+    } else {
+        exit_code
     }
+}
 
-    fn record_parse_timings(&mut self, parse_timings: &ParseContextTimings) {
-        self.parse_timings
-            .insert("build tree".to_string(), parse_timings.build_tree);
-        self.parse_timings.insert(
-            "collect code sections".to_string(),
-            parse_timings.collect_code_sections,
-        );
-        self.parse_timings.insert(
-            "collect spacing context".to_string(),
-            parse_timings.collect_spacing_context,
-        );
-        self.parse_timings.insert(
-            "collect inherited call expansion context".to_string(),
-            parse_timings.collect_inherited_call_expansion_context,
-        );
-        self.parse_timings.insert(
-            "collect local routine spacing context".to_string(),
-            parse_timings.collect_local_routine_spacing_context,
-        );
-        self.parse_timings.insert(
-            "collect control body wrapping context".to_string(),
-            parse_timings.collect_control_statement_body_wrapping_context,
-        );
-        self.parse_timings.insert(
-            "collect inline local var definition context".to_string(),
-            parse_timings.collect_inline_local_var_definition_context,
-        );
+/// Options for `filename`, switching from the options loaded from `config_path` to a matching
+/// custom config. Only the custom config is loaded here; `config` is loaded once per run.
+fn resolve_loaded_options(
+    config: &Options,
+    filename: &str,
+    config_path: &str,
+) -> Result<Options, DFixxerError> {
+    // Check if there's a custom config for this specific file
+    match find_custom_config_for_file(&config.custom_config_patterns, filename, Some(config_path)) {
+        Some(CustomConfig::Path(final_config_path)) if final_config_path != config_path => {
+            log::info!("Loading custom configuration from: {}", final_config_path);
+            load_config(&final_config_path).map(|(options, _)| options)
+        }
+        Some(CustomConfig::Overrides(overrides)) => config.with_overrides(&overrides),
+        _ => Ok(config.clone()),
     }
+}
 
-    fn record_rule_timing(
-        &mut self,
-        rule_name: &str,
-        candidates: usize,
-        replacements: usize,
-        duration: Duration,
-    ) {
-        if candidates == 0 && replacements == 0 {
+/// Apply `--only` / `--skip` on top of the configured transformations. `--only` also turns
+/// off the transformations it cannot name; `--skip` leaves everything else as configured.
+fn apply_transformation_filter(options: &mut Options, filter: &TransformationFilter) {
+    let only = match filter {
+        TransformationFilter::Only(only) => only,
+        TransformationFilter::Skip(skip) => {
+            let transformations = &mut options.transformations;
+            for kind in skip {
+                match kind {
+                    TransformationKind::Uses => transformations.enable_uses_section = false,
+                    TransformationKind::Unit => transformations.enable_unit_program_section = false,
+                    TransformationKind::Keyword => {
+                        transformations.enable_single_keyword_sections = false
+                    }
+                    TransformationKind::Procedure => {
+                        transformations.enable_procedure_section = false
+                    }
+                    TransformationKind::Text => transformations.enable_text_transformations = false,
+                }
+            }
             return;
         }
-        let stats = self.rule_timings.entry(rule_name.to_string()).or_default();
-        stats.candidates += candidates;
-        stats.replacements += replacements;
-        stats.duration += duration;
-    }
-
-    fn record_text_stats(&mut self, stats: transform_text::TextTransformationStats) {
-        self.text_stats.merge(stats);
-    }
+    };
+    let enabled = |kind| only.contains(&kind);
+    options.transformations = TransformationOptions {
+        enable_uses_section: enabled(TransformationKind::Uses),
+        enable_unit_program_section: enabled(TransformationKind::Unit),
+        enable_single_keyword_sections: enabled(TransformationKind::Keyword),
+        enable_procedure_section: enabled(TransformationKind::Procedure),
+        enable_text_transformations: enabled(TransformationKind::Text),
+        enable_local_routine_spacing: false,
+        enable_local_routine_indentation: false,
+        enable_inline_local_var_definitions: false,
+        enable_for_body_wrapping: false,
+        enable_while_body_wrapping: false,
+        enable_if_body_wrapping: false,
+        enable_inherited_call_expansion: false,
+        align_declarations: false,
+        reindent_blocks: false,
+        annotate_end: false,
+        remove_redundant_semicolons: false,
+        one_statement_per_line: false,
+        keyword_case: KeywordCase::Preserve,
+        comment_style: CommentStyle::Preserve,
+        ..options.transformations.clone()
+    };
+}
 
-    fn time_operation<T, F>(&mut self, operation_name: &str, operation: F) -> T
-    where
-        F: FnOnce() -> T,
-    {
-        let start = Instant::now();
-        let result = operation();
-        let duration = start.elapsed();
-        log::debug!("{} took: {:?}", operation_name, duration);
-        self.record_stage_duration(operation_name, duration);
-        result
+/// Check the configured include and exclude patterns for a file
+fn is_selected_by_config(options: &Options, filename: &str, config_path: &str) -> bool {
+    if !should_include_file(&options.include_files, filename, Some(config_path)) {
+        log::info!(
+            "File '{}' is not included by configuration, skipping",
+            filename
+        );
+        false
+    } else {
+        !is_excluded_by_config(options, filename, config_path)
     }
+}
 
-    fn time_operation_result<T, E, F>(&mut self, operation_name: &str, operation: F) -> Result<T, E>
-    where
-        F: FnOnce() -> Result<T, E>,
-    {
-        let start = Instant::now();
-        let result = operation();
-        let duration = start.elapsed();
-        log::debug!("{} took: {:?}", operation_name, duration);
-        self.record_stage_duration(operation_name, duration);
-        result
+fn is_excluded_by_config(options: &Options, filename: &str, config_path: &str) -> bool {
+    if should_exclude_file(&options.exclude_files, filename, Some(config_path)) {
+        log::info!("File '{}' is excluded by configuration, skipping", filename);
+        true
+    } else if should_exclude_by_ignore_file(filename) {
+        log::info!(
+            "File '{}' is excluded by {}, skipping",
+            filename,
+            IGNORE_FILE_NAME
+        );
+        true
+    } else {
+        false
     }
+}
 
-    fn log_summary(&self) {
-        let total_processing: Duration = self.stage_timings.values().copied().sum();
-
-        log::info!("Performance summary:");
-        if !self.stage_timings.is_empty() {
-            log::info!("  Stage timings:");
-            for (operation, duration) in &self.stage_timings {
-                log::info!("    {}: {:?}", operation, duration);
+/// Paths named by `{$I name}` / `{$INCLUDE name}` directives, in source order.
+/// `{$I+}` and `{$I-}` switch I/O checking and are not includes.
+fn include_directive_paths(source: &str) -> Vec<String> {
+    source
+        .match_indices("{$")
+        .filter_map(|(start, _)| {
+            let body = &source[start + 2..];
+            let body = &body[..body.find('}')?];
+            let name_len = body
+                .find(|ch: char| !ch.is_ascii_alphabetic())
+                .unwrap_or(body.len());
+            let (name, argument) = body.split_at(name_len);
+            let is_include = name.eq_ignore_ascii_case("I") || name.eq_ignore_ascii_case("INCLUDE");
+            if !is_include || !argument.starts_with(char::is_whitespace) {
+                return None;
             }
-        }
-        if !self.parse_timings.is_empty() {
-            log::info!("  Parse substage timings:");
-            for (operation, duration) in &self.parse_timings {
-                log::info!("    {}: {:?}", operation, duration);
-            }
-        }
-        if !self.rule_timings.is_empty() {
-            log::info!("  Rule timings:");
-            for (rule_name, stats) in &self.rule_timings {
-                log::info!(
-                    "    {}: candidates={} replacements={} total={:?}",
-                    rule_name,
-                    stats.candidates,
-                    stats.replacements,
-                    stats.duration
-                );
-            }
-        }
-        if !self.text_stats.is_empty() {
-            log::info!(
-                "  Text transformation counters: sections={} changed_sections={} bytes={} skipped_error_ranges={} file_level_runs={} file_level_changes={}",
-                self.text_stats.sections_processed,
-                self.text_stats.sections_changed,
-                self.text_stats.bytes_processed,
-                self.text_stats.skipped_error_ranges,
-                self.text_stats.file_level_runs,
-                self.text_stats.file_level_changes
+            let path = argument.trim().trim_matches('\'');
+            (!path.is_empty()).then(|| path.to_string())
+        })
+        .collect()
+}
+
+/// Queue the files `source` includes, resolved against the directory of `filename`.
+/// Files already seen are skipped so include cycles end; missing files are reported and skipped.
+/// Include files are not limited by `include_files`, but exclusions still apply.
+fn queue_included_files(
+    filename: &str,
+    source: &str,
+    files: &mut Vec<String>,
+    seen: &mut HashSet<PathBuf>,
+    options: &Options,
+    config_path: &str,
+) {
+    let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    for include in include_directive_paths(source) {
+        let path = dir.join(include.replace('\\', std::path::MAIN_SEPARATOR_STR));
+        let Ok(canonical) = std::fs::canonicalize(&path) else {
+            let message = format!(
+                "{}: included file '{}' not found, skipping",
+                filename,
+                path.display()
             );
-            for (rule_name, stats) in self.text_stats.rule_stats() {
-                log::info!(
-                    "    {}: hits={} changes={} skips={}",
-                    rule_name,
-                    stats.hits,
-                    stats.changes,
-                    stats.skips
-                );
-            }
+            log::warn!("{}", message);
+            eprintln!("Warning: {}", message);
+            continue;
+        };
+        if !seen.insert(canonical) {
+            continue;
+        }
+        let path = path.to_string_lossy().to_string();
+        if !is_excluded_by_config(options, &path, config_path) {
+            log::info!("Following include: {}", path);
+            files.push(path);
         }
-        log::info!("  Total processing: {:?}", total_processing);
     }
 }
 
-fn filtered_control_statement_context<F>(
-    context: &ControlStatementBodyWrappingContext,
-    predicate: F,
-) -> ControlStatementBodyWrappingContext
-where
-    F: Fn(&ControlStatementKind) -> bool,
-{
-    ControlStatementBodyWrappingContext {
-        candidates: context
-            .candidates
-            .iter()
-            .filter(|candidate| predicate(&candidate.kind))
-            .cloned()
-            .collect(),
-    }
+/// Source, encoding and the sorted replacements that update a processed file
+type ProcessedFile = (String, FileEncoding, Vec<TextReplacement>);
+
+/// Options for `filename` with the overrides given on the command line applied
+fn options_for_file(
+    filename: &str,
+    config: &Options,
+    arguments: &Arguments,
+) -> Result<Options, DFixxerError> {
+    let config_path = arguments.config_path.as_deref().unwrap_or("dfixxer.toml");
+    Ok(with_argument_overrides(
+        resolve_loaded_options(config, filename, config_path)?,
+        arguments,
+    ))
 }
 
-fn load_file(filename: &str) -> Result<String, DFixxerError> {
-    Ok(std::fs::read_to_string(filename)?)
+/// Apply `--only` / `--skip` and `--strict-parse` to resolved options
+fn with_argument_overrides(mut options: Options, arguments: &Arguments) -> Options {
+    if let Some(filter) = &arguments.transformation_filter {
+        apply_transformation_filter(&mut options, filter);
+    }
+    if arguments.strict_parse {
+        options.parse_policy = ParsePolicy::Strict;
+    }
+    options
 }
 
-/// Process a file and return the replacements that would be made
+/// Process a file and return its source and the replacements that update it, along with the
+/// encoding the file was read with so it can be written back unchanged
 fn process_file(
     filename: &str,
-    config_path: Option<&str>,
+    config: &Options,
+    arguments: &Arguments,
     timing: &mut PerformanceCollector,
-) -> Result<(String, String, usize), DFixxerError> {
-    // Load options from config file, or use defaults if not found
-    let config_path = config_path.unwrap_or("dfixxer.toml");
-    let initial_options: Options = Options::load_or_default(config_path);
-
-    // Check if there's a custom config for this specific file
-    let final_config_path = find_custom_config_for_file(
-        &initial_options.custom_config_patterns,
-        filename,
-        Some(config_path),
-    )
-    .unwrap_or_else(|| config_path.to_string());
-
-    let options: Options = if final_config_path != config_path {
-        log::info!("Loading custom configuration from: {}", final_config_path);
-        Options::load_or_default(&final_config_path)
-    } else {
-        initial_options
-    };
-
-    // Time file loading
-    let source = timing.time_operation_result("File loading", || load_file(filename))?;
-    let suppression_context = timing.time_operation("Inline suppression scan", || {
-        collect_suppression_context(&source)
-    });
-    for warning in &suppression_context.warnings {
-        let message = format!("{}:{}: {}", filename, warning.line, warning.message());
-        log::warn!("{}", message);
-        eprintln!("Warning: {}", message);
-    }
-
-    // Time parsing
-    let (
-        parse_result,
-        spacing_context,
-        inherited_expansion_context,
-        local_routine_spacing_context,
-        control_statement_body_wrapping_context,
-        inline_local_var_definition_context,
-        parse_context_timings,
-    ) = timing.time_operation_result("Parsing", || parse_with_contexts_and_timings(&source))?;
-    timing.record_parse_timings(&parse_context_timings);
-    if !spacing_context.error_ranges.is_empty() {
-        let message = format!(
-            "Parser recovered with {} error span(s) in '{}'; text changes are skipped inside error spans.",
-            spacing_context.error_ranges.len(),
-            filename
+) -> Result<ProcessedFile, DFixxerError> {
+    let options = options_for_file(filename, config, arguments)?;
+    let (source, file_encoding) =
+        timing.time_operation_result("File loading", || load_file(filename, &options))?;
+    if options.max_file_size_bytes > 0 && source.len() > options.max_file_size_bytes {
+        log::info!(
+            "Skipping '{}': {} bytes exceeds max_file_size_bytes ({})",
+            filename,
+            source.len(),
+            options.max_file_size_bytes
         );
-        log::warn!("{}", message);
-        eprintln!("Warning: {}", message);
+        return Ok((source, file_encoding, Vec::new()));
     }
+    let range = range_in_text(arguments.range, &source, file_encoding);
+    let (source, replacements) =
+        process_source_replacements(filename, source, options, range, timing)?;
+    Ok((source, file_encoding, replacements))
+}
 
-    // Helper function to apply text transformations to a replacement if enabled
-    let mut text_stats = transform_text::TextTransformationStats::default();
-    let apply_text_transformation_if_enabled =
-        |replacement: TextReplacement,
-         text_stats: &mut transform_text::TextTransformationStats|
-         -> Option<TextReplacement> {
-            if options.transformations.enable_text_transformations {
-                let text = replacement.text.as_str();
-                transform_text::apply_text_transformation_with_context_and_stats(
-                    replacement.start,
-                    replacement.end,
-                    text,
-                    &options.text_changes,
-                    Some(&spacing_context),
-                    text_stats,
-                )
-                .or(Some(replacement))
-            } else {
-                Some(replacement)
-            }
-        };
-
-    let transformation_start = Instant::now();
-    let mut replacements: Vec<TextReplacement> = Vec::new();
-
-    if options.transformations.enable_uses_section {
-        let uses_sections: Vec<_> = parse_result
-            .code_sections
-            .iter()
-            .filter(|code_section| code_section.keyword.kind == parser::Kind::Uses)
-            .collect();
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = uses_sections
-            .iter()
-            .filter_map(|code_section| transform_uses_section(code_section, &options, &source))
-            .collect();
-        timing.record_rule_timing(
-            "uses_section",
-            uses_sections.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Process each file on its own thread, returning the results in the order of `filenames`
+fn process_files(
+    filenames: &[String],
+    config: &Options,
+    arguments: &Arguments,
+) -> Vec<(PerformanceCollector, Result<ProcessedFile, DFixxerError>)> {
+    let process = |filename: &String| {
+        let mut timing = PerformanceCollector::new();
+        let result = process_file(filename, config, arguments, &mut timing);
+        (timing, result)
+    };
+    if filenames.len() <= 1 {
+        return filenames.iter().map(process).collect();
     }
-
-    if options.transformations.enable_unit_program_section {
-        let unit_program_sections: Vec<_> = parse_result
-            .code_sections
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = filenames
             .iter()
-            .filter(|code_section| {
-                matches!(
-                    code_section.keyword.kind,
-                    parser::Kind::Unit | parser::Kind::Program
-                )
-            })
-            .collect();
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = unit_program_sections
-            .iter()
-            .filter_map(|code_section| {
-                transform_unit_program_section(code_section, &options, &source)
-            })
+            .map(|filename| scope.spawn(|| process(filename)))
             .collect();
-        timing.record_rule_timing(
-            "unit_program_section",
-            unit_program_sections.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
-    }
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("file processing thread panicked"))
+            .collect()
+    })
+}
 
-    if options.transformations.enable_single_keyword_sections {
-        let single_keyword_sections: Vec<_> = parse_result
-            .code_sections
-            .iter()
-            .filter(|code_section| {
-                matches!(
-                    code_section.keyword.kind,
-                    parser::Kind::Interface
-                        | parser::Kind::Implementation
-                        | parser::Kind::Initialization
-                        | parser::Kind::Finalization
-                )
-            })
-            .collect();
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = single_keyword_sections
-            .iter()
-            .filter_map(|code_section| {
-                transform_single_keyword_section(&source, code_section, &options)
-            })
-            .collect();
-        timing.record_rule_timing(
-            "single_keyword_sections",
-            single_keyword_sections.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Result for `filenames[index]`, processing up to `arguments.jobs` files from there at once
+/// when none are waiting in `processed`. Results are consumed in order, so the report does
+/// not depend on the job count.
+fn next_processed_file(
+    processed: &mut VecDeque<(PerformanceCollector, Result<ProcessedFile, DFixxerError>)>,
+    filenames: &[String],
+    index: usize,
+    config: &Options,
+    arguments: &Arguments,
+) -> (PerformanceCollector, Result<ProcessedFile, DFixxerError>) {
+    if processed.is_empty() {
+        let end = (index + arguments.jobs).min(filenames.len());
+        processed.extend(process_files(&filenames[index..end], config, arguments));
     }
+    processed
+        .pop_front()
+        .expect("a file is processed for every index")
+}
 
-    if options.transformations.enable_procedure_section {
-        let procedure_sections: Vec<_> = parse_result
-            .code_sections
-            .iter()
-            .filter(|code_section| {
-                matches!(
-                    code_section.keyword.kind,
-                    parser::Kind::ProcedureDeclaration | parser::Kind::FunctionDeclaration
-                )
-            })
-            .collect();
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = procedure_sections
-            .iter()
-            .filter_map(|code_section| transform_procedure_section(code_section, &options, &source))
-            .filter_map(|replacement| {
-                apply_text_transformation_if_enabled(replacement, &mut text_stats)
-            })
-            .collect();
-        timing.record_rule_timing(
-            "procedure_section",
-            procedure_sections.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Write `source` with `replacements` applied to `filename` only when there are any, so
+/// unchanged files keep their modification time. The updated text is streamed piece by piece
+/// into a file that replaces the original atomically, so a failed write leaves the original
+/// in place. Returns whether the file was written.
+fn write_if_changed(
+    filename: &str,
+    source: &str,
+    replacements: &[TextReplacement],
+    file_encoding: FileEncoding,
+    timing: &mut PerformanceCollector,
+) -> Result<bool, DFixxerError> {
+    if replacements.is_empty() {
+        log::debug!("File '{}' is unchanged, not writing", filename);
+        return Ok(false);
     }
 
-    if options.transformations.enable_inherited_call_expansion {
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = transform_inherited_calls(&inherited_expansion_context)
-            .into_iter()
-            .filter_map(|replacement| {
-                apply_text_transformation_if_enabled(replacement, &mut text_stats)
-            })
-            .collect();
-        timing.record_rule_timing(
-            "inherited_call_expansion",
-            inherited_expansion_context.candidates.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
-    }
+    timing.time_operation_result("Writing updated file", || {
+        write_file_atomically(Path::new(filename), |out| {
+            write_source_pieces(out, output_pieces(source, replacements), file_encoding)
+        })
+        .map_err(DFixxerError::from)
+    })?;
+    Ok(true)
+}
 
-    if options.transformations.enable_local_routine_indentation {
-        let rule_start = Instant::now();
-        let rule_replacements =
-            transform_local_routine_indentation(&source, &local_routine_spacing_context, &options);
-        timing.record_rule_timing(
-            "local_routine_indentation",
-            local_routine_spacing_context.blocks.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Render a unified diff with `context` unchanged lines around each hunk, coloring removed
+/// lines red and added lines green when `color` is set
+fn format_patch(source: &str, updated_source: &str, context: usize, color: bool) -> String {
+    let patch = DiffOptions::new()
+        .set_context_len(context)
+        .create_patch(source, updated_source);
+    let formatter = if color {
+        PatchFormatter::new().with_color()
+    } else {
+        PatchFormatter::new()
+    };
+    formatter.fmt_patch(&patch).to_string()
+}
+
+/// 1-based line where the replacement first differs from the source, since text passes
+/// can span many unchanged lines
+fn first_changed_line(source: &str, replacement: &TextReplacement) -> usize {
+    let original = &source[replacement.start..replacement.end];
+    let unchanged_prefix = original
+        .char_indices()
+        .zip(replacement.text.chars())
+        .find(|((_, original_ch), replacement_ch)| original_ch != replacement_ch)
+        .map(|((idx, _), _)| idx)
+        .unwrap_or_else(|| original.len().min(replacement.text.len()));
+    source[..replacement.start + unchanged_prefix]
+        .matches('\n')
+        .count()
+        + 1
+}
+
+/// Write a `filename:line: reason` entry for every replacement that carries a reason
+fn print_explanations(
+    out: &mut impl Write,
+    filename: &str,
+    source: &str,
+    replacements: &[TextReplacement],
+) -> std::io::Result<()> {
+    let mut replacements: Vec<_> = replacements.iter().collect();
+    replacements.sort_by_key(|replacement| replacement.start);
+    for replacement in replacements {
+        if let Some(reason) = &replacement.reason {
+            let line = first_changed_line(source, replacement);
+            writeln!(out, "{}:{}: {}", filename, line, reason)?;
+        }
     }
+    Ok(())
+}
 
-    if options.transformations.enable_local_routine_spacing {
-        let rule_start = Instant::now();
-        let rule_replacements =
-            transform_local_routine_spacing(&source, &local_routine_spacing_context, &options);
-        timing.record_rule_timing(
-            "local_routine_spacing",
-            local_routine_spacing_context.gaps.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Write a `filename:line: issue` entry for every line `check --report-indentation` flags
+fn print_indentation_issues(
+    out: &mut impl Write,
+    filename: &str,
+    source: &str,
+    indentation: &str,
+) -> std::io::Result<()> {
+    for issue in indentation_issues(source, indentation) {
+        writeln!(out, "{}:{}: {}", filename, issue.line, issue.message)?;
     }
+    Ok(())
+}
 
-    if options.transformations.enable_inline_local_var_definitions {
-        let rule_start = Instant::now();
-        let rule_replacements: Vec<_> = transform_inline_local_var_definitions(
-            &source,
-            &inline_local_var_definition_context,
-            &options,
-        )
+/// The `check --format json` entry for one file, with its changes in source order
+fn json_file_report(
+    filename: &str,
+    source: &str,
+    replacements: &[TextReplacement],
+) -> serde_json::Value {
+    let mut replacements: Vec<_> = replacements.iter().collect();
+    replacements.sort_by_key(|replacement| replacement.start);
+    let changes: Vec<_> = replacements
         .into_iter()
-        .filter_map(|replacement| {
-            apply_text_transformation_if_enabled(replacement, &mut text_stats)
+        .map(|replacement| {
+            serde_json::json!({
+                "line": first_changed_line(source, replacement),
+                "start": replacement.start,
+                "end": replacement.end,
+                "original": &source[replacement.start..replacement.end],
+                "replacement": replacement.text,
+                "reason": replacement.reason,
+            })
         })
         .collect();
-        timing.record_rule_timing(
-            "inline_local_var_definitions",
-            inline_local_var_definition_context.routines.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
-    }
+    serde_json::json!({ "path": filename, "changed": !changes.is_empty(), "changes": changes })
+}
 
-    if options.transformations.enable_for_body_wrapping {
-        let for_context =
-            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
-                matches!(
-                    kind,
-                    ControlStatementKind::For | ControlStatementKind::Foreach
-                )
-            });
-        let rule_start = Instant::now();
-        let rule_replacements =
-            transform_control_statement_body_wrapping(&source, &for_context, &options);
-        timing.record_rule_timing(
-            "control_body_wrapping.for_foreach",
-            for_context.candidates.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
-    }
+/// Print the single object `check --format json` reports for all files
+fn print_json_report(files: Vec<serde_json::Value>) {
+    let total_changes: usize = files
+        .iter()
+        .map(|file| file["changes"].as_array().map_or(0, Vec::len))
+        .sum();
+    println!(
+        "{}",
+        serde_json::json!({ "files": files, "total_changes": total_changes })
+    );
+}
 
-    if options.transformations.enable_while_body_wrapping {
-        let while_context =
-            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
-                matches!(kind, ControlStatementKind::While)
-            });
-        let rule_start = Instant::now();
-        let rule_replacements =
-            transform_control_statement_body_wrapping(&source, &while_context, &options);
-        timing.record_rule_timing(
-            "control_body_wrapping.while",
-            while_context.candidates.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
+/// Log the performance summary, or print it to stderr with `--verbose-timing`
+fn report_timing(timing: &PerformanceCollector, verbose_timing: bool) {
+    if verbose_timing {
+        timing.print_summary();
+    } else {
+        timing.log_summary();
     }
+}
 
-    if options.transformations.enable_if_body_wrapping {
-        let if_else_context =
-            filtered_control_statement_context(&control_statement_body_wrapping_context, |kind| {
-                matches!(
-                    kind,
-                    ControlStatementKind::IfThen | ControlStatementKind::Else
-                )
-            });
-        let rule_start = Instant::now();
-        let rule_replacements =
-            transform_control_statement_body_wrapping(&source, &if_else_context, &options);
-        timing.record_rule_timing(
-            "control_body_wrapping.if_else",
-            if_else_context.candidates.len(),
-            rule_replacements.len(),
-            rule_start.elapsed(),
-        );
-        replacements.extend(rule_replacements);
-    }
+/// Format source read from stdin, writing the result (update) or diff (check) to stdout
+fn process_stdin(config: &Options, arguments: &Arguments) -> Result<i32, DFixxerError> {
+    let filename = arguments.filename.as_str();
+    let config_path = arguments.config_path.as_deref().unwrap_or("dfixxer.toml");
+    let selected = is_selected_by_config(config, filename, config_path);
+    let file_options = options_for_file(filename, config, arguments)?;
+    let indentation = file_options.indentation.clone();
+
+    let mut bytes = Vec::new();
+    std::io::stdin().read_to_end(&mut bytes)?;
+    let (source, file_encoding) = decode_source(&bytes, file_options.encoding)?;
+
+    let mut timing = PerformanceCollector::new();
+    let (source, replacements) = if selected {
+        let range = range_in_text(arguments.range, &source, file_encoding);
+        process_source_replacements(filename, source, file_options, range, &mut timing)?
+    } else {
+        // Excluded input is passed through unchanged so editors keep the buffer as-is
+        (source, Vec::new())
+    };
 
-    timing.record_stage_duration("Transformation", transformation_start.elapsed());
-    replacements.retain(|replacement| {
-        !suppression_context.suppresses_replacement(replacement.start, replacement.end)
-    });
-
-    // Apply text transformations if enabled
-    if options.transformations.enable_text_transformations {
-        timing.time_operation("Text transformations", || {
-            // Calculate sections (gaps + existing replacements)
-            let sections = compute_source_sections(
-                &source,
-                &replacements,
-                &suppression_context.text_exclusion_ranges(),
-            );
+    if arguments.report_indentation {
+        print_indentation_issues(
+            &mut std::io::stdout().lock(),
+            filename,
+            &source,
+            &indentation,
+        )?;
+    }
 
-            // Apply text transformation to each section and add to replacements if there's a change
-            for section in sections {
-                let text = &source[section.start..section.end];
-                if let Some(transformation) =
-                    transform_text::apply_text_transformation_with_context_and_stats(
-                        section.start,
-                        section.end,
-                        text,
-                        &options.text_changes,
-                        Some(&spacing_context),
-                        &mut text_stats,
+    let exit_code = match arguments.command {
+        Command::UpdateFile => {
+            write_source_pieces(
+                &mut std::io::stdout().lock(),
+                output_pieces(&source, &replacements),
+                file_encoding,
+            )?;
+            EXIT_CLEAN
+        }
+        Command::CheckFile if replacements.is_empty() => EXIT_CLEAN,
+        Command::CheckFile => {
+            if arguments.list_different {
+                println!("{}", filename);
+            } else {
+                println!(
+                    "{}",
+                    format_patch(
+                        &source,
+                        &apply_replacements_to_string(&source, &replacements),
+                        arguments.diff_context,
+                        arguments.color.enabled(),
                     )
-                {
-                    replacements.push(transformation);
+                );
+                if arguments.explain {
+                    print_explanations(
+                        &mut std::io::stdout().lock(),
+                        filename,
+                        &source,
+                        &replacements,
+                    )?;
                 }
             }
-        });
-    }
-    replacements.retain(|replacement| {
-        !suppression_context.suppresses_replacement(replacement.start, replacement.end)
-    });
-
-    let mut replacement_count = replacements.len();
-    let mut updated_source = if replacements.is_empty() {
-        source.clone()
-    } else {
-        timing.time_operation("Applying replacements (in-memory)", || {
-            apply_replacements_to_string(&source, &replacements)
-        })
+            EXIT_CHANGES
+        }
+        _ => EXIT_CLEAN,
     };
 
-    if options.transformations.enable_text_transformations
-        && let Some(file_level_update) =
-            timing.time_operation("File-level text transformations", || {
-                transform_text::apply_file_level_text_changes_with_stats(
-                    &updated_source,
-                    &options.text_changes,
-                    &options.line_ending,
-                    &mut text_stats,
-                )
-            })
-    {
-        updated_source = file_level_update;
-        replacement_count += 1;
-    }
-
-    timing.record_text_stats(text_stats);
-
-    Ok((source, updated_source, replacement_count))
+    report_timing(&timing, arguments.verbose_timing);
+    Ok(exit_code)
 }
 
 fn run() -> Result<i32, DFixxerError> {
@@ -600,40 +528,81 @@ fn run() -> Result<i32, DFixxerError> {
         return Ok(0);
     }
 
+    // Completions are printed before any file handling so no filename is required
+    if let Command::Completions(shell) = arguments.command {
+        write_completions(shell, &mut std::io::stdout());
+        return Ok(0);
+    }
+
+    if matches!(arguments.command, Command::Serve) {
+        return serve::run_server(
+            arguments.config_path.as_deref(),
+            arguments.config_search_root.clone(),
+        );
+    }
+
+    // Loaded and validated once; only custom configs are loaded again for matching files
+    let config_path = arguments.config_path.as_deref().unwrap_or("dfixxer.toml");
+    let (config, deprecated_config) =
+        if matches!(arguments.command, Command::InitConfig | Command::Parse) {
+            (Options::default(), false)
+        } else {
+            load_config(config_path)?
+        };
+
+    // Resolved the same way as for update and check, including custom config patterns
+    if matches!(arguments.command, Command::PrintConfig) {
+        let options = resolve_loaded_options(&config, &arguments.filename, config_path)?;
+        print!("{}", options.to_toml_string()?);
+        return Ok(with_deprecated_config_exit(EXIT_CLEAN, deprecated_config));
+    }
+
+    if arguments.stdin {
+        let exit_code = process_stdin(&config, &arguments)?;
+        return Ok(with_deprecated_config_exit(exit_code, deprecated_config));
+    }
+
     // Expand filename pattern if multi flag is set, but only for commands that support it
     let filenames = match &arguments.command {
-        Command::UpdateFile | Command::CheckFile | Command::Parse | Command::ParseDebug => {
-            expand_filename_pattern(&arguments.filename, arguments.multi)?
+        Command::UpdateFile
+        | Command::CheckFile
+        | Command::CheckAndUpdate
+        | Command::Parse
+        | Command::ParseDebug => {
+            expand_filename_patterns(&arguments.filenames, arguments.multi, arguments.allow_empty)?
         }
         Command::InitConfig => {
             // InitConfig doesn't use multi mode
             vec![arguments.filename.clone()]
         }
-        Command::Version => {
-            // Version doesn't need filenames, but this is unreachable due to early return
+        Command::PrintConfig | Command::Version | Command::Completions(_) | Command::Serve => {
+            // These commands don't need filenames, but this is unreachable due to early return
             vec![]
         }
     };
 
     // For commands that process files, check if files should be excluded
-    let filtered_filenames: Vec<String> = match &arguments.command {
-        Command::UpdateFile | Command::CheckFile => {
-            // Load options to check exclusion patterns
-            let config_path = arguments.config_path.as_deref().unwrap_or("dfixxer.toml");
-            let options = Options::load_or_default(config_path);
-
-            // Filter out excluded files
-            filenames
+    let mut filtered_filenames: Vec<String> = match &arguments.command {
+        Command::UpdateFile | Command::CheckFile | Command::CheckAndUpdate => {
+            // Keep only included files that are not excluded
+            let filenames = filenames
                 .into_iter()
-                .filter(|filename| {
-                    if should_exclude_file(&options.exclude_files, filename, Some(config_path)) {
-                        log::info!("File '{}' is excluded by configuration, skipping", filename);
-                        false
-                    } else {
-                        true
-                    }
-                })
-                .collect()
+                .filter(|filename| is_selected_by_config(&config, filename, config_path));
+            match &arguments.since {
+                Some(since) => {
+                    let changed: HashSet<PathBuf> = changed_files_since(since)?
+                        .iter()
+                        .filter_map(|path| std::fs::canonicalize(path).ok())
+                        .collect();
+                    filenames
+                        .filter(|filename| {
+                            std::fs::canonicalize(filename)
+                                .is_ok_and(|path| changed.contains(&path))
+                        })
+                        .collect()
+                }
+                None => filenames.collect(),
+            }
         }
         _ => filenames,
     };
@@ -642,17 +611,42 @@ fn run() -> Result<i32, DFixxerError> {
         if arguments.multi {
             log::info!("No files to process after filtering");
         }
-        return Ok(0);
+        if arguments.format == OutputFormat::Json {
+            print_json_report(Vec::new());
+        }
+        return Ok(with_deprecated_config_exit(EXIT_CLEAN, deprecated_config));
     }
 
+    let use_color = arguments.color.enabled();
     let mut total_exit_code = 0i32;
+    let mut written_files = 0usize;
+    // With --format json, every file is reported in one object printed at the end
+    let mut json_files: Vec<serde_json::Value> = Vec::new();
+
+    // Files reached through includes are appended while processing; `seen` ends cycles
+    let mut seen: HashSet<PathBuf> = filtered_filenames
+        .iter()
+        .filter_map(|filename| std::fs::canonicalize(filename).ok())
+        .collect();
+    let mut next_file = 0;
+    // Files processed ahead of the one being reported when `--jobs` is above one
+    let mut processed = VecDeque::new();
+    let mut progress = ProgressBar::new(
+        arguments.progress && arguments.multi,
+        matches!(arguments.log_level, Some(LogLevel::Off)),
+    );
 
     // Process each file
-    for filename in &filtered_filenames {
+    while let Some(filename) = filtered_filenames.get(next_file).cloned() {
+        next_file += 1;
+        let filename = &filename;
         // For multi mode, show filename for check, parse, parse-debug commands
-        if arguments.multi {
+        if arguments.multi && !arguments.list_different && arguments.format == OutputFormat::Text {
             match &arguments.command {
-                Command::CheckFile | Command::Parse | Command::ParseDebug => {
+                Command::CheckFile
+                | Command::CheckAndUpdate
+                | Command::Parse
+                | Command::ParseDebug => {
                     let absolute_path =
                         std::fs::canonicalize(filename).unwrap_or_else(|_| filename.into());
                     println!("Processing file: {}", absolute_path.display());
@@ -666,45 +660,121 @@ fn run() -> Result<i32, DFixxerError> {
 
         let exit_code = match arguments.command {
             Command::UpdateFile => {
-                let mut timing = PerformanceCollector::new();
-
-                let (source, updated_source, _) =
-                    process_file(filename, arguments.config_path.as_deref(), &mut timing)?;
+                let (mut timing, result) = next_processed_file(
+                    &mut processed,
+                    &filtered_filenames,
+                    next_file - 1,
+                    &config,
+                    &arguments,
+                );
+                let (source, file_encoding, replacements) = result?;
+                if arguments.follow_includes {
+                    queue_included_files(
+                        filename,
+                        &source,
+                        &mut filtered_filenames,
+                        &mut seen,
+                        &config,
+                        config_path,
+                    );
+                }
 
-                if source != updated_source {
-                    timing.time_operation_result("Writing updated file", || {
-                        std::fs::write(filename, &updated_source).map_err(DFixxerError::from)
-                    })?;
+                if write_if_changed(filename, &source, &replacements, file_encoding, &mut timing)? {
+                    written_files += 1;
                 }
 
-                // Log the timing summary
-                timing.log_summary();
+                report_timing(&timing, arguments.verbose_timing);
                 0
             }
-            Command::CheckFile => {
-                let mut timing = PerformanceCollector::new();
+            Command::CheckFile | Command::CheckAndUpdate => {
+                let (mut timing, result) = next_processed_file(
+                    &mut processed,
+                    &filtered_filenames,
+                    next_file - 1,
+                    &config,
+                    &arguments,
+                );
+                let (source, file_encoding, replacements) = result?;
+                let changed = !replacements.is_empty();
+                if arguments.follow_includes {
+                    queue_included_files(
+                        filename,
+                        &source,
+                        &mut filtered_filenames,
+                        &mut seen,
+                        &config,
+                        config_path,
+                    );
+                }
 
-                let (source, updated_source, replacement_count) =
-                    process_file(filename, arguments.config_path.as_deref(), &mut timing)?;
+                if arguments.format == OutputFormat::Json {
+                    json_files.push(json_file_report(filename, &source, &replacements));
+                } else if changed {
+                    if arguments.list_different {
+                        // Only the path is printed
+                        println!("{}", filename);
+                    } else {
+                        let patch = timing.time_operation("Diff generation", || {
+                            format_patch(
+                                &source,
+                                &apply_replacements_to_string(&source, &replacements),
+                                arguments.diff_context,
+                                use_color,
+                            )
+                        });
+                        println!("{}", patch);
+                        if arguments.explain {
+                            print_explanations(
+                                &mut std::io::stdout().lock(),
+                                filename,
+                                &source,
+                                &replacements,
+                            )?;
+                        }
+                    }
+                }
+                if arguments.report_indentation {
+                    print_indentation_issues(
+                        &mut std::io::stdout().lock(),
+                        filename,
+                        &source,
+                        &options_for_file(filename, &config, &arguments)?.indentation,
+                    )?;
+                }
 
-                if source != updated_source {
-                    let patch = timing.time_operation("Diff generation", || {
-                        create_patch(&source, &updated_source)
-                    });
-                    println!("{}", patch);
+                // The reported result is written as-is, without processing the file again
+                if matches!(arguments.command, Command::CheckAndUpdate)
+                    && write_if_changed(
+                        filename,
+                        &source,
+                        &replacements,
+                        file_encoding,
+                        &mut timing,
+                    )?
+                {
+                    written_files += 1;
                 }
 
-                // Log the timing summary
-                timing.log_summary();
+                report_timing(&timing, arguments.verbose_timing);
 
-                // Return the number of replacements as exit code
-                replacement_count as i32
+                if changed { EXIT_CHANGES } else { EXIT_CLEAN }
             }
             Command::InitConfig => {
                 // InitConfig doesn't use multi mode, so just process first file
                 if filename == &filtered_filenames[0] {
+                    if std::path::Path::new(filename).exists() && !arguments.force {
+                        return Err(DFixxerError::InvalidArgs(format!(
+                            "Configuration file '{}' already exists; pass --force to overwrite it",
+                            filename
+                        )));
+                    }
                     println!("Initializing configuration...");
-                    match Options::create_default_config(filename) {
+                    let created = if arguments.template {
+                        Options::create_template_config(filename)
+                    } else {
+                        Options::create_default_config(filename)
+                    };
+                    match created {
                         Ok(()) => {
                             println!("Created default configuration file: {}", filename);
                             0
@@ -720,26 +790,67 @@ fn run() -> Result<i32, DFixxerError> {
             Command::Parse => {
                 // Parse the file and print each node's kind and text using parse_raw
                 let source = std::fs::read_to_string(filename)?;
-                parser::parse_raw(&source)?;
+                parser::parse_raw(&source, arguments.show_bytes, &mut std::io::stdout().lock())?;
                 0
             }
             Command::ParseDebug => {
                 // Parse the file and print the ParseResult structure
                 let source = std::fs::read_to_string(filename)?;
-                let parse_result = parse(&source)?;
-                println!("{:#?}", parse_result);
+                let options = resolve_loaded_options(&config, filename, config_path)?;
+                let mut parse_result = parse(&source)?;
+                if let Some((first_line, last_line)) = arguments.line_range {
+                    parse_result =
+                        parser::filter_code_sections_by_lines(parse_result, first_line, last_line);
+                }
+                if options.parse_debug.line_column_positions {
+                    parser::print_line_column_positions(
+                        &parse_result,
+                        &mut std::io::stdout().lock(),
+                    )?;
+                } else {
+                    println!("{:#?}", parse_result);
+                }
+                // The partial result is still printed, since that is when it helps most
+                if let Some(err) = parser::first_syntax_error(&source)? {
+                    let message = err.with_filename(filename);
+                    log::warn!("{}", message);
+                    eprintln!("Warning: {}", message);
+                }
                 0
             }
-            Command::Version => {
+            Command::PrintConfig | Command::Version | Command::Completions(_) | Command::Serve => {
                 // This is unreachable due to early return above, but included for completeness
                 0
             }
         };
 
         total_exit_code += exit_code;
+        progress.advance(filtered_filenames.len());
+    }
+    progress.finish();
+
+    if arguments.format == OutputFormat::Json {
+        print_json_report(json_files);
+    }
+
+    if arguments.multi
+        && matches!(
+            arguments.command,
+            Command::UpdateFile | Command::CheckAndUpdate
+        )
+    {
+        log::info!(
+            "Updated {} of {} file(s); unchanged files were not rewritten",
+            written_files,
+            filtered_filenames.len()
+        );
     }
 
-    Ok(total_exit_code)
+    // Any file that would change yields EXIT_CHANGES, however many there are
+    Ok(with_deprecated_config_exit(
+        total_exit_code.min(EXIT_CHANGES),
+        deprecated_config,
+    ))
 }
 
 fn main() {
@@ -766,7 +877,7 @@ fn main() {
         }
         Err(e) => {
             eprintln!("{}", e);
-            std::process::exit(1);
+            std::process::exit(e.exit_code());
         }
     }
 }
@@ -5,23 +5,131 @@ use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+/// Commented configuration written by `init-config --template`
+const CONFIG_TEMPLATE: &str = include_str!("config_template.toml");
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum UsesSectionStyle {
     CommaAtTheBeginning,
     #[default]
     CommaAtTheEnd,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+/// Value side of a `custom_config_patterns` entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum CustomConfig {
+    /// Path of a config file that replaces the base configuration
+    Path(String),
+    /// Inline table merged over the base configuration
+    Overrides(toml::Table),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum UsesSortKey {
+    #[default]
+    UnitName,
+    InPath,
+}
+
+/// Pascal dialect the sources are written in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    Delphi,
+    /// Free Pascal, where `(* *)` comments nest
+    FreePascal,
+}
+
+/// What happens to a uses section that contains comments
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum UsesCommentPolicy {
+    /// Leave the section unformatted and log a warning
+    #[default]
+    Skip,
+    /// Fail the run for the file
+    Error,
+    /// Sort the section, keeping each comment with its unit
+    Preserve,
+}
+
+/// How files the parser only partly understands are handled
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum ParsePolicy {
+    /// Format the error-free parts and leave error regions alone
+    #[default]
+    Lenient,
+    /// Fail a file that has any parse error
+    Strict,
+}
+
+/// Ordering applied to uses entries after `override_sorting_order`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum UsesSortPreset {
+    /// Locale-aware base collation, ignoring case and accents
+    #[default]
+    Alphabetical,
+    /// Ordinal comparison, so uppercase letters sort before lowercase ones
+    AlphabeticalCaseSensitive,
+    /// Shorter names first, then alphabetical
+    LengthThenAlpha,
+    /// Compare names from their last character backwards, case-insensitively
+    Rtl,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum SpaceOperation {
     NoChange,
     Before,
     #[default]
     After,
     BeforeAndAfter,
+    /// Drop the managed spaces: inside the brackets for `paren_inner` / `bracket_inner`, after
+    /// the sign for `unary_sign`, before the token for `call_paren`, `keyword_paren`,
+    /// `word_operator`, and `amp`, and on both sides for the other operators and punctuation
+    Remove,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone, Copy)]
+pub enum SourceEncoding {
+    /// Detect from a byte-order mark; files without one are UTF-8, or Latin-1 if not valid UTF-8
+    #[default]
+    Auto,
+    Utf8,
+    Utf16Le,
+    Latin1,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum KeywordCase {
+    Lower,
+    Upper,
+    #[default]
+    Preserve,
+    PascalInitial,
+}
+
+/// Casing of the digits in `$` hexadecimal literals
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum HexCase {
+    #[default]
+    Preserve,
+    Upper,
+    Lower,
+}
+
+/// Delimiters that `{ }` and `(* *)` comments are rewritten to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum CommentStyle {
+    #[default]
+    Preserve,
+    /// `{ ... }`
+    Brace,
+    /// `(* ... *)`
+    ParenStar,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default, Clone, Copy)]
 pub enum LineEnding {
     #[default]
     Auto,
@@ -29,6 +137,21 @@ pub enum LineEnding {
     Lf,
 }
 
+impl LineEnding {
+    /// Resolve `Auto` to the first line ending found in `source`.
+    /// Sources without any newline keep `Auto`, which falls back to the platform default.
+    pub fn resolve_for_source(self, source: &str) -> LineEnding {
+        if self != LineEnding::Auto {
+            return self;
+        }
+        match source.find('\n') {
+            Some(idx) if idx > 0 && source.as_bytes()[idx - 1] == b'\r' => LineEnding::Crlf,
+            Some(_) => LineEnding::Lf,
+            None => LineEnding::Auto,
+        }
+    }
+}
+
 impl fmt::Display for LineEnding {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let ending = match self {
@@ -46,7 +169,7 @@ impl fmt::Display for LineEnding {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct TextChangeOptions {
     pub comma: SpaceOperation,
@@ -61,6 +184,7 @@ pub struct TextChangeOptions {
     pub sub: SpaceOperation,                    // '-'
     pub mul: SpaceOperation,                    // '*'
     pub fdiv: SpaceOperation,                   // '/'
+    pub unary_sign: SpaceOperation,             // prefix '-' / '+', e.g. -x
     pub assign: SpaceOperation,                 // ':='
     pub assign_add: SpaceOperation,             // '+='
     pub assign_sub: SpaceOperation,             // '-='
@@ -68,12 +192,26 @@ pub struct TextChangeOptions {
     pub assign_div: SpaceOperation,             // '/='
     pub colon: SpaceOperation,                  // ':'
     pub colon_numeric_exception: bool, // Skip spacing for ':' when numbers before and after
+    pub label_colon: SpaceOperation,   // ':' after a statement label
+    pub paren_inner: SpaceOperation,   // inside '(' and ')'
+    pub bracket_inner: SpaceOperation, // inside '[' and ']'
+    pub call_paren: SpaceOperation,    // before '(' following an identifier, e.g. Foo(x)
+    pub keyword_paren: SpaceOperation, // before '(' following a keyword, e.g. if (x)
+    pub word_operator: SpaceOperation, // 'div', 'mod', 'and', 'or', 'not', 'xor'
+    pub amp: SpaceOperation,           // before '&' escaping a keyword, e.g. &begin
     pub space_inside_brace_comments: bool, // Add one space after '{' and before '}' for non-directive brace comments
     pub space_inside_paren_star_comments: bool, // Add one space after '(*' and before '*)' for non-directive paren-star comments
     pub space_after_line_comment_slashes: bool, // Ensure at least one space after // slash run, preserving existing spacing
     pub trim_trailing_whitespace: bool,
     pub ensure_single_trailing_newline: bool,
+    pub trim_leading_blank_lines: bool, // Drop blank lines before the first line of the file
+    pub normalize_line_endings: bool, // Rewrite every CRLF, CR, or LF to the configured line_ending
+    pub max_consecutive_blank_lines: usize, // Drop blank lines beyond this many in a row; 0 = unlimited
+    pub align_trailing_comments: bool, // Pad runs of code lines ending in // comments so the comments line up
+    pub verify_string_literals: bool, // Warn with line:col about string literals left open at a line break
+    pub preserve_directives: bool, // Keep single-line {$...} directives byte-exact; false collapses spaces and applies the comma rule
     pub enforce_word_casing: Vec<String>, // Canonical casing for identifiers; matching is case-insensitive
+    pub hex_case: HexCase,                // Case of the digits in $FF-style hexadecimal literals
 }
 
 impl Default for TextChangeOptions {
@@ -91,6 +229,7 @@ impl Default for TextChangeOptions {
             sub: SpaceOperation::BeforeAndAfter,        // '-'
             mul: SpaceOperation::BeforeAndAfter,        // '*'
             fdiv: SpaceOperation::BeforeAndAfter,       // '/'
+            unary_sign: SpaceOperation::NoChange,       // prefix '-' / '+', e.g. -x
             assign: SpaceOperation::BeforeAndAfter,     // ':='
             assign_add: SpaceOperation::BeforeAndAfter, // '+='
             assign_sub: SpaceOperation::BeforeAndAfter, // '-='
@@ -98,22 +237,78 @@ impl Default for TextChangeOptions {
             assign_div: SpaceOperation::BeforeAndAfter, // '/='
             colon: SpaceOperation::After,               // ':'
             colon_numeric_exception: true, // Skip spacing for ':' when numbers before and after
+            label_colon: SpaceOperation::After, // ':' after a statement label
+            paren_inner: SpaceOperation::NoChange, // inside '(' and ')'
+            bracket_inner: SpaceOperation::NoChange, // inside '[' and ']'
+            call_paren: SpaceOperation::NoChange, // before '(' following an identifier, e.g. Foo(x)
+            keyword_paren: SpaceOperation::NoChange, // before '(' following a keyword, e.g. if (x)
+            word_operator: SpaceOperation::NoChange, // 'div', 'mod', 'and', 'or', 'not', 'xor'
+            amp: SpaceOperation::NoChange, // before '&' escaping a keyword, e.g. &begin
             space_inside_brace_comments: true,
             space_inside_paren_star_comments: true,
             space_after_line_comment_slashes: true,
             trim_trailing_whitespace: true,
             ensure_single_trailing_newline: true,
+            trim_leading_blank_lines: false,
+            normalize_line_endings: false,
+            max_consecutive_blank_lines: 0,
+            align_trailing_comments: false,
+            verify_string_literals: false,
+            preserve_directives: true,
             enforce_word_casing: Vec::new(),
+            hex_case: HexCase::Preserve,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ParseDebugOptions {
+    pub line_column_positions: bool, // Print node positions as 1-based line:col instead of byte offsets
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SectionLayoutOptions {
+    pub enforce_blank_lines_before: bool, // Normalize blank lines before interface/implementation/initialization/finalization
+    pub blank_lines_before: usize,
+}
+
+impl Default for SectionLayoutOptions {
+    fn default() -> Self {
+        SectionLayoutOptions {
+            enforce_blank_lines_before: false,
+            blank_lines_before: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct UsesSectionOptions {
     pub uses_section_style: UsesSectionStyle,
     pub override_sorting_order: Vec<String>,
     pub module_names_to_update: Vec<String>,
+    /// Rename units before sorting, e.g. `["OldUnit", "NewUnit"]`; matching is case-insensitive
+    pub module_renames: Vec<(String, String)>,
+    pub uses_sort_key: UsesSortKey,
+    pub sort_preset: UsesSortPreset,
+    /// Put every module on its own line; when false, keep the original line grouping
+    pub reflow: bool,
+    /// With `CommaAtTheBeginning`, put the closing `;` on its own line instead of after the
+    /// last module
+    pub semicolon_on_own_line: bool,
+    /// Leave an empty line between the `uses` keyword and the first module
+    pub blank_line_after_uses: bool,
+    /// Sort project units after every other unit
+    pub project_units_last: bool,
+    /// Prefixes that mark project units; when empty, units without a dot are project units
+    pub project_unit_prefixes: Vec<String>,
+    /// How sections that contain comments are handled
+    pub uses_comment_policy: UsesCommentPolicy,
+    /// Units kept at the start of the section in this order, ahead of all sorting;
+    /// matching is case-insensitive
+    pub pinned_first_units: Vec<String>,
 }
 
 impl Default for UsesSectionOptions {
@@ -121,6 +316,16 @@ impl Default for UsesSectionOptions {
         UsesSectionOptions {
             uses_section_style: UsesSectionStyle::CommaAtTheEnd,
             override_sorting_order: Vec::new(),
+            uses_sort_key: UsesSortKey::UnitName,
+            sort_preset: UsesSortPreset::Alphabetical,
+            reflow: true,
+            semicolon_on_own_line: true,
+            blank_line_after_uses: false,
+            project_units_last: false,
+            project_unit_prefixes: Vec::new(),
+            uses_comment_policy: UsesCommentPolicy::Skip,
+            pinned_first_units: Vec::new(),
+            module_renames: Vec::new(),
             module_names_to_update: vec![
                 "System:Actions".to_string(),
                 "System:Analytics.AppAnalytics".to_string(),
@@ -492,7 +697,27 @@ impl Default for UsesSectionOptions {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl UsesSectionOptions {
+    /// Describe each `module_names_to_update` entry that is not a `Prefix:ShortName` pair with
+    /// exactly one colon and non-empty halves; such entries never match and are ignored.
+    pub fn malformed_module_name_updates(&self) -> Vec<String> {
+        self.module_names_to_update
+            .iter()
+            .filter_map(|mapping| match mapping.split_once(':') {
+                None => Some(format!("'{}' has no ':' separator", mapping)),
+                Some((_, name)) if name.contains(':') => {
+                    Some(format!("'{}' has more than one ':'", mapping))
+                }
+                Some((prefix, name)) if prefix.trim().is_empty() || name.trim().is_empty() => {
+                    Some(format!("'{}' has an empty prefix or unit name", mapping))
+                }
+                Some(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct TransformationOptions {
     pub enable_uses_section: bool,
@@ -510,6 +735,20 @@ pub struct TransformationOptions {
     pub skip_terminating_if_body_wrapping: bool,
     pub enable_inherited_call_expansion: bool,
     pub enable_text_transformations: bool,
+    /// Line up `:` and `=` in consecutive `var` / `const` declarations
+    pub align_declarations: bool,
+    /// Re-indent statements and `end` inside `begin`/`end` blocks by nesting depth
+    pub reindent_blocks: bool,
+    /// Append `// <qualified name>` after the `end;` of top-level method implementations
+    pub annotate_end: bool,
+    /// Drop empty statements that follow another `;` in `begin`/`end` blocks, e.g. `x;; y`
+    pub remove_redundant_semicolons: bool,
+    /// Put each statement of a `begin`/`end` block that shares a line with another on its own line
+    pub one_statement_per_line: bool,
+    /// Only apply text changes inside recognized code sections
+    pub preserve_unparsed_regions: bool,
+    pub keyword_case: KeywordCase,
+    pub comment_style: CommentStyle,
 }
 
 impl Default for TransformationOptions {
@@ -530,32 +769,64 @@ impl Default for TransformationOptions {
             skip_terminating_if_body_wrapping: true,
             enable_inherited_call_expansion: true,
             enable_text_transformations: true,
+            align_declarations: false,
+            reindent_blocks: false,
+            annotate_end: false,
+            remove_redundant_semicolons: false,
+            one_statement_per_line: false,
+            preserve_unparsed_regions: false,
+            keyword_case: KeywordCase::Preserve,
+            comment_style: CommentStyle::Preserve,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Options {
     pub indentation: String,
+    pub dialect: Dialect,
+    pub parse_policy: ParsePolicy,
     pub uses_section: UsesSectionOptions,
     pub line_ending: LineEnding,
+    pub encoding: SourceEncoding,
     pub transformations: TransformationOptions,
     pub text_changes: TextChangeOptions,
+    pub section_layout: SectionLayoutOptions,
+    pub parse_debug: ParseDebugOptions,
     pub exclude_files: Vec<String>,
-    pub custom_config_patterns: Vec<(String, String)>,
+    pub include_files: Vec<String>,
+    pub custom_config_patterns: Vec<(String, CustomConfig)>,
+    /// Leave files larger than this many bytes unchanged; 0 means unlimited
+    pub max_file_size_bytes: usize,
+    /// Comment text that turns formatting off until `format_on_marker`
+    pub format_off_marker: String,
+    /// Comment text that turns formatting back on
+    pub format_on_marker: String,
+    /// Comment text that, ahead of the first code, leaves the whole file unchanged
+    pub skip_file_marker: String,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Options {
             indentation: "  ".to_string(),
+            dialect: Dialect::Delphi,
+            parse_policy: ParsePolicy::Lenient,
             uses_section: UsesSectionOptions::default(),
             exclude_files: Vec::new(),
+            include_files: Vec::new(),
             custom_config_patterns: Vec::new(),
+            max_file_size_bytes: 0,
+            format_off_marker: "dfixxer:off".to_string(),
+            format_on_marker: "dfixxer:on".to_string(),
+            skip_file_marker: "dfixxer:skip".to_string(),
             line_ending: LineEnding::Auto,
+            encoding: SourceEncoding::Auto,
             transformations: TransformationOptions::default(),
             text_changes: TextChangeOptions::default(),
+            section_layout: SectionLayoutOptions::default(),
+            parse_debug: ParseDebugOptions::default(),
         }
     }
 }
@@ -644,22 +915,227 @@ pub fn should_exclude_file(
     }
 }
 
+/// Check if a file should be included based on include_files patterns
+///
+/// Patterns are matched relative to the configuration file's directory.
+/// An empty pattern list includes every file.
+///
+/// # Arguments
+/// * `include_patterns` - A slice of glob patterns to match against
+/// * `file_path` - The absolute or relative path to the file to check
+/// * `config_path` - The path to the configuration file (for determining base directory)
+///
+/// # Returns
+/// * `true` if the file should be included, `false` otherwise
+pub fn should_include_file(
+    include_patterns: &[String],
+    file_path: &str,
+    config_path: Option<&str>,
+) -> bool {
+    if include_patterns.is_empty() {
+        return true;
+    }
+
+    if match_file_patterns(include_patterns, file_path, config_path).is_some() {
+        true
+    } else {
+        log::info!("File '{}' not matched by any include pattern", file_path);
+        false
+    }
+}
+
+/// File name of the gitignore-style exclusion list looked up next to processed files
+pub const IGNORE_FILE_NAME: &str = ".dfixxerignore";
+
+/// Parse the contents of a `.dfixxerignore` file into glob patterns
+///
+/// Each non-blank line is a pattern; lines starting with `#` are comments.
+pub fn parse_ignore_patterns(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Find the nearest `.dfixxerignore` by walking up from the file's directory
+pub fn find_ignore_file_for_filename(filename: &str) -> Option<PathBuf> {
+    let file_path = std::path::absolute(filename).ok()?;
+    let mut dir = file_path.parent();
+    while let Some(current) = dir {
+        let candidate = current.join(IGNORE_FILE_NAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        dir = current.parent();
+    }
+    None
+}
+
+/// Check if a file should be excluded by the nearest `.dfixxerignore` file
+///
+/// Patterns are matched relative to the ignore file's directory, like `exclude_files`
+/// patterns are matched relative to the configuration file.
+///
+/// # Arguments
+/// * `file_path` - The absolute or relative path to the file to check
+///
+/// # Returns
+/// * `true` if an ignore file pattern matches the file, `false` otherwise
+pub fn should_exclude_by_ignore_file(file_path: &str) -> bool {
+    let Some(ignore_path) = find_ignore_file_for_filename(file_path) else {
+        return false;
+    };
+    let content = match fs::read_to_string(&ignore_path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::warn!(
+                "Failed to read ignore file '{}': {}",
+                ignore_path.display(),
+                e
+            );
+            return false;
+        }
+    };
+
+    let patterns = parse_ignore_patterns(&content);
+    let Ok(absolute_file_path) = std::path::absolute(file_path) else {
+        return false;
+    };
+    should_exclude_file(
+        &patterns,
+        &absolute_file_path.to_string_lossy(),
+        Some(&ignore_path.to_string_lossy()),
+    )
+}
+
+fn merge_toml_tables(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => {
+                merge_toml_tables(base_table, override_table);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// A renamed option that is still honored: loading a config maps it onto its replacement
+struct DeprecatedKey {
+    /// Dotted path of the old key
+    old: &'static str,
+    /// Dotted path of the key that replaces it
+    new: &'static str,
+    /// Converts the old value, or returns `None` when it cannot be mapped
+    convert: fn(&toml::Value) -> Option<toml::Value>,
+}
+
+/// `space_after_comma = true` from before the `comma` spacing rule existed
+fn space_after_comma_to_comma(value: &toml::Value) -> Option<toml::Value> {
+    let operation = if value.as_bool()? {
+        "After"
+    } else {
+        "NoChange"
+    };
+    Some(toml::Value::String(operation.to_string()))
+}
+
+const DEPRECATED_KEYS: &[DeprecatedKey] = &[
+    DeprecatedKey {
+        old: "space_after_comma",
+        new: "text_changes.comma",
+        convert: space_after_comma_to_comma,
+    },
+    DeprecatedKey {
+        old: "text_changes.space_after_comma",
+        new: "text_changes.comma",
+        convert: space_after_comma_to_comma,
+    },
+];
+
+fn warn_config(message: &str) {
+    log::warn!("{}", message);
+    eprintln!("Warning: {}", message);
+}
+
+/// Table holding the last key of a dotted path, created on the way when `create` is set
+fn parent_table<'a>(
+    table: &'a mut toml::Table,
+    path: &'a str,
+    create: bool,
+) -> Option<(&'a mut toml::Table, &'a str)> {
+    let (parents, key) = match path.rsplit_once('.') {
+        Some((parents, key)) => (Some(parents), key),
+        None => (None, path),
+    };
+    let mut table = table;
+    for name in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        if create && !table.contains_key(name) {
+            table.insert(name.to_string(), toml::Value::Table(toml::Table::new()));
+        }
+        table = table.get_mut(name)?.as_table_mut()?;
+    }
+    Some((table, key))
+}
+
+/// Move deprecated keys onto their replacements, warning about each one.
+/// A replacement that is already set wins over the deprecated key.
+/// Returns whether any deprecated key was found.
+fn migrate_deprecated_keys(table: &mut toml::Table) -> bool {
+    let mut found = false;
+    for deprecated in DEPRECATED_KEYS {
+        let Some(value) =
+            parent_table(table, deprecated.old, false).and_then(|(parent, key)| parent.remove(key))
+        else {
+            continue;
+        };
+        found = true;
+
+        let already_set = parent_table(table, deprecated.new, false)
+            .is_some_and(|(parent, key)| parent.contains_key(key));
+        match (deprecated.convert)(&value) {
+            Some(_) if already_set => warn_config(&format!(
+                "Ignoring deprecated option '{}' because '{}' is also set",
+                deprecated.old, deprecated.new
+            )),
+            Some(converted) => {
+                warn_config(&format!(
+                    "Option '{}' is deprecated; use '{} = {}' instead",
+                    deprecated.old, deprecated.new, converted
+                ));
+                if let Some((parent, key)) = parent_table(table, deprecated.new, true) {
+                    parent.insert(key.to_string(), converted);
+                }
+            }
+            None => warn_config(&format!(
+                "Ignoring deprecated option '{}' with unsupported value {}; use '{}' instead",
+                deprecated.old, value, deprecated.new
+            )),
+        }
+    }
+    found
+}
+
 /// Find a custom configuration file for a file based on custom_config_patterns
 ///
 /// Patterns are matched relative to the configuration file's directory.
 ///
 /// # Arguments
-/// * `custom_patterns` - A slice of (pattern, config_path) pairs
+/// * `custom_patterns` - A slice of (pattern, config path or inline overrides) pairs
 /// * `file_path` - The absolute or relative path to the file to check
 /// * `config_path` - The path to the configuration file (for determining base directory)
 ///
 /// # Returns
-/// * `Some(config_path)` if the file matches a pattern, `None` otherwise
+/// * `Some(custom_config)` if the file matches a pattern, with a relative config path resolved
+///   against the configuration file's directory; `None` otherwise
 pub fn find_custom_config_for_file(
-    custom_patterns: &[(String, String)],
+    custom_patterns: &[(String, CustomConfig)],
     file_path: &str,
     config_path: Option<&str>,
-) -> Option<String> {
+) -> Option<CustomConfig> {
     if custom_patterns.is_empty() {
         return None;
     }
@@ -671,8 +1147,20 @@ pub fn find_custom_config_for_file(
 
     if let Some(matched_pattern) = match_file_patterns(&patterns, file_path, config_path) {
         // Find the config path for the matched pattern
-        for (pattern, custom_config_path) in custom_patterns {
+        for (pattern, custom_config) in custom_patterns {
             if pattern == &matched_pattern {
+                let custom_config_path = match custom_config {
+                    CustomConfig::Path(path) => path,
+                    CustomConfig::Overrides(overrides) => {
+                        log::info!(
+                            "File '{}' matched custom config pattern '{}', using inline overrides",
+                            file_path,
+                            pattern
+                        );
+                        return Some(CustomConfig::Overrides(overrides.clone()));
+                    }
+                };
+
                 // Resolve the custom config path relative to the current config's directory if it's relative
                 let resolved_path = if Path::new(custom_config_path).is_absolute() {
                     custom_config_path.clone()
@@ -696,7 +1184,7 @@ pub fn find_custom_config_for_file(
                     pattern,
                     resolved_path
                 );
-                return Some(resolved_path);
+                return Some(CustomConfig::Path(resolved_path));
             }
         }
     }
@@ -704,20 +1192,58 @@ pub fn find_custom_config_for_file(
     None
 }
 
+/// Error for a TOML parse failure, positioned at the offending value when the error has a span
+fn config_parse_error(path: &Path, content: &str, err: toml::de::Error) -> DFixxerError {
+    match err.span() {
+        Some(span) => {
+            let before = &content[..span.start.min(content.len())];
+            let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+            DFixxerError::InvalidConfig {
+                path: path.display().to_string(),
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+                message: err.message().to_string(),
+            }
+        }
+        None => DFixxerError::ConfigError(format!("Failed to parse config file: {}", err)),
+    }
+}
+
 impl Options {
     /// Load options from a TOML file, using defaults for missing fields
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, DFixxerError> {
+        Self::load_from_file_with_deprecations(path).map(|(options, _)| options)
+    }
+
+    /// Load options from a TOML file like `load_from_file`, also returning whether the file
+    /// uses deprecated keys
+    pub fn load_from_file_with_deprecations<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, bool), DFixxerError> {
+        let path = path.as_ref();
         let content = fs::read_to_string(path)
             .map_err(|e| DFixxerError::ConfigError(format!("Failed to read config file: {}", e)))?;
-        let options: Options = toml::from_str(&content).map_err(|e| {
-            DFixxerError::ConfigError(format!("Failed to parse config file: {}", e))
-        })?;
+        let parse_error = |e: toml::de::Error| config_parse_error(path, &content, e);
+        let mut table: toml::Table = toml::from_str(&content).map_err(parse_error)?;
+        // Without deprecated keys, deserialize the text so errors keep their line and column
+        let deprecated = migrate_deprecated_keys(&mut table);
+        let options: Options = if deprecated {
+            table.try_into().map_err(parse_error)?
+        } else {
+            toml::from_str(&content).map_err(parse_error)?
+        };
+        for problem in options.uses_section.malformed_module_name_updates() {
+            warn_config(&format!(
+                "Ignoring uses_section.module_names_to_update entry {}; expected \"Prefix:ShortName\"",
+                problem
+            ));
+        }
 
         // If uses_section_style is not set, use default
         // (TOML deserialization will use default if missing, but for robustness)
         // If you want to handle string values, you can add custom logic here.
 
-        Ok(options)
+        Ok((options, deprecated))
     }
 
     /// Create a default configuration file
@@ -727,15 +1253,38 @@ impl Options {
         Ok(())
     }
 
+    /// Create a configuration file from the commented template, which documents each option
+    /// and leaves `module_names_to_update` at its built-in default
+    pub fn create_template_config<P: AsRef<Path>>(path: P) -> Result<(), DFixxerError> {
+        fs::write(path, CONFIG_TEMPLATE)
+            .map_err(|e| DFixxerError::ConfigError(format!("Failed to write config file: {}", e)))
+    }
+
+    /// Layer an inline override table over these options. Nested tables are merged key by key;
+    /// any other value replaces the base value.
+    pub fn with_overrides(&self, overrides: &toml::Table) -> Result<Self, DFixxerError> {
+        let mut merged = toml::Table::try_from(self)
+            .map_err(|e| DFixxerError::ConfigError(format!("Failed to serialize config: {}", e)))?;
+        merge_toml_tables(&mut merged, overrides);
+        merged.try_into().map_err(|e| {
+            DFixxerError::ConfigError(format!("Failed to apply config overrides: {}", e))
+        })
+    }
+
     /// Load options from a TOML file, or return default if file doesn't exist
     pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
         Self::load_from_file(path).unwrap_or_default()
     }
 
+    /// Serialize options to the TOML written by `init-config`
+    pub fn to_toml_string(&self) -> Result<String, DFixxerError> {
+        toml::to_string_pretty(self)
+            .map_err(|e| DFixxerError::ConfigError(format!("Failed to serialize config: {}", e)))
+    }
+
     /// Save options to a TOML file
     fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), DFixxerError> {
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| DFixxerError::ConfigError(format!("Failed to serialize config: {}", e)))?;
+        let content = self.to_toml_string()?;
         fs::write(path, content).map_err(|e| {
             DFixxerError::ConfigError(format!("Failed to write config file: {}", e))
         })?;
@@ -774,9 +1323,10 @@ mod tests {
             Vec::<String>::new()
         );
         assert_eq!(options.exclude_files, Vec::<String>::new());
+        assert_eq!(options.include_files, Vec::<String>::new());
         assert_eq!(
             options.custom_config_patterns,
-            Vec::<(String, String)>::new()
+            Vec::<(String, CustomConfig)>::new()
         );
         assert!(!options.uses_section.module_names_to_update.is_empty());
         assert_eq!(options.uses_section.module_names_to_update.len(), 365);
@@ -799,7 +1349,14 @@ mod tests {
                 .contains(&"Vcl.Touch:KeyboardTypes".to_string())
         );
         assert_eq!(options.line_ending, LineEnding::Auto);
+        assert_eq!(options.encoding, SourceEncoding::Auto);
         assert_eq!(options.text_changes.comma, SpaceOperation::After);
+        assert_eq!(options.text_changes.label_colon, SpaceOperation::After);
+        assert_eq!(options.text_changes.paren_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.bracket_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.call_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.keyword_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.word_operator, SpaceOperation::NoChange);
         assert!(options.transformations.enable_local_routine_spacing);
         assert!(options.transformations.enable_local_routine_indentation);
         assert!(options.transformations.enable_inline_local_var_definitions);
@@ -810,10 +1367,18 @@ mod tests {
         assert!(options.transformations.skip_terminating_while_body_wrapping);
         assert!(options.transformations.skip_terminating_if_body_wrapping);
         assert!(options.transformations.enable_inherited_call_expansion);
+        assert_eq!(options.transformations.keyword_case, KeywordCase::Preserve);
+        assert_eq!(
+            options.transformations.comment_style,
+            CommentStyle::Preserve
+        );
+        assert!(!options.transformations.preserve_unparsed_regions);
         assert!(options.text_changes.space_inside_brace_comments);
         assert!(options.text_changes.space_inside_paren_star_comments);
         assert!(options.text_changes.space_after_line_comment_slashes);
         assert!(options.text_changes.ensure_single_trailing_newline);
+        assert!(!options.text_changes.normalize_line_endings);
+        assert_eq!(options.text_changes.max_consecutive_blank_lines, 0);
         assert!(options.text_changes.enforce_word_casing.is_empty());
     }
 
@@ -830,14 +1395,22 @@ mod tests {
             Vec::<String>::new()
         );
         assert_eq!(options.exclude_files, Vec::<String>::new());
+        assert_eq!(options.include_files, Vec::<String>::new());
         assert_eq!(
             options.custom_config_patterns,
-            Vec::<(String, String)>::new()
+            Vec::<(String, CustomConfig)>::new()
         );
         assert!(!options.uses_section.module_names_to_update.is_empty());
         assert_eq!(options.uses_section.module_names_to_update.len(), 365);
         assert_eq!(options.line_ending, LineEnding::Auto);
+        assert_eq!(options.encoding, SourceEncoding::Auto);
         assert_eq!(options.text_changes.comma, SpaceOperation::After);
+        assert_eq!(options.text_changes.label_colon, SpaceOperation::After);
+        assert_eq!(options.text_changes.paren_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.bracket_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.call_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.keyword_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.word_operator, SpaceOperation::NoChange);
         assert!(options.transformations.enable_local_routine_spacing);
         assert!(options.transformations.enable_local_routine_indentation);
         assert!(options.transformations.enable_inline_local_var_definitions);
@@ -852,9 +1425,116 @@ mod tests {
         assert!(options.text_changes.space_inside_paren_star_comments);
         assert!(options.text_changes.space_after_line_comment_slashes);
         assert!(options.text_changes.ensure_single_trailing_newline);
+        assert!(!options.text_changes.normalize_line_endings);
+        assert_eq!(options.text_changes.max_consecutive_blank_lines, 0);
         assert!(options.text_changes.enforce_word_casing.is_empty());
     }
 
+    #[test]
+    fn test_template_config_loads_as_defaults() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("dfixxer.toml");
+
+        Options::create_template_config(&file_path).unwrap();
+        let loaded = Options::load_from_file(&file_path).unwrap();
+        assert_eq!(
+            toml::to_string_pretty(&loaded).unwrap(),
+            toml::to_string_pretty(&Options::default()).unwrap()
+        );
+
+        fs::remove_dir_all(&temp_path).unwrap();
+    }
+
+    #[test]
+    fn test_deprecated_space_after_comma_maps_to_comma() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("legacy.toml");
+
+        fs::write(
+            &file_path,
+            "space_after_comma = false\nindentation = \"\\t\"\n",
+        )
+        .unwrap();
+        let options = Options::load_from_file(&file_path).unwrap();
+        assert_eq!(options.text_changes.comma, SpaceOperation::NoChange);
+        assert_eq!(options.indentation, "\t");
+
+        fs::write(&file_path, "[text_changes]\nspace_after_comma = true\n").unwrap();
+        let options = Options::load_from_file(&file_path).unwrap();
+        assert_eq!(options.text_changes.comma, SpaceOperation::After);
+
+        // The replacement wins when both are set
+        fs::write(
+            &file_path,
+            "space_after_comma = true\n[text_changes]\ncomma = \"Remove\"\n",
+        )
+        .unwrap();
+        let (options, deprecated) = Options::load_from_file_with_deprecations(&file_path).unwrap();
+        assert_eq!(options.text_changes.comma, SpaceOperation::Remove);
+        assert!(deprecated);
+
+        fs::write(&file_path, "[text_changes]\ncomma = \"After\"\n").unwrap();
+        let (_, deprecated) = Options::load_from_file_with_deprecations(&file_path).unwrap();
+        assert!(!deprecated);
+
+        fs::remove_dir_all(&temp_path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_config_value_reports_line_and_column() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("invalid_value.toml");
+        fs::write(
+            &file_path,
+            "indentation = \"  \"\n\n[uses_section]\nreflow = \"yes\"\n",
+        )
+        .unwrap();
+
+        let err = Options::load_from_file(&file_path).unwrap_err();
+        assert!(matches!(
+            err,
+            DFixxerError::InvalidConfig {
+                line: 4,
+                column: 10,
+                ..
+            }
+        ));
+        let message = err.to_string();
+        assert!(
+            message.starts_with(&format!("{}:4:10: ", file_path.display())),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_malformed_module_name_updates_are_reported() {
+        let uses_section = UsesSectionOptions {
+            module_names_to_update: vec![
+                "System:Classes".to_string(),
+                "SystemClasses".to_string(),
+                ":Classes".to_string(),
+                "Vcl:".to_string(),
+                "A:B:C".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            uses_section.malformed_module_name_updates(),
+            vec![
+                "'SystemClasses' has no ':' separator",
+                "':Classes' has an empty prefix or unit name",
+                "'Vcl:' has an empty prefix or unit name",
+                "'A:B:C' has more than one ':'",
+            ]
+        );
+        assert!(
+            UsesSectionOptions::default()
+                .malformed_module_name_updates()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_save_and_load() {
         let temp_path = create_unique_temp_dir();
@@ -862,17 +1542,35 @@ mod tests {
 
         let original_options = Options {
             indentation: "    ".to_string(), // 4 spaces
+            dialect: Dialect::FreePascal,
+            parse_policy: ParsePolicy::Strict,
             uses_section: UsesSectionOptions {
                 uses_section_style: UsesSectionStyle::CommaAtTheBeginning,
                 override_sorting_order: vec!["test_error".to_string()],
                 module_names_to_update: Vec::new(),
+                module_renames: Vec::new(),
+                uses_sort_key: UsesSortKey::InPath,
+                sort_preset: UsesSortPreset::LengthThenAlpha,
+                reflow: false,
+                semicolon_on_own_line: false,
+                blank_line_after_uses: true,
+                project_units_last: true,
+                project_unit_prefixes: vec!["MyCompany".to_string()],
+                uses_comment_policy: UsesCommentPolicy::Preserve,
+                pinned_first_units: vec!["FastMM4".to_string()],
             },
             exclude_files: vec!["*.tmp".to_string(), "backup/*".to_string()],
+            include_files: vec!["src/**/*.pas".to_string()],
             custom_config_patterns: vec![(
                 "test/*.pas".to_string(),
-                "test_config.toml".to_string(),
+                CustomConfig::Path("test_config.toml".to_string()),
             )],
+            max_file_size_bytes: 1_000_000,
+            format_off_marker: "fmt: off".to_string(),
+            format_on_marker: "fmt: on".to_string(),
+            skip_file_marker: "generated".to_string(),
             line_ending: LineEnding::Lf,
+            encoding: SourceEncoding::Latin1,
             transformations: TransformationOptions::default(),
             text_changes: TextChangeOptions {
                 comma: SpaceOperation::NoChange,
@@ -882,6 +1580,13 @@ mod tests {
                 enforce_word_casing: vec!["HTTPClient".to_string(), "iOS".to_string()],
                 ..Default::default()
             },
+            section_layout: SectionLayoutOptions {
+                enforce_blank_lines_before: true,
+                blank_lines_before: 2,
+            },
+            parse_debug: ParseDebugOptions {
+                line_column_positions: true,
+            },
         };
 
         // Save options
@@ -908,13 +1613,24 @@ mod tests {
             loaded_options.exclude_files,
             vec!["*.tmp".to_string(), "backup/*".to_string()]
         );
+        assert_eq!(
+            loaded_options.include_files,
+            vec!["src/**/*.pas".to_string()]
+        );
         assert_eq!(
             loaded_options.custom_config_patterns,
-            vec![("test/*.pas".to_string(), "test_config.toml".to_string())]
+            vec![(
+                "test/*.pas".to_string(),
+                CustomConfig::Path("test_config.toml".to_string())
+            )]
         );
         assert_eq!(loaded_options.line_ending, LineEnding::Lf);
+        assert_eq!(loaded_options.encoding, SourceEncoding::Latin1);
         assert_eq!(loaded_options.text_changes.comma, SpaceOperation::NoChange);
         assert!(!loaded_options.text_changes.ensure_single_trailing_newline);
+        assert!(loaded_options.section_layout.enforce_blank_lines_before);
+        assert_eq!(loaded_options.section_layout.blank_lines_before, 2);
+        assert!(loaded_options.parse_debug.line_column_positions);
         assert_eq!(
             loaded_options.text_changes.enforce_word_casing,
             vec!["HTTPClient".to_string(), "iOS".to_string()]
@@ -1022,7 +1738,14 @@ enable_uses_section = false
         assert!(!options.uses_section.module_names_to_update.is_empty());
         assert_eq!(options.uses_section.module_names_to_update.len(), 365);
         assert_eq!(options.line_ending, LineEnding::Auto);
+        assert_eq!(options.encoding, SourceEncoding::Auto);
         assert_eq!(options.text_changes.comma, SpaceOperation::After);
+        assert_eq!(options.text_changes.label_colon, SpaceOperation::After);
+        assert_eq!(options.text_changes.paren_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.bracket_inner, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.call_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.keyword_paren, SpaceOperation::NoChange);
+        assert_eq!(options.text_changes.word_operator, SpaceOperation::NoChange);
         assert!(options.transformations.enable_local_routine_spacing);
         assert!(options.transformations.enable_local_routine_indentation);
         assert!(options.transformations.enable_inline_local_var_definitions);
@@ -1034,6 +1757,8 @@ enable_uses_section = false
         assert!(options.transformations.skip_terminating_if_body_wrapping);
         assert!(options.transformations.enable_inherited_call_expansion);
         assert!(options.text_changes.ensure_single_trailing_newline);
+        assert!(!options.text_changes.normalize_line_endings);
+        assert_eq!(options.text_changes.max_consecutive_blank_lines, 0);
         assert!(options.text_changes.enforce_word_casing.is_empty());
     }
 
@@ -1059,6 +1784,58 @@ enable_inline_local_var_definitions = false
         fs::remove_dir(&temp_path).ok();
     }
 
+    #[test]
+    fn test_transformations_keyword_case_config() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("keyword_case_config.toml");
+
+        fs::write(
+            &file_path,
+            r#"
+[transformations]
+keyword_case = "PascalInitial"
+"#,
+        )
+        .unwrap();
+
+        let options = Options::load_from_file(&file_path).unwrap();
+        assert_eq!(
+            options.transformations.keyword_case,
+            KeywordCase::PascalInitial
+        );
+        assert!(options.transformations.enable_uses_section);
+
+        fs::remove_file(&file_path).ok();
+        fs::remove_dir(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_section_layout_config() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("section_layout_config.toml");
+
+        fs::write(
+            &file_path,
+            r#"
+[section_layout]
+enforce_blank_lines_before = true
+blank_lines_before = 2
+"#,
+        )
+        .unwrap();
+
+        let options = Options::load_from_file(&file_path).unwrap();
+        assert!(options.section_layout.enforce_blank_lines_before);
+        assert_eq!(options.section_layout.blank_lines_before, 2);
+
+        let defaults = SectionLayoutOptions::default();
+        assert!(!defaults.enforce_blank_lines_before);
+        assert_eq!(defaults.blank_lines_before, 1);
+
+        fs::remove_file(&file_path).ok();
+        fs::remove_dir(&temp_path).ok();
+    }
+
     #[test]
     fn test_text_changes_enforce_word_casing_config() {
         let temp_path = create_unique_temp_dir();
@@ -1172,6 +1949,68 @@ ensure_single_trailing_newline = false
         ));
     }
 
+    #[test]
+    fn test_should_include_file() {
+        let patterns = vec!["src/*.pas".to_string(), "*.dpr".to_string()];
+
+        assert!(should_include_file(&patterns, "src/main.pas", None));
+        assert!(should_include_file(&patterns, "project.dpr", None));
+        assert!(!should_include_file(&patterns, "test/main.pas", None));
+
+        // Empty list includes everything
+        assert!(should_include_file(&[], "anything.pas", None));
+    }
+
+    #[test]
+    fn test_include_and_exclude_files_combined() {
+        let include_patterns = vec!["src/*.pas".to_string()];
+        let exclude_patterns = vec!["src/generated_*.pas".to_string()];
+        let config_path = "project/dfixxer.toml";
+
+        let is_processed = |file: &str| {
+            should_include_file(&include_patterns, file, Some(config_path))
+                && !should_exclude_file(&exclude_patterns, file, Some(config_path))
+        };
+
+        assert!(is_processed("src/main.pas"));
+        assert!(!is_processed("src/generated_types.pas"));
+        assert!(!is_processed("test/main.pas"));
+    }
+
+    #[test]
+    fn test_parse_ignore_patterns() {
+        let content = "# generated code\n*.generated.pas\n\n  vendor/*  \n#backup/*\n";
+        assert_eq!(
+            parse_ignore_patterns(content),
+            vec!["*.generated.pas".to_string(), "vendor/*".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_should_exclude_by_ignore_file() {
+        let temp_path = create_unique_temp_dir();
+        fs::write(
+            temp_path.join(IGNORE_FILE_NAME),
+            "# Skip generated and vendored code\n*.generated.pas\n\nvendor/*\n",
+        )
+        .unwrap();
+        let nested = temp_path.join("src");
+        fs::create_dir_all(&nested).unwrap();
+
+        let path_str = |name: &str| temp_path.join(name).to_string_lossy().to_string();
+        assert!(should_exclude_by_ignore_file(&path_str(
+            "src/types.generated.pas"
+        )));
+        assert!(should_exclude_by_ignore_file(&path_str("vendor/lib.pas")));
+        assert!(!should_exclude_by_ignore_file(&path_str("src/main.pas")));
+        assert_eq!(
+            find_ignore_file_for_filename(&path_str("src/main.pas")),
+            Some(temp_path.join(IGNORE_FILE_NAME))
+        );
+
+        fs::remove_dir_all(&temp_path).unwrap();
+    }
+
     #[test]
     fn test_invalid_glob_pattern() {
         // Invalid pattern should be ignored (not crash)
@@ -1205,6 +2044,28 @@ ensure_single_trailing_newline = false
         assert_eq!(options_auto.line_ending.to_string(), "\n");
     }
 
+    #[test]
+    fn test_line_ending_auto_resolves_from_source() {
+        let crlf_source = "unit A;\r\ninterface\nend.";
+        assert_eq!(
+            LineEnding::Auto.resolve_for_source(crlf_source),
+            LineEnding::Crlf
+        );
+        assert_eq!(
+            LineEnding::Auto.resolve_for_source("unit A;\ninterface\r\nend."),
+            LineEnding::Lf
+        );
+        assert_eq!(
+            LineEnding::Auto.resolve_for_source("unit A;"),
+            LineEnding::Auto
+        );
+        // Explicit settings are never overridden by the source
+        assert_eq!(
+            LineEnding::Lf.resolve_for_source(crlf_source),
+            LineEnding::Lf
+        );
+    }
+
     #[test]
     fn test_config_with_exclude_files() {
         let temp_path = create_unique_temp_dir();
@@ -1242,7 +2103,10 @@ enable_uses_section = true
         assert!(find_custom_config_for_file(&empty_patterns, "test.pas", None).is_none());
 
         // Test with single pattern match
-        let single_pattern = vec![("test/*.pas".to_string(), "custom.toml".to_string())];
+        let single_pattern = vec![(
+            "test/*.pas".to_string(),
+            CustomConfig::Path("custom.toml".to_string()),
+        )];
         let result = find_custom_config_for_file(
             &single_pattern,
             "test/file.pas",
@@ -1253,22 +2117,28 @@ enable_uses_section = true
             .join("custom.toml")
             .to_string_lossy()
             .to_string();
-        assert_eq!(result, Some(expected));
+        assert_eq!(result, Some(CustomConfig::Path(expected)));
 
         // Test with absolute path
         let absolute_pattern = vec![(
             "test/*.pas".to_string(),
-            "/absolute/custom.toml".to_string(),
+            CustomConfig::Path("/absolute/custom.toml".to_string()),
         )];
         let result = find_custom_config_for_file(
             &absolute_pattern,
             "test/file.pas",
             Some("project/dfixxer.toml"),
         );
-        assert_eq!(result, Some("/absolute/custom.toml".to_string()));
+        assert_eq!(
+            result,
+            Some(CustomConfig::Path("/absolute/custom.toml".to_string()))
+        );
 
         // Test with no match
-        let no_match_pattern = vec![("other/*.pas".to_string(), "custom.toml".to_string())];
+        let no_match_pattern = vec![(
+            "other/*.pas".to_string(),
+            CustomConfig::Path("custom.toml".to_string()),
+        )];
         let result = find_custom_config_for_file(
             &no_match_pattern,
             "test/file.pas",
@@ -1278,9 +2148,18 @@ enable_uses_section = true
 
         // Test with multiple patterns
         let multiple_patterns = vec![
-            ("test/*.pas".to_string(), "test_custom.toml".to_string()),
-            ("src/*.pas".to_string(), "src_custom.toml".to_string()),
-            ("backup*.pas".to_string(), "backup_custom.toml".to_string()),
+            (
+                "test/*.pas".to_string(),
+                CustomConfig::Path("test_custom.toml".to_string()),
+            ),
+            (
+                "src/*.pas".to_string(),
+                CustomConfig::Path("src_custom.toml".to_string()),
+            ),
+            (
+                "backup*.pas".to_string(),
+                CustomConfig::Path("backup_custom.toml".to_string()),
+            ),
         ];
         let result = find_custom_config_for_file(
             &multiple_patterns,
@@ -1291,11 +2170,11 @@ enable_uses_section = true
             .join("src_custom.toml")
             .to_string_lossy()
             .to_string();
-        assert_eq!(result, Some(expected));
+        assert_eq!(result, Some(CustomConfig::Path(expected)));
 
         // Test without base config path
         let result = find_custom_config_for_file(&single_pattern, "test/file.pas", None);
-        assert_eq!(result, Some("custom.toml".to_string()));
+        assert_eq!(result, Some(CustomConfig::Path("custom.toml".to_string())));
     }
 
     #[test]
@@ -1324,20 +2203,23 @@ enable_uses_section = true
         assert_eq!(options.custom_config_patterns.len(), 3);
         assert_eq!(
             options.custom_config_patterns[0],
-            ("test/*.pas".to_string(), "test_config.toml".to_string())
+            (
+                "test/*.pas".to_string(),
+                CustomConfig::Path("test_config.toml".to_string())
+            )
         );
         assert_eq!(
             options.custom_config_patterns[1],
             (
                 "src/**/*.pas".to_string(),
-                "../src/dfixxer.toml".to_string()
+                CustomConfig::Path("../src/dfixxer.toml".to_string())
             )
         );
         assert_eq!(
             options.custom_config_patterns[2],
             (
                 "legacy/*.pas".to_string(),
-                "/absolute/legacy_config.toml".to_string()
+                CustomConfig::Path("/absolute/legacy_config.toml".to_string())
             )
         );
 
@@ -1346,6 +2228,50 @@ enable_uses_section = true
         fs::remove_dir(&temp_path).ok();
     }
 
+    #[test]
+    fn test_custom_config_patterns_inline_overrides() {
+        let temp_path = create_unique_temp_dir();
+        let file_path = temp_path.join("dfixxer.toml");
+        fs::write(
+            &file_path,
+            r#"
+indentation = "    "
+custom_config_patterns = [
+    ["legacy/*.pas", "legacy.toml"],
+    ["tests/*.pas", { text_changes = { comma = "NoChange" }, transformations = { enable_uses_section = false } }],
+]
+
+[text_changes]
+semi_colon = "NoChange"
+"#,
+        )
+        .unwrap();
+        let config_path = file_path.to_string_lossy().to_string();
+
+        let options = Options::load_from_file(&file_path).unwrap();
+        assert_eq!(
+            options.custom_config_patterns[0].1,
+            CustomConfig::Path("legacy.toml".to_string())
+        );
+
+        let Some(CustomConfig::Overrides(overrides)) = find_custom_config_for_file(
+            &options.custom_config_patterns,
+            &temp_path.join("tests").join("a.pas").to_string_lossy(),
+            Some(&config_path),
+        ) else {
+            panic!("Expected inline overrides for tests/a.pas");
+        };
+        let merged = options.with_overrides(&overrides).unwrap();
+        assert_eq!(merged.text_changes.comma, SpaceOperation::NoChange);
+        assert!(!merged.transformations.enable_uses_section);
+        // Values the overrides do not name come from the base config
+        assert_eq!(merged.indentation, "    ");
+        assert_eq!(merged.text_changes.semi_colon, SpaceOperation::NoChange);
+        assert!(merged.transformations.enable_procedure_section);
+
+        fs::remove_dir_all(&temp_path).ok();
+    }
+
     #[test]
     fn test_config_loading_with_different_line_endings() {
         // Test loading config with Auto
@@ -1377,6 +2303,7 @@ comma = "After"
 
         let options = Options::load_from_file(&auto_config_path).unwrap();
         assert_eq!(options.line_ending, LineEnding::Auto);
+        assert_eq!(options.encoding, SourceEncoding::Auto);
 
         // Test loading config with Lf
         let lf_config_path = temp_path.join("lf_config.toml");
@@ -1,4 +1,5 @@
 use crate::dfixxer_error::DFixxerError;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 use tree_sitter::{Node, Parser, Tree};
@@ -8,6 +9,8 @@ use tree_sitter_pascal::LANGUAGE;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Kind {
     Uses,
+    Requires,
+    Contains,
     Program,
     Unit,
     Interface,
@@ -21,6 +24,10 @@ pub enum Kind {
     ProcedureDeclaration,
     FunctionDeclaration,
     Identifier,
+    Var,
+    Const,
+    Type,
+    Declaration,
 }
 
 /// Struct to store parsed text block information independent of tree-sitter types.
@@ -58,6 +65,19 @@ impl std::fmt::Debug for ParsedNode {
     }
 }
 
+impl ParsedNode {
+    /// The node's span as 1-based `line:col-line:col`
+    pub fn line_column_span(&self) -> String {
+        format!(
+            "{}:{}-{}:{}",
+            self.start_row + 1,
+            self.start_column + 1,
+            self.end_row + 1,
+            self.end_column + 1
+        )
+    }
+}
+
 /// Struct representing a code section (uses or program) in the parsed text.
 /// The section type can be determined from the keyword's Kind.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -67,7 +87,7 @@ pub struct CodeSection {
 }
 
 /// Struct representing the result of parsing.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ParseResult {
     pub code_sections: Vec<CodeSection>,
 }
@@ -82,6 +102,11 @@ pub struct ParseContextTimings {
     pub collect_local_routine_spacing_context: Duration,
     pub collect_control_statement_body_wrapping_context: Duration,
     pub collect_inline_local_var_definition_context: Duration,
+    pub collect_keyword_context: Duration,
+    pub collect_block_indentation_context: Duration,
+    pub collect_routine_end_context: Duration,
+    pub collect_redundant_semicolon_context: Duration,
+    pub collect_comment_context: Duration,
 }
 
 pub type ParseWithContextsResult = (
@@ -91,17 +116,28 @@ pub type ParseWithContextsResult = (
     LocalRoutineSpacingContext,
     ControlStatementBodyWrappingContext,
     InlineLocalVarDefinitionContext,
+    KeywordContext,
+    BlockIndentationContext,
+    RoutineEndContext,
+    RedundantSemicolonContext,
 );
 
-pub type ParseWithContextsAndTimingsResult = (
-    ParseResult,
-    SpacingContext,
-    InheritedExpansionContext,
-    LocalRoutineSpacingContext,
-    ControlStatementBodyWrappingContext,
-    InlineLocalVarDefinitionContext,
-    ParseContextTimings,
-);
+/// Every parser context with the time each took to collect, from `parse_with_contexts_and_timings`
+#[derive(Debug, Default)]
+pub struct ParseWithContextsAndTimingsResult {
+    pub parse_result: ParseResult,
+    pub spacing_context: SpacingContext,
+    pub inherited_expansion_context: InheritedExpansionContext,
+    pub local_routine_spacing_context: LocalRoutineSpacingContext,
+    pub control_statement_body_wrapping_context: ControlStatementBodyWrappingContext,
+    pub inline_local_var_definition_context: InlineLocalVarDefinitionContext,
+    pub keyword_context: KeywordContext,
+    pub block_indentation_context: BlockIndentationContext,
+    pub routine_end_context: RoutineEndContext,
+    pub redundant_semicolon_context: RedundantSemicolonContext,
+    pub comment_context: CommentContext,
+    pub timings: ParseContextTimings,
+}
 
 /// Collected spacing context derived from the AST for operator-aware formatting.
 #[derive(Debug, Clone, Default)]
@@ -114,6 +150,8 @@ pub struct SpacingContext {
     pub generic_angle_positions: HashSet<usize>,
     pub expr_binary_lt_positions: HashSet<usize>,
     pub expr_binary_gt_positions: HashSet<usize>,
+    pub label_colon_positions: HashSet<usize>,
+    pub case_label_colon_positions: HashSet<usize>,
     pub error_ranges: Vec<(usize, usize)>,
 }
 
@@ -229,6 +267,53 @@ pub struct InlineLocalVarDefinitionContext {
     pub routines: Vec<InlineLocalVarDefinitionRoutine>,
 }
 
+/// Byte ranges of reserved-word tokens recognized by the grammar.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeywordContext {
+    pub reserved_word_ranges: Vec<(usize, usize)>,
+}
+
+/// Byte ranges of comment tokens, which never include `{$...}` directives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommentContext {
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// A `begin`/`end` block with the byte spans of the statements and comments directly inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndentationBlock {
+    pub begin_start_byte: usize,
+    pub end_start_byte: usize,
+    pub statements: Vec<(usize, usize)>,
+}
+
+/// Error-free `begin`/`end` blocks in source order, so enclosing blocks precede nested ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockIndentationContext {
+    pub blocks: Vec<IndentationBlock>,
+}
+
+/// A top-level method body and the end of the `;` that closes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutineEnd {
+    pub qualified_name: String,
+    pub semicolon_end_byte: usize,
+}
+
+/// Error-free top-level method implementations in source order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoutineEndContext {
+    pub routines: Vec<RoutineEnd>,
+}
+
+/// Empty statements in `begin`/`end` blocks that directly follow another `;`. Each range runs
+/// from the end of the preceding `;` to the end of the redundant one, so removing it collapses
+/// `x;; y` to `x; y`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedundantSemicolonContext {
+    pub ranges: Vec<(usize, usize)>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct LocalVarDeclarationSpec {
     name: String,
@@ -253,16 +338,81 @@ struct LocalVarUsageSummary {
     top_level_assignments: Vec<TopLevelSimpleAssignmentCandidate>,
 }
 
-fn parse_to_tree(source: &str) -> Result<Tree, DFixxerError> {
+thread_local! {
+    /// Parser shared by every parse on this thread, so the language is set once per thread
+    /// rather than once per file
+    static THREAD_PARSER: RefCell<Option<Parser>> = const { RefCell::new(None) };
+}
+
+/// Create a parser for the Pascal grammar, to be reused across files with `parse_with`
+pub fn new_parser() -> Result<Parser, DFixxerError> {
     let mut parser = Parser::new();
     parser
         .set_language(&LANGUAGE.into())
         .map_err(|_| DFixxerError::ParseError("Failed to set language".to_string()))?;
+    Ok(parser)
+}
+
+fn parse_tree_with(parser: &mut Parser, source: &str) -> Result<Tree, DFixxerError> {
+    // Drop any state left by a previous parse that did not finish
+    parser.reset();
     parser
         .parse(source, None)
         .ok_or_else(|| DFixxerError::ParseError("Failed to parse source".to_string()))
 }
 
+fn parse_to_tree(source: &str) -> Result<Tree, DFixxerError> {
+    THREAD_PARSER.with(|cell| {
+        let mut parser = cell.borrow_mut();
+        if parser.is_none() {
+            *parser = Some(new_parser()?);
+        }
+        parse_tree_with(parser.as_mut().expect("thread parser is set"), source)
+    })
+}
+
+/// Find the first error or missing node in document order.
+pub fn find_first_error_node(node: Node) -> Option<Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+
+    for i in 0..node.child_count() {
+        if let Some(child) = node.child(i)
+            && let Some(error_node) = find_first_error_node(child)
+        {
+            return Some(error_node);
+        }
+    }
+    None
+}
+
+/// Build a positioned syntax error for an error or missing node.
+pub fn syntax_error_for_node(node: Node, source: &str) -> DFixxerError {
+    let near = if node.is_missing() {
+        format!("missing {}", node.kind())
+    } else {
+        source[node.start_byte()..node.end_byte()]
+            .lines()
+            .next()
+            .unwrap_or("")
+            .trim()
+            .chars()
+            .take(40)
+            .collect()
+    };
+    DFixxerError::SyntaxError {
+        filename: None,
+        byte_offset: node.start_byte(),
+        line: node.start_position().row + 1,
+        column: node.start_position().column + 1,
+        near,
+    }
+}
+
 /// Convert a tree-sitter Node to a ParsedNode
 fn node_to_parsed_node(node: Node, kind: Kind) -> ParsedNode {
     ParsedNode {
@@ -286,6 +436,17 @@ fn traverse_and_parse<'a>(node: Node<'a>, code_sections: &mut Vec<CodeSection>)
             }
             // Continue parsing after this uses section (no need to traverse children)
         }
+        "kRequires" | "kContains" => {
+            // Package clauses list modules just like uses sections
+            let kind = if node.kind() == "kRequires" {
+                Kind::Requires
+            } else {
+                Kind::Contains
+            };
+            if let Some(code_section) = transform_keyword_to_code_section(node, kind) {
+                code_sections.push(code_section);
+            }
+        }
         "kProgram" => {
             // When we find a program node, try to transform it into a CodeSection
             if let Some(code_section) = transform_keyword_to_code_section(node, Kind::Program) {
@@ -332,6 +493,17 @@ fn traverse_and_parse<'a>(node: Node<'a>, code_sections: &mut Vec<CodeSection>)
                 code_sections.push(code_section);
             }
         }
+        "kVar" | "kConst" | "kType" => {
+            // Declaration section keywords; routine arguments and inline vars are not sections
+            let kind = match node.kind() {
+                "kVar" => Kind::Var,
+                "kConst" => Kind::Const,
+                _ => Kind::Type,
+            };
+            if let Some(code_section) = transform_declaration_section_to_code_section(node, kind) {
+                code_sections.push(code_section);
+            }
+        }
         "declProc" => {
             // Check if this is a procedure or function declaration without parentheses
             if let Some(code_section) = transform_procedure_declaration_to_code_section(node) {
@@ -407,6 +579,12 @@ fn collect_spacing_context(node: Node, source: &str, context: &mut SpacingContex
                 }
             }
         }
+        "label" => {
+            collect_colon_positions(node, &mut context.label_colon_positions);
+        }
+        "caseLabel" => {
+            collect_colon_positions(node, &mut context.case_label_colon_positions);
+        }
         "literalNumber" => {
             let start = node.start_byte();
             let end = node.end_byte();
@@ -438,6 +616,16 @@ fn collect_spacing_context(node: Node, source: &str, context: &mut SpacingContex
     }
 }
 
+fn collect_colon_positions(node: Node, positions: &mut HashSet<usize>) {
+    for i in 0..node.child_count() {
+        if let Some(child) = node.child(i)
+            && child.kind() == ":"
+        {
+            positions.insert(child.start_byte());
+        }
+    }
+}
+
 fn is_error_guard_node(node: Node) -> bool {
     node.kind() == "exprTpl" && node.has_error()
 }
@@ -1427,6 +1615,204 @@ fn collect_inline_local_var_definition_context(
     }
 }
 
+/// Delphi reserved words; directives such as `read` or `override` are not included.
+const RESERVED_WORDS: &[&str] = &[
+    "and",
+    "array",
+    "as",
+    "asm",
+    "begin",
+    "case",
+    "class",
+    "const",
+    "constructor",
+    "destructor",
+    "dispinterface",
+    "div",
+    "do",
+    "downto",
+    "else",
+    "end",
+    "except",
+    "exports",
+    "file",
+    "finalization",
+    "finally",
+    "for",
+    "function",
+    "goto",
+    "if",
+    "implementation",
+    "in",
+    "inherited",
+    "initialization",
+    "inline",
+    "interface",
+    "is",
+    "label",
+    "library",
+    "mod",
+    "nil",
+    "not",
+    "object",
+    "of",
+    "or",
+    "packed",
+    "procedure",
+    "program",
+    "property",
+    "raise",
+    "record",
+    "repeat",
+    "resourcestring",
+    "set",
+    "shl",
+    "shr",
+    "string",
+    "then",
+    "threadvar",
+    "to",
+    "try",
+    "type",
+    "unit",
+    "until",
+    "uses",
+    "var",
+    "while",
+    "with",
+    "xor",
+];
+
+fn is_reserved_word_node(node: Node, source: &str) -> bool {
+    if !node.kind().starts_with('k') || node.child_count() > 0 || node.is_missing() {
+        return false;
+    }
+    let text = &source[node.start_byte()..node.end_byte()];
+    RESERVED_WORDS
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(text))
+}
+
+fn collect_keyword_context(node: Node, source: &str, context: &mut KeywordContext) {
+    if is_reserved_word_node(node, source) {
+        context
+            .reserved_word_ranges
+            .push((node.start_byte(), node.end_byte()));
+        return;
+    }
+
+    for i in 0..node.child_count() {
+        if let Some(child) = node.child(i) {
+            collect_keyword_context(child, source, context);
+        }
+    }
+}
+
+fn collect_comment_context(node: Node, context: &mut CommentContext) {
+    if node.kind() == "comment" {
+        context.ranges.push((node.start_byte(), node.end_byte()));
+        return;
+    }
+
+    for i in 0..node.child_count() {
+        if let Some(child) = node.child(i) {
+            collect_comment_context(child, context);
+        }
+    }
+}
+
+fn collect_block_indentation_context(node: Node, context: &mut BlockIndentationContext) {
+    if node.kind() == "block" && !node.has_error() {
+        let children = direct_children(node);
+        if let (Some(begin), Some(end)) = (children.first(), children.last())
+            && begin.kind() == "kBegin"
+            && end.kind() == "kEnd"
+        {
+            let statements = children[1..children.len() - 1]
+                .iter()
+                .filter(|child| child.kind() != ";")
+                .map(|child| (child.start_byte(), child.end_byte()))
+                .collect();
+            context.blocks.push(IndentationBlock {
+                begin_start_byte: begin.start_byte(),
+                end_start_byte: end.start_byte(),
+                statements,
+            });
+        }
+    }
+
+    for child in direct_children(node) {
+        collect_block_indentation_context(child, context);
+    }
+}
+
+fn collect_routine_end_context(node: Node, source: &str, context: &mut RoutineEndContext) {
+    if node.kind() != "defProc" {
+        for child in direct_children(node) {
+            collect_routine_end_context(child, source, context);
+        }
+        return;
+    }
+
+    // Nested routines are not visited: only the outermost implementation is annotated.
+    if node.has_error() {
+        return;
+    }
+    let children = direct_children(node);
+    let (Some(declproc), Some(semicolon)) = (children.first(), children.last()) else {
+        return;
+    };
+    if declproc.kind() != "declProc"
+        || semicolon.kind() != ";"
+        || !children.iter().any(|child| child.kind() == "block")
+    {
+        return;
+    }
+    let Some(name_node) = direct_children(*declproc)
+        .into_iter()
+        .find(|child| child.kind() == "genericDot")
+    else {
+        return;
+    };
+    let qualified_name: String = source[name_node.start_byte()..name_node.end_byte()]
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect();
+    context.routines.push(RoutineEnd {
+        qualified_name,
+        semicolon_end_byte: semicolon.end_byte(),
+    });
+}
+
+fn collect_redundant_semicolon_context(
+    node: Node,
+    source: &str,
+    context: &mut RedundantSemicolonContext,
+) {
+    if node.kind() == "block" && !node.has_error() {
+        let children = direct_children(node);
+        for pair in children.windows(2) {
+            let (previous, semicolon) = (pair[0], pair[1]);
+            // Comments and directives between the two semicolons keep both in place
+            if semicolon.kind() == ";"
+                && !matches!(previous.kind(), "comment" | "pp" | "kBegin")
+                && source[..previous.end_byte()].ends_with(';')
+                && source[previous.end_byte()..semicolon.start_byte()]
+                    .trim()
+                    .is_empty()
+            {
+                context
+                    .ranges
+                    .push((previous.end_byte(), semicolon.end_byte()));
+            }
+        }
+    }
+
+    for child in direct_children(node) {
+        collect_redundant_semicolon_context(child, source, context);
+    }
+}
+
 fn loop_control_statement_kind(node: Node) -> Option<ControlStatementKind> {
     match node.kind() {
         "for" => Some(ControlStatementKind::For),
@@ -1911,7 +2297,14 @@ fn collect_control_statement_body_wrapping_context(
     }
 }
 
-/// Generic transform function for kUses, kProgram, and kUnit nodes into a CodeSection
+/// True for sections that hold a comma-separated module list: `uses`, and the `requires` and
+/// `contains` clauses of a package
+pub fn is_module_list_kind(kind: &Kind) -> bool {
+    matches!(kind, Kind::Uses | Kind::Requires | Kind::Contains)
+}
+
+/// Generic transform function for kUses, kRequires, kContains, kProgram, and kUnit nodes into a
+/// CodeSection
 fn transform_keyword_to_code_section(
     keyword_node: Node,
     keyword_kind: Kind,
@@ -1925,7 +2318,7 @@ fn transform_keyword_to_code_section(
     let parent = keyword_node.parent()?;
 
     // Check parent for errors, but skip for unit and program as they may cover the whole file
-    if parent.has_error() && keyword_kind == Kind::Uses {
+    if parent.has_error() && is_module_list_kind(&keyword_kind) {
         return None;
     }
 
@@ -1975,7 +2368,8 @@ fn transform_keyword_to_code_section(
                         // For uses statements, default to module
                         match keyword_kind {
                             Kind::Program | Kind::Unit => continue, // Skip other nodes for program and unit statements
-                            Kind::Uses => Kind::Module, // Default to module for uses statements
+                            // Default to module for uses, requires, and contains clauses
+                            Kind::Uses | Kind::Requires | Kind::Contains => Kind::Module,
                             _ => continue,
                         }
                     }
@@ -2008,6 +2402,43 @@ fn transform_single_keyword_to_code_section(
     })
 }
 
+/// Transform function for `var`, `const`, and `type` section keywords
+/// The siblings are the individual declarations plus any comments or preprocessor nodes between them
+fn transform_declaration_section_to_code_section(
+    keyword_node: Node,
+    keyword_kind: Kind,
+) -> Option<CodeSection> {
+    let parent = keyword_node.parent()?;
+    let declaration_kind = match parent.kind() {
+        "declVars" => "declVar",
+        "declConsts" => "declConst",
+        "declTypes" => "declType",
+        _ => return None,
+    };
+    if parent.has_error() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    for child in direct_children(parent) {
+        if child == keyword_node {
+            continue;
+        }
+        let kind = match child.kind() {
+            "comment" => Kind::Comment,
+            "pp" => Kind::Preprocessor,
+            kind if kind == declaration_kind => Kind::Declaration,
+            _ => continue,
+        };
+        siblings.push(node_to_parsed_node(child, kind));
+    }
+
+    Some(CodeSection {
+        keyword: node_to_parsed_node(keyword_node, keyword_kind),
+        siblings,
+    })
+}
+
 /// Transform function for procedure/function declarations without parentheses
 /// These are `declProc` nodes that contain kProcedure/kFunction -> identifier -> ; (no declArgs)
 fn transform_procedure_declaration_to_code_section(declproc_node: Node) -> Option<CodeSection> {
@@ -2076,28 +2507,48 @@ fn transform_procedure_declaration_to_code_section(declproc_node: Node) -> Optio
     None
 }
 
-/// Parse source code string and return ParseResult
+/// Parse source code string and return ParseResult. Parts of the file the parser could not
+/// make sense of are left out; only a file tree-sitter cannot build a tree for is an error.
 pub fn parse(source: &str) -> Result<ParseResult, DFixxerError> {
+    Ok(parse_result_from_tree(parse_to_tree(source)?))
+}
+
+/// Same as `parse`, but with a caller-owned parser from `new_parser`, e.g. one per worker
+pub fn parse_with(parser: &mut Parser, source: &str) -> Result<ParseResult, DFixxerError> {
+    Ok(parse_result_from_tree(parse_tree_with(parser, source)?))
+}
+
+/// The positioned error for the first syntax error in `source`, or `None` when it parses
+/// cleanly. Failing to build a tree at all is returned as the error itself.
+pub fn first_syntax_error(source: &str) -> Result<Option<DFixxerError>, DFixxerError> {
     let tree = parse_to_tree(source)?;
+    Ok(find_first_error_node(tree.root_node()).map(|node| syntax_error_for_node(node, source)))
+}
+
+fn parse_result_from_tree(tree: Tree) -> ParseResult {
     let mut code_sections = Vec::new();
 
     // Traverse the AST and collect all code sections
     traverse_and_parse(tree.root_node(), &mut code_sections);
 
-    Ok(ParseResult { code_sections })
+    ParseResult { code_sections }
 }
 
 /// Parse source code and collect parser contexts needed by transformations.
 pub fn parse_with_contexts(source: &str) -> Result<ParseWithContextsResult, DFixxerError> {
-    let (
+    let ParseWithContextsAndTimingsResult {
         parse_result,
         spacing_context,
         inherited_expansion_context,
         local_routine_spacing_context,
         control_statement_body_wrapping_context,
         inline_local_var_definition_context,
-        _timings,
-    ) = parse_with_contexts_and_timings(source)?;
+        keyword_context,
+        block_indentation_context,
+        routine_end_context,
+        redundant_semicolon_context,
+        ..
+    } = parse_with_contexts_and_timings(source)?;
 
     Ok((
         parse_result,
@@ -2106,6 +2557,10 @@ pub fn parse_with_contexts(source: &str) -> Result<ParseWithContextsResult, DFix
         local_routine_spacing_context,
         control_statement_body_wrapping_context,
         inline_local_var_definition_context,
+        keyword_context,
+        block_indentation_context,
+        routine_end_context,
+        redundant_semicolon_context,
     ))
 }
 
@@ -2169,6 +2624,31 @@ pub fn parse_with_contexts_and_timings(
     let collect_inline_local_var_definition_context =
         collect_inline_local_var_definition_context_start.elapsed();
 
+    let mut keyword_context = KeywordContext::default();
+    let collect_keyword_context_start = Instant::now();
+    collect_keyword_context(tree.root_node(), source, &mut keyword_context);
+    let collect_keyword_context = collect_keyword_context_start.elapsed();
+
+    let mut block_indentation_context = BlockIndentationContext::default();
+    let collect_block_indentation_context_start = Instant::now();
+    collect_block_indentation_context(tree.root_node(), &mut block_indentation_context);
+    let collect_block_indentation_context = collect_block_indentation_context_start.elapsed();
+
+    let mut routine_end_context = RoutineEndContext::default();
+    let collect_routine_end_context_start = Instant::now();
+    collect_routine_end_context(tree.root_node(), source, &mut routine_end_context);
+    let collect_routine_end_context = collect_routine_end_context_start.elapsed();
+
+    let mut redundant_semicolon_context = RedundantSemicolonContext::default();
+    let collect_redundant_semicolon_context_start = Instant::now();
+    collect_redundant_semicolon_context(tree.root_node(), source, &mut redundant_semicolon_context);
+    let collect_redundant_semicolon_context = collect_redundant_semicolon_context_start.elapsed();
+
+    let mut comment_context = CommentContext::default();
+    let collect_comment_context_start = Instant::now();
+    collect_comment_context(tree.root_node(), &mut comment_context);
+    let collect_comment_context = collect_comment_context_start.elapsed();
+
     let timings = ParseContextTimings {
         build_tree,
         collect_code_sections,
@@ -2177,17 +2657,27 @@ pub fn parse_with_contexts_and_timings(
         collect_local_routine_spacing_context,
         collect_control_statement_body_wrapping_context,
         collect_inline_local_var_definition_context,
+        collect_keyword_context,
+        collect_block_indentation_context,
+        collect_routine_end_context,
+        collect_redundant_semicolon_context,
+        collect_comment_context,
     };
 
-    Ok((
-        ParseResult { code_sections },
+    Ok(ParseWithContextsAndTimingsResult {
+        parse_result: ParseResult { code_sections },
         spacing_context,
         inherited_expansion_context,
         local_routine_spacing_context,
         control_statement_body_wrapping_context,
         inline_local_var_definition_context,
+        keyword_context,
+        block_indentation_context,
+        routine_end_context,
+        redundant_semicolon_context,
+        comment_context,
         timings,
-    ))
+    })
 }
 
 /// Parse source code and also collect spacing context for AST-aware text transformations.
@@ -2195,42 +2685,218 @@ pub fn parse_with_contexts_and_timings(
 pub fn parse_with_spacing_context(
     source: &str,
 ) -> Result<(ParseResult, SpacingContext), DFixxerError> {
-    let (parse_result, spacing_context, _, _, _, _) = parse_with_contexts(source)?;
+    let (parse_result, spacing_context, _, _, _, _, _, _, _, _) = parse_with_contexts(source)?;
     Ok((parse_result, spacing_context))
 }
 
-/// Parse the source, create the tree-sitter tree, and print each node's kind and text
-pub fn parse_raw(source: &str) -> Result<(), DFixxerError> {
+/// Parse the source, create the tree-sitter tree, and write each node's kind and text to `out`
+/// Each node's line shows its 1-based `line:col-line:col` span, followed by its byte range when
+/// `show_bytes` is set.
+pub fn parse_raw(
+    source: &str,
+    show_bytes: bool,
+    out: &mut impl std::io::Write,
+) -> Result<(), DFixxerError> {
     let tree = parse_to_tree(source)?;
     let root = tree.root_node();
-    fn print_node(node: tree_sitter::Node, depth: usize, source: &str) {
+    fn print_node(
+        node: tree_sitter::Node,
+        depth: usize,
+        source: &str,
+        show_bytes: bool,
+        out: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
         let indent = "  ".repeat(depth);
         let kind = node.kind();
         let text = node.utf8_text(source.as_bytes()).unwrap_or("");
         let error_info = if node.has_error() { " | ERROR" } else { "" };
-        println!(
-            "{}Node kind: {} | Text: {}{}",
-            indent, kind, text, error_info
-        );
+        let start = node.start_position();
+        let end = node.end_position();
+        let bytes = if show_bytes {
+            format!(" (bytes {}-{})", node.start_byte(), node.end_byte())
+        } else {
+            String::new()
+        };
+        writeln!(
+            out,
+            "{}Node kind: {} | Position: {}:{}-{}:{}{} | Text: {}{}",
+            indent,
+            kind,
+            start.row + 1,
+            start.column + 1,
+            end.row + 1,
+            end.column + 1,
+            bytes,
+            text,
+            error_info
+        )?;
         for i in 0..node.child_count() {
             if let Some(child) = node.child(i) {
-                print_node(child, depth + 1, source);
+                print_node(child, depth + 1, source, show_bytes, out)?;
             }
         }
+        Ok(())
     }
     // Skip printing the root node, print only its children
     for i in 0..root.child_count() {
         if let Some(child) = root.child(i) {
-            print_node(child, 0, source);
+            print_node(child, 0, source, show_bytes, out)?;
         }
     }
     Ok(())
 }
 
+/// Print each code section's keyword and siblings with 1-based line:col spans instead of the
+/// byte offsets shown by the `Debug` output.
+pub fn print_line_column_positions(
+    parse_result: &ParseResult,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    for section in &parse_result.code_sections {
+        writeln!(
+            out,
+            "{:?} {}",
+            section.keyword.kind,
+            section.keyword.line_column_span()
+        )?;
+        for sibling in &section.siblings {
+            writeln!(out, "  {:?} {}", sibling.kind, sibling.line_column_span())?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep only the code sections overlapping the 1-based, inclusive line range.
+pub fn filter_code_sections_by_lines(
+    parse_result: ParseResult,
+    first_line: usize,
+    last_line: usize,
+) -> ParseResult {
+    let code_sections = parse_result
+        .code_sections
+        .into_iter()
+        .filter(|section| {
+            let start_row = section.keyword.start_row;
+            let end_row = section
+                .siblings
+                .iter()
+                .map(|sibling| sibling.end_row)
+                .fold(section.keyword.end_row, usize::max);
+            start_row < last_line && end_row + 1 >= first_line
+        })
+        .collect();
+    ParseResult { code_sections }
+}
+
+/// Byte ranges of `source_len` bytes not covered by any code section, in order.
+pub fn unparsed_regions(parse_result: &ParseResult, source_len: usize) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = parse_result
+        .code_sections
+        .iter()
+        .map(|section| {
+            let end = section
+                .siblings
+                .iter()
+                .map(|sibling| sibling.end_byte)
+                .fold(section.keyword.end_byte, usize::max);
+            (section.keyword.start_byte, end)
+        })
+        .collect();
+    spans.sort_unstable();
+
+    let mut regions = Vec::new();
+    let mut last_end = 0usize;
+    for (start, end) in spans {
+        if last_end < start {
+            regions.push((last_end, start));
+        }
+        last_end = last_end.max(end);
+    }
+    if last_end < source_len {
+        regions.push((last_end, source_len));
+    }
+    regions
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn node_at(kind: Kind, start_byte: usize, end_byte: usize) -> ParsedNode {
+        ParsedNode {
+            kind,
+            start_byte,
+            end_byte,
+            start_row: 0,
+            start_column: start_byte,
+            end_row: 0,
+            end_column: end_byte,
+        }
+    }
+
+    #[test]
+    fn test_unparsed_regions_are_gaps_between_sections() {
+        let parse_result = ParseResult {
+            code_sections: vec![
+                CodeSection {
+                    keyword: node_at(Kind::Interface, 20, 29),
+                    siblings: vec![],
+                },
+                CodeSection {
+                    keyword: node_at(Kind::Unit, 0, 4),
+                    siblings: vec![node_at(Kind::Module, 5, 6), node_at(Kind::Semicolon, 6, 7)],
+                },
+            ],
+        };
+        assert_eq!(unparsed_regions(&parse_result, 40), vec![(7, 20), (29, 40)]);
+        assert_eq!(unparsed_regions(&parse_result, 29), vec![(7, 20)]);
+        assert_eq!(
+            unparsed_regions(
+                &ParseResult {
+                    code_sections: vec![]
+                },
+                5
+            ),
+            vec![(0, 5)]
+        );
+    }
+
+    #[test]
+    fn test_print_line_column_positions_uses_one_based_spans() {
+        let parse_result = ParseResult {
+            code_sections: vec![CodeSection {
+                keyword: node_at(Kind::Unit, 0, 4),
+                siblings: vec![node_at(Kind::Module, 5, 6), node_at(Kind::Semicolon, 6, 7)],
+            }],
+        };
+        let mut out = Vec::new();
+        print_line_column_positions(&parse_result, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Unit 1:1-1:5\n  Module 1:6-1:7\n  Semicolon 1:7-1:8\n"
+        );
+    }
+
+    #[test]
+    fn test_filter_code_sections_by_lines() {
+        let source =
+            "unit A;\n\ninterface\n\nuses\n  B;\n\nimplementation\n\nuses\n  C,\n  D;\n\nend.";
+        let result = parse(source).expect("Failed to parse");
+        let section_count = result.code_sections.len();
+
+        let filtered = filter_code_sections_by_lines(result.clone(), 11, 11);
+        assert_eq!(filtered.code_sections.len(), 1);
+        assert_eq!(filtered.code_sections[0].keyword.kind, Kind::Uses);
+        assert_eq!(filtered.code_sections[0].keyword.start_row, 9);
+
+        let filtered = filter_code_sections_by_lines(result.clone(), 1, 1);
+        assert_eq!(filtered.code_sections.len(), 1);
+        assert_eq!(filtered.code_sections[0].keyword.kind, Kind::Unit);
+
+        let filtered = filter_code_sections_by_lines(result, 1, usize::MAX);
+        assert_eq!(filtered.code_sections.len(), section_count);
+    }
+
     #[test]
     fn test_parse_program_statement_legacy() {
         let source = r#"program myProgram;
@@ -2261,6 +2927,51 @@ end."#;
         assert_eq!(code_section.keyword.start_byte, 0);
     }
 
+    #[test]
+    fn test_parse_reports_position_of_first_syntax_error() {
+        let source = r#"unit Broken;
+interface
+implementation
+procedure Foo;
+begin
+  X := ;
+end;
+end."#;
+
+        // parse itself stays lenient; the error is reported on request
+        assert!(parse(source).is_ok());
+        let error = first_syntax_error(source)
+            .expect("Failed to build a tree")
+            .expect("Broken source should report a syntax error");
+        match &error {
+            DFixxerError::SyntaxError {
+                filename,
+                byte_offset,
+                line,
+                column,
+                ..
+            } => {
+                assert!(filename.is_none());
+                assert!(
+                    *line >= 5,
+                    "error reported before the routine body: {}",
+                    line
+                );
+                assert!(*column >= 1);
+                assert!(*byte_offset >= source.find("begin").unwrap());
+            }
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+
+        let message = error.with_filename("broken.pas").to_string();
+        assert!(
+            message.starts_with("broken.pas:"),
+            "Unexpected message: {}",
+            message
+        );
+        assert!(message.contains(": parse error near "));
+    }
+
     #[test]
     fn test_parse_code_section_program() {
         let source = r#"program myProgram;
@@ -2327,6 +3038,102 @@ end."#;
         assert!(has_semicolon, "Should have semicolon in siblings");
     }
 
+    #[test]
+    fn test_parse_uses_in_interface_and_implementation() {
+        let source = r#"unit MyUnit;
+interface
+uses
+  B, { keep } A;
+implementation
+uses
+  D, C;
+end."#;
+
+        let result = parse(source).expect("Failed to parse");
+
+        // The section keyword handlers must not stop the search for nested uses clauses
+        let uses_sections: Vec<_> = result
+            .code_sections
+            .iter()
+            .filter(|cs| cs.keyword.kind == Kind::Uses)
+            .collect();
+        assert_eq!(uses_sections.len(), 2);
+        assert_eq!(uses_sections[0].keyword.start_row, 2);
+        assert_eq!(uses_sections[1].keyword.start_row, 5);
+
+        // Only the interface clause carries the comment that makes the transformer skip it
+        assert!(
+            uses_sections[0]
+                .siblings
+                .iter()
+                .any(|s| s.kind == Kind::Comment)
+        );
+        assert!(
+            !uses_sections[1]
+                .siblings
+                .iter()
+                .any(|s| s.kind == Kind::Comment)
+        );
+    }
+
+    #[test]
+    fn test_parse_package_requires_and_contains() {
+        let source = "package MyPackage;\n\nrequires\n  vcl,\n  rtl;\n\ncontains\n  UnitB in 'UnitB.pas',\n  UnitA in 'UnitA.pas';\n\nend.\n";
+        let result = parse(source).expect("Failed to parse");
+
+        let requires = result
+            .code_sections
+            .iter()
+            .find(|cs| cs.keyword.kind == Kind::Requires)
+            .expect("requires clause");
+        assert_eq!(requires.keyword.start_row, 2);
+        let modules: Vec<_> = requires
+            .siblings
+            .iter()
+            .filter(|s| s.kind == Kind::Module)
+            .map(|s| &source[s.start_byte..s.end_byte])
+            .collect();
+        assert_eq!(modules, vec!["vcl", "rtl"]);
+
+        let contains = result
+            .code_sections
+            .iter()
+            .find(|cs| cs.keyword.kind == Kind::Contains)
+            .expect("contains clause");
+        assert_eq!(contains.keyword.start_row, 6);
+        assert_eq!(
+            contains
+                .siblings
+                .iter()
+                .filter(|s| s.kind == Kind::Module)
+                .count(),
+            2
+        );
+        assert!(contains.siblings.iter().any(|s| s.kind == Kind::Semicolon));
+    }
+
+    #[test]
+    fn test_parse_keeps_interface_section_and_its_nested_uses() {
+        let source = "unit MyUnit;\ninterface\nuses A;\nimplementation\nend.";
+
+        let result = parse(source).expect("Failed to parse");
+
+        let kinds: Vec<_> = result
+            .code_sections
+            .iter()
+            .map(|cs| cs.keyword.kind.clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Kind::Unit,
+                Kind::Interface,
+                Kind::Uses,
+                Kind::Implementation
+            ]
+        );
+    }
+
     #[test]
     fn test_parse_code_section_unit() {
         let source = r#"UnIT   ex2 ;
@@ -2438,6 +3245,60 @@ end."#;
         }
     }
 
+    #[test]
+    fn test_parse_declaration_sections() {
+        let source = r#"unit MyUnit;
+interface
+const
+  A = 1;
+  B = 2;
+type
+  TFoo = Integer;
+var
+  X: Integer;
+implementation
+procedure Foo(var Y: Integer);
+begin
+end;
+end."#;
+
+        let result = parse(source).expect("Failed to parse");
+
+        let const_section = result
+            .code_sections
+            .iter()
+            .find(|cs| cs.keyword.kind == Kind::Const)
+            .expect("Should have const section");
+        assert_eq!(const_section.siblings.len(), 2);
+        assert!(
+            const_section
+                .siblings
+                .iter()
+                .all(|s| s.kind == Kind::Declaration)
+        );
+        assert_eq!(
+            &source[const_section.siblings[1].start_byte..const_section.siblings[1].end_byte],
+            "B = 2;"
+        );
+
+        let kinds: Vec<_> = result
+            .code_sections
+            .iter()
+            .map(|cs| cs.keyword.kind.clone())
+            .filter(|kind| matches!(kind, Kind::Var | Kind::Const | Kind::Type))
+            .collect();
+        // The `var` routine argument is not a declaration section
+        assert_eq!(kinds, vec![Kind::Const, Kind::Type, Kind::Var]);
+
+        let regions = unparsed_regions(&result, source.len());
+        let const_start = source.find("const").unwrap();
+        assert!(
+            !regions
+                .iter()
+                .any(|&(start, end)| start <= const_start && const_start < end)
+        );
+    }
+
     #[test]
     fn test_parse_procedure_without_parentheses() {
         let source = r#"unit TestProcedures;
@@ -2626,7 +3487,7 @@ end;
 
 end."#;
 
-        let (_, _, inherited_context, _, _, _) =
+        let (_, _, inherited_context, _, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
         assert_eq!(inherited_context.candidates.len(), 1);
 
@@ -2665,7 +3526,7 @@ end;
 
 end."#;
 
-        let (_, _, inherited_context, _, _, _) =
+        let (_, _, inherited_context, _, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
         assert_eq!(inherited_context.candidates.len(), 1);
 
@@ -2707,7 +3568,7 @@ end;
 
 end."#;
 
-        let (_, _, inherited_context, _, _, _) =
+        let (_, _, inherited_context, _, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
         assert_eq!(inherited_context.candidates.len(), 1);
 
@@ -2742,7 +3603,7 @@ end;
 
 end."#;
 
-        let (_, _, inherited_context, _, _, _) =
+        let (_, _, inherited_context, _, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
         assert!(
             inherited_context.candidates.is_empty(),
@@ -2778,7 +3639,7 @@ end;
 
 end."#;
 
-        let (_, _, inherited_context, _, _, _) =
+        let (_, _, inherited_context, _, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
         assert_eq!(
             inherited_context.candidates.len(),
@@ -2803,7 +3664,7 @@ end;
 begin
 end;"#;
 
-        let (_, _, _, local_routine_context, _, _) =
+        let (_, _, _, local_routine_context, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(
@@ -2836,7 +3697,7 @@ end;
 begin
 end;"#;
 
-        let (_, _, _, local_routine_context, _, _) =
+        let (_, _, _, local_routine_context, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(local_routine_context.gaps.len(), 2);
@@ -2863,7 +3724,7 @@ end;
 begin
 end;"#;
 
-        let (_, _, _, local_routine_context, _, _) =
+        let (_, _, _, local_routine_context, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(
@@ -2892,7 +3753,7 @@ end;
 begin
 end;"#;
 
-        let (_, _, _, local_routine_context, _, _) =
+        let (_, _, _, local_routine_context, _, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert!(
@@ -2918,7 +3779,8 @@ begin
   B := B + 1;
 end;"#;
 
-        let (_, _, _, _, _, inline_context) = parse_with_contexts(source).expect("Failed to parse");
+        let (_, _, _, _, _, inline_context, _, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(inline_context.routines.len(), 1);
         let routine = &inline_context.routines[0];
@@ -2950,7 +3812,8 @@ begin
   A := 1;
 end;"#;
 
-        let (_, _, _, _, _, inline_context) = parse_with_contexts(source).expect("Failed to parse");
+        let (_, _, _, _, _, inline_context, _, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
 
         assert!(
             inline_context.routines.is_empty(),
@@ -2969,7 +3832,7 @@ begin
   for Value in Values do Bar(Value);
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 3);
@@ -3005,7 +3868,7 @@ begin
     Foo;
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 1);
@@ -3062,7 +3925,7 @@ begin
     ;
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         let candidate_bodies: Vec<&str> = wrapping_context
@@ -3099,7 +3962,7 @@ begin
 {$ENDIF}
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert!(
@@ -3120,7 +3983,7 @@ begin
     Bar; // keep tail
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 3);
@@ -3160,7 +4023,7 @@ begin
     Bar;
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 3);
@@ -3217,7 +4080,7 @@ begin
     Exit;
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 1);
@@ -3244,7 +4107,7 @@ begin
     Foo()
 end."#;
 
-        let (_, _, _, _, wrapping_context, _) =
+        let (_, _, _, _, wrapping_context, _, _, _, _, _) =
             parse_with_contexts(source).expect("Failed to parse");
 
         assert_eq!(wrapping_context.candidates.len(), 2);
@@ -3273,4 +4136,173 @@ end."#;
             "Foo()"
         );
     }
+
+    #[test]
+    fn test_parse_with_contexts_collects_reserved_word_ranges() {
+        let source = r#"UNIT KeywordCase;
+
+INTERFACE
+
+IMPLEMENTATION
+
+PROCEDURE Run;
+VAR
+  BeginValue: Integer;
+BEGIN
+  BeginValue := 1;
+END;
+
+END."#;
+
+        let (_, _, _, _, _, _, keyword_context, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
+
+        let keywords: Vec<&str> = keyword_context
+            .reserved_word_ranges
+            .iter()
+            .map(|(start, end)| &source[*start..*end])
+            .collect();
+        assert_eq!(
+            keywords,
+            vec![
+                "UNIT",
+                "INTERFACE",
+                "IMPLEMENTATION",
+                "PROCEDURE",
+                "VAR",
+                "BEGIN",
+                "END",
+                "END"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_contexts_and_timings_collects_comments_without_directives() {
+        let source = "unit C;\n\ninterface\n\n{$IFDEF X}\n{ brace }\n(* paren *)\n// line\n{$ENDIF}\n\nimplementation\n\nend.";
+
+        let parsed = parse_with_contexts_and_timings(source).expect("Failed to parse");
+
+        let comments: Vec<&str> = parsed
+            .comment_context
+            .ranges
+            .iter()
+            .map(|(start, end)| &source[*start..*end])
+            .collect();
+        assert_eq!(comments, vec!["{ brace }", "(* paren *)", "// line"]);
+    }
+
+    #[test]
+    fn test_parse_with_contexts_collects_nested_begin_end_blocks() {
+        let source = r#"procedure Run;
+begin
+  Foo;
+  if A then
+  begin
+    Bar;
+  end;
+end;"#;
+
+        let (_, _, _, _, _, _, _, block_context, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
+
+        assert_eq!(block_context.blocks.len(), 2);
+        let outer = &block_context.blocks[0];
+        let statements: Vec<&str> = outer
+            .statements
+            .iter()
+            .map(|(start, end)| &source[*start..*end])
+            .collect();
+        assert_eq!(
+            statements,
+            vec!["Foo", "if A then\n  begin\n    Bar;\n  end"]
+        );
+        assert_eq!(outer.end_start_byte, source.rfind("end").unwrap());
+        let inner = &block_context.blocks[1];
+        assert_eq!(inner.begin_start_byte, source.find("  begin").unwrap() + 2);
+        assert_eq!(inner.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_with_contexts_collects_top_level_method_ends() {
+        let source = r#"unit A;
+
+interface
+
+implementation
+
+procedure TMyClass.DoThing;
+
+  procedure Local;
+  begin
+  end;
+
+begin
+  Local;
+end;
+
+procedure Plain;
+begin
+end;
+
+end."#;
+
+        let (_, _, _, _, _, _, _, _, routine_end_context, _) =
+            parse_with_contexts(source).expect("Failed to parse");
+
+        let method_end = source.find("  Local;\nend;").unwrap() + "  Local;\nend;".len();
+        assert_eq!(
+            routine_end_context.routines,
+            vec![RoutineEnd {
+                qualified_name: "TMyClass.DoThing".to_string(),
+                semicolon_end_byte: method_end,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_with_contexts_collects_doubled_semicolons() {
+        let source = r#"procedure Run;
+begin
+  x := 1;; Foo;
+  Bar; ;
+  Baz; { keep } ;
+end;"#;
+
+        let (_, _, _, _, _, _, _, _, _, semicolon_context) =
+            parse_with_contexts(source).expect("Failed to parse");
+
+        let removed: Vec<&str> = semicolon_context
+            .ranges
+            .iter()
+            .map(|(start, end)| &source[*start..*end])
+            .collect();
+        assert_eq!(removed, vec![";", " ;"]);
+    }
+
+    #[test]
+    fn test_parse_raw_writes_node_report_to_writer() {
+        let source = "program P;\nbegin\nend.";
+        let mut out = Vec::new();
+        parse_raw(source, false, &mut out).expect("Failed to parse");
+
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.starts_with("Node kind: program | Position: 1:1-3:5 | Text: program P;"));
+        assert!(report.contains("Node kind: kProgram | Position: 1:1-1:8 | Text: program\n"));
+        assert!(report.contains("Node kind: kBegin | Position: 2:1-2:6 | Text: begin\n"));
+        assert!(!report.contains("ERROR"));
+    }
+
+    #[test]
+    fn test_parse_raw_show_bytes_adds_byte_ranges() {
+        let source = "program P;\nbegin\nend.";
+        let mut out = Vec::new();
+        parse_raw(source, true, &mut out).expect("Failed to parse");
+
+        let report = String::from_utf8(out).unwrap();
+        assert!(
+            report
+                .contains("Node kind: kProgram | Position: 1:1-1:8 (bytes 0-7) | Text: program\n")
+        );
+    }
 }
@@ -0,0 +1,274 @@
+use crate::parser::ParseContextTimings;
+use crate::transform_text::TextTransformationStats;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Stage name used for text transformations over the gaps between structural rewrites
+pub(crate) const TEXT_TRANSFORMATION_STAGE: &str = "Text transformations";
+
+#[derive(Debug, Clone, Default)]
+struct RulePerformanceSummary {
+    candidates: usize,
+    replacements: usize,
+    duration: Duration,
+}
+
+/// Collects top-level timings plus fine-grained parser, rule, and text-rule metrics.
+pub struct PerformanceCollector {
+    stage_timings: BTreeMap<String, Duration>,
+    parse_timings: BTreeMap<String, Duration>,
+    rule_timings: BTreeMap<String, RulePerformanceSummary>,
+    text_stats: TextTransformationStats,
+}
+
+impl PerformanceCollector {
+    pub fn new() -> Self {
+        Self {
+            stage_timings: BTreeMap::new(),
+            parse_timings: BTreeMap::new(),
+            rule_timings: BTreeMap::new(),
+            text_stats: TextTransformationStats::default(),
+        }
+    }
+
+    pub(crate) fn record_stage_duration(&mut self, operation_name: &str, duration: Duration) {
+        self.stage_timings
+            .entry(operation_name.to_string())
+            .and_modify(|total| *total += duration)
+            .or_insert(duration);
+    }
+
+    pub(crate) fn record_parse_timings(&mut self, parse_timings: &ParseContextTimings) {
+        self.parse_timings
+            .insert("build tree".to_string(), parse_timings.build_tree);
+        self.parse_timings.insert(
+            "collect code sections".to_string(),
+            parse_timings.collect_code_sections,
+        );
+        self.parse_timings.insert(
+            "collect spacing context".to_string(),
+            parse_timings.collect_spacing_context,
+        );
+        self.parse_timings.insert(
+            "collect inherited call expansion context".to_string(),
+            parse_timings.collect_inherited_call_expansion_context,
+        );
+        self.parse_timings.insert(
+            "collect local routine spacing context".to_string(),
+            parse_timings.collect_local_routine_spacing_context,
+        );
+        self.parse_timings.insert(
+            "collect control body wrapping context".to_string(),
+            parse_timings.collect_control_statement_body_wrapping_context,
+        );
+        self.parse_timings.insert(
+            "collect inline local var definition context".to_string(),
+            parse_timings.collect_inline_local_var_definition_context,
+        );
+        self.parse_timings.insert(
+            "collect keyword context".to_string(),
+            parse_timings.collect_keyword_context,
+        );
+        self.parse_timings.insert(
+            "collect block indentation context".to_string(),
+            parse_timings.collect_block_indentation_context,
+        );
+        self.parse_timings.insert(
+            "collect routine end context".to_string(),
+            parse_timings.collect_routine_end_context,
+        );
+        self.parse_timings.insert(
+            "collect redundant semicolon context".to_string(),
+            parse_timings.collect_redundant_semicolon_context,
+        );
+        self.parse_timings.insert(
+            "collect comment context".to_string(),
+            parse_timings.collect_comment_context,
+        );
+    }
+
+    pub(crate) fn record_rule_timing(
+        &mut self,
+        rule_name: &str,
+        candidates: usize,
+        replacements: usize,
+        duration: Duration,
+    ) {
+        if candidates == 0 && replacements == 0 {
+            return;
+        }
+        let stats = self.rule_timings.entry(rule_name.to_string()).or_default();
+        stats.candidates += candidates;
+        stats.replacements += replacements;
+        stats.duration += duration;
+    }
+
+    pub(crate) fn record_text_stats(&mut self, stats: TextTransformationStats) {
+        self.text_stats.merge(stats);
+    }
+
+    pub fn time_operation<T, F>(&mut self, operation_name: &str, operation: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let result = operation();
+        let duration = start.elapsed();
+        log::debug!("{} took: {:?}", operation_name, duration);
+        self.record_stage_duration(operation_name, duration);
+        result
+    }
+
+    pub fn time_operation_result<T, E, F>(
+        &mut self,
+        operation_name: &str,
+        operation: F,
+    ) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let start = Instant::now();
+        let result = operation();
+        let duration = start.elapsed();
+        log::debug!("{} took: {:?}", operation_name, duration);
+        self.record_stage_duration(operation_name, duration);
+        result
+    }
+
+    /// Time spent in each transformer, slowest first. Structural rules are keyed by rule
+    /// name and the gap text pass is reported as `text`.
+    pub fn transformation_breakdown(&self) -> Vec<(String, Duration)> {
+        let mut breakdown: Vec<(String, Duration)> = self
+            .rule_timings
+            .iter()
+            .map(|(rule_name, stats)| (rule_name.clone(), stats.duration))
+            .collect();
+        if let Some(duration) = self.stage_timings.get(TEXT_TRANSFORMATION_STAGE) {
+            breakdown.push(("text".to_string(), *duration));
+        }
+        breakdown.sort_by_key(|(_, duration)| std::cmp::Reverse(*duration));
+        breakdown
+    }
+
+    fn summary_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let total_processing: Duration = self.stage_timings.values().copied().sum();
+
+        lines.push("Performance summary:".to_string());
+        if !self.stage_timings.is_empty() {
+            lines.push("  Stage timings:".to_string());
+            for (operation, duration) in &self.stage_timings {
+                lines.push(format!("    {}: {:?}", operation, duration));
+            }
+        }
+        if !self.parse_timings.is_empty() {
+            lines.push("  Parse substage timings:".to_string());
+            for (operation, duration) in &self.parse_timings {
+                lines.push(format!("    {}: {:?}", operation, duration));
+            }
+        }
+        if !self.rule_timings.is_empty() {
+            lines.push("  Rule timings:".to_string());
+            for (rule_name, stats) in &self.rule_timings {
+                lines.push(format!(
+                    "    {}: candidates={} replacements={} total={:?}",
+                    rule_name, stats.candidates, stats.replacements, stats.duration
+                ));
+            }
+        }
+        if !self.text_stats.is_empty() {
+            lines.push(format!(
+                "  Text transformation counters: sections={} changed_sections={} bytes={} skipped_error_ranges={} file_level_runs={} file_level_changes={}",
+                self.text_stats.sections_processed,
+                self.text_stats.sections_changed,
+                self.text_stats.bytes_processed,
+                self.text_stats.skipped_error_ranges,
+                self.text_stats.file_level_runs,
+                self.text_stats.file_level_changes
+            ));
+            for (rule_name, stats) in self.text_stats.rule_stats() {
+                lines.push(format!(
+                    "    {}: hits={} changes={} skips={}",
+                    rule_name, stats.hits, stats.changes, stats.skips
+                ));
+            }
+        }
+        let breakdown = self.transformation_breakdown();
+        let breakdown_total: Duration = breakdown.iter().map(|(_, duration)| *duration).sum();
+        if !breakdown.is_empty() {
+            lines.push("  Transformation breakdown (slowest first):".to_string());
+            for (name, duration) in &breakdown {
+                let share = if breakdown_total.is_zero() {
+                    0.0
+                } else {
+                    duration.as_secs_f64() * 100.0 / breakdown_total.as_secs_f64()
+                };
+                lines.push(format!("    {}: {:?} ({:.1}%)", name, duration, share));
+            }
+        }
+        lines.push(format!("  Total processing: {:?}", total_processing));
+        lines
+    }
+
+    pub fn log_summary(&self) {
+        for line in self.summary_lines() {
+            log::info!("{}", line);
+        }
+    }
+
+    /// Print the summary to stderr regardless of the configured log level
+    pub fn print_summary(&self) {
+        for line in self.summary_lines() {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+impl Default for PerformanceCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transformation_breakdown_has_per_kind_buckets() {
+        let mut timing = PerformanceCollector::new();
+        for (rule_name, millis) in [
+            ("uses_section", 4),
+            ("unit_program_section", 1),
+            ("single_keyword_sections", 2),
+            ("procedure_section", 3),
+        ] {
+            timing.record_rule_timing(rule_name, 1, 1, Duration::from_millis(millis));
+        }
+        timing.record_stage_duration(TEXT_TRANSFORMATION_STAGE, Duration::from_millis(5));
+        timing.record_stage_duration("Transformation", Duration::from_millis(10));
+
+        let names: Vec<_> = timing
+            .transformation_breakdown()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "text",
+                "uses_section",
+                "procedure_section",
+                "single_keyword_sections",
+                "unit_program_section",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transformation_breakdown_skips_rules_without_candidates() {
+        let mut timing = PerformanceCollector::new();
+        timing.record_rule_timing("keyword_case", 0, 0, Duration::from_millis(1));
+        assert!(timing.transformation_breakdown().is_empty());
+    }
+}
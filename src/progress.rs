@@ -0,0 +1,50 @@
+// Progress bar for --progress, drawn on stderr so stdout output stays untouched
+use std::io::{IsTerminal, Write};
+
+const BAR_WIDTH: usize = 30;
+
+/// A single-line bar that advances once per processed file
+pub struct ProgressBar {
+    enabled: bool,
+    done: usize,
+}
+
+impl ProgressBar {
+    /// Draws nothing unless requested, stderr is a terminal, and logging is not turned off
+    pub fn new(requested: bool, quiet: bool) -> Self {
+        ProgressBar {
+            enabled: requested && !quiet && std::io::stderr().is_terminal(),
+            done: 0,
+        }
+    }
+
+    /// Count one more file out of `total`, which grows when included files are queued
+    pub fn advance(&mut self, total: usize) {
+        if !self.enabled {
+            return;
+        }
+        self.done += 1;
+        let filled = BAR_WIDTH * self.done / total.max(1);
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(
+            stderr,
+            "\r[{}{}] {}/{}",
+            "#".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.done,
+            total
+        );
+        let _ = stderr.flush();
+    }
+
+    /// Clear the bar so later stderr output starts on an empty line
+    pub fn finish(&self) {
+        if self.enabled && self.done > 0 {
+            let _ = write!(
+                std::io::stderr().lock(),
+                "\r{}\r",
+                " ".repeat(BAR_WIDTH + 24)
+            );
+        }
+    }
+}
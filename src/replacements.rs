@@ -3,6 +3,21 @@ pub struct TextReplacement {
     pub start: usize,
     pub end: usize,
     pub text: String,
+    /// Why the change is suggested, shown by `check --explain`
+    pub reason: Option<String>,
+    /// The text is already formatted, so the text pass leaves it alone; other rewrites get
+    /// the spacing rules applied to their new text
+    pub is_final: bool,
+}
+
+impl TextReplacement {
+    /// Tag the replacement with the reason it is suggested
+    pub fn with_reason(self, reason: &str) -> Self {
+        TextReplacement {
+            reason: Some(reason.to_string()),
+            ..self
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -66,29 +81,293 @@ pub fn apply_replacements_to_string(
     let mut order: Vec<_> = replacements.iter().collect();
     order.sort_by_key(|r| r.start);
 
-    // Build final text by processing original text and applying replacements.
-    let mut out = String::new();
+    output_pieces(original_source, order).concat()
+}
+
+/// The text `replacements` make from `original_source`, as the untouched source between them
+/// and their texts in order, so it can be written out without building it in memory.
+/// The replacements must be sorted by start and must not overlap.
+pub fn output_pieces<'a>(
+    original_source: &'a str,
+    replacements: impl IntoIterator<Item = &'a TextReplacement>,
+) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
     let mut current_pos = 0usize;
 
-    for replacement in order {
+    for replacement in replacements {
         if current_pos < replacement.start {
-            out.push_str(&original_source[current_pos..replacement.start]);
+            pieces.push(&original_source[current_pos..replacement.start]);
         }
-        out.push_str(&replacement.text);
+        pieces.push(replacement.text.as_str());
         current_pos = replacement.end;
     }
 
     if current_pos < original_source.len() {
-        out.push_str(&original_source[current_pos..]);
+        pieces.push(&original_source[current_pos..]);
+    }
+
+    pieces
+}
+
+/// Replace the last `len` bytes of the text that sorted, non-overlapping `replacements` make
+/// from `original_source` with `text`. The replacements producing those bytes are folded into
+/// one replacement that reaches the end of the source; it keeps their reason, or takes
+/// `reason` when none of their text is left.
+pub fn replace_output_suffix(
+    original_source: &str,
+    replacements: &mut Vec<TextReplacement>,
+    len: usize,
+    text: &str,
+    reason: &str,
+) {
+    let mut start = original_source.len();
+    let mut remaining = len;
+    let mut kept = String::new();
+    let mut kept_reason = None;
+    loop {
+        let gap = start - replacements.last().map_or(0, |replacement| replacement.end);
+        if remaining <= gap {
+            start -= remaining;
+            break;
+        }
+        remaining -= gap;
+        let replacement = replacements
+            .pop()
+            .expect("the suffix is no longer than the output");
+        start = replacement.start;
+        if remaining < replacement.text.len() {
+            kept = replacement.text[..replacement.text.len() - remaining].to_string();
+            kept_reason = replacement.reason;
+            break;
+        }
+        remaining -= replacement.text.len();
     }
+    replacements.push(TextReplacement {
+        start,
+        end: original_source.len(),
+        text: kept + text,
+        reason: kept_reason.or_else(|| Some(reason.to_string())),
+        is_final: true,
+    });
+}
 
-    out
+/// Replace the first `len` bytes of the text that sorted, non-overlapping `replacements` make
+/// from `original_source` with `text`, like `replace_output_suffix` at the other end
+pub fn replace_output_prefix(
+    original_source: &str,
+    replacements: &mut Vec<TextReplacement>,
+    len: usize,
+    text: &str,
+    reason: &str,
+) {
+    let mut end = 0usize;
+    let mut remaining = len;
+    let mut consumed = 0usize;
+    let mut kept = String::new();
+    let mut kept_reason = None;
+    loop {
+        let gap = replacements
+            .get(consumed)
+            .map_or(original_source.len(), |replacement| replacement.start)
+            - end;
+        if remaining <= gap {
+            end += remaining;
+            break;
+        }
+        remaining -= gap;
+        let replacement = replacements
+            .get(consumed)
+            .expect("the prefix is no longer than the output");
+        consumed += 1;
+        end = replacement.end;
+        if remaining < replacement.text.len() {
+            kept = replacement.text[remaining..].to_string();
+            kept_reason = replacement.reason.clone();
+            break;
+        }
+        remaining -= replacement.text.len();
+    }
+    let folded = TextReplacement {
+        start: 0,
+        end,
+        text: format!("{}{}", text, kept),
+        reason: kept_reason.or_else(|| Some(reason.to_string())),
+        is_final: true,
+    };
+    replacements.splice(..consumed, [folded]);
+}
+
+/// Clamp a requested byte range to the source, widening it to the nearest char boundaries.
+pub fn clamp_range_to_source(original_source: &str, start: usize, end: usize) -> (usize, usize) {
+    let mut start = start.min(original_source.len());
+    let mut end = end.clamp(start, original_source.len());
+    while !original_source.is_char_boundary(start) {
+        start -= 1;
+    }
+    while !original_source.is_char_boundary(end) {
+        end += 1;
+    }
+    (start, end)
+}
+
+/// Check whether a replacement lies completely inside `range`.
+pub fn is_replacement_within_range(replacement: &TextReplacement, range: (usize, usize)) -> bool {
+    replacement.start >= range.0 && replacement.end <= range.1
+}
+
+/// Check whether applying `replacement` would leave `source` unchanged.
+pub fn is_noop_replacement(source: &str, replacement: &TextReplacement) -> bool {
+    source.get(replacement.start..replacement.end) == Some(replacement.text.as_str())
+}
+
+/// Drop replacements whose span is out of bounds or splits a UTF-8 character, logging a
+/// warning for each, so malformed offsets are skipped instead of panicking when applied.
+pub fn retain_valid_replacements(source: &str, replacements: &mut Vec<TextReplacement>) {
+    replacements.retain(|replacement| {
+        let valid = replacement.start <= replacement.end
+            && source.is_char_boundary(replacement.start)
+            && source.is_char_boundary(replacement.end);
+        if !valid {
+            log::warn!(
+                "Skipping replacement at bytes {}..{}: not on character boundaries of the source",
+                replacement.start,
+                replacement.end
+            );
+        }
+        valid
+    });
+}
+
+/// Describe the change from `original` to `updated` as a single replacement covering
+/// everything between their common prefix and suffix, or `None` when they are equal.
+pub fn replacement_between(original: &str, updated: &str) -> Option<TextReplacement> {
+    if original == updated {
+        return None;
+    }
+
+    let mut prefix = original
+        .bytes()
+        .zip(updated.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(prefix) || !updated.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = original.len().min(updated.len()) - prefix;
+    let mut suffix = original
+        .bytes()
+        .rev()
+        .zip(updated.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(original.len() - suffix)
+        || !updated.is_char_boundary(updated.len() - suffix)
+    {
+        suffix -= 1;
+    }
+
+    Some(TextReplacement {
+        start: prefix,
+        end: original.len() - suffix,
+        text: updated[prefix..updated.len() - suffix].to_string(),
+        reason: None,
+        is_final: false,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_replacement_between_equal_text() {
+        assert!(replacement_between("Foo(a, b);", "Foo(a, b);").is_none());
+    }
+
+    #[test]
+    fn test_replacement_between_covers_only_changed_span() {
+        let replacement = replacement_between("Foo(a,b);", "Foo(a, b);").unwrap();
+        assert_eq!((replacement.start, replacement.end), (6, 6));
+        assert_eq!(replacement.text, " ");
+        assert_eq!(
+            apply_replacements_to_string("Foo(a,b);", &[replacement]),
+            "Foo(a, b);"
+        );
+    }
+
+    #[test]
+    fn test_replacement_between_keeps_char_boundaries() {
+        let replacement = replacement_between("x := 'é';", "x := 'è';").unwrap();
+        assert_eq!((replacement.start, replacement.end), (6, 8));
+        assert_eq!(replacement.text, "è");
+    }
+
+    #[test]
+    fn test_replace_output_suffix_folds_replacements_at_the_end() {
+        let source = "a := 1;\nb := 2;\n\n";
+        let mut replacements = vec![
+            TextReplacement {
+                start: 0,
+                end: 1,
+                text: "xx".to_string(),
+                reason: Some("first".to_string()),
+                is_final: false,
+            },
+            TextReplacement {
+                start: 8,
+                end: 17,
+                text: "b := 22;\n\n".to_string(),
+                reason: Some("second".to_string()),
+                is_final: false,
+            },
+        ];
+
+        // The suffix starts inside the second replacement, which keeps its head
+        replace_output_suffix(source, &mut replacements, 2, "\n", "final newline");
+        assert_eq!(replacements.len(), 2);
+        assert_eq!((replacements[1].start, replacements[1].end), (8, 17));
+        assert_eq!(replacements[1].text, "b := 22;\n");
+        assert_eq!(replacements[1].reason.as_deref(), Some("second"));
+
+        // A suffix covering whole replacements and source folds them into one
+        replace_output_suffix(source, &mut replacements, 17, "y\n", "final newline");
+        assert_eq!(replacements.len(), 1);
+        assert_eq!((replacements[0].start, replacements[0].end), (0, 17));
+        assert_eq!(replacements[0].text, "xy\n");
+        assert_eq!(replacements[0].reason.as_deref(), Some("first"));
+        assert_eq!(apply_replacements_to_string(source, &replacements), "xy\n");
+    }
+
+    #[test]
+    fn test_replace_output_prefix_keeps_later_replacements() {
+        let source = "\n\nunit A;\nend.";
+        let mut replacements = vec![TextReplacement {
+            start: 2,
+            end: 6,
+            text: "UNIT".to_string(),
+            reason: Some("keyword case".to_string()),
+            is_final: false,
+        }];
+
+        replace_output_prefix(source, &mut replacements, 2, "", "leading blank lines");
+        assert_eq!(replacements.len(), 2);
+        assert_eq!((replacements[0].start, replacements[0].end), (0, 2));
+        assert_eq!(
+            replacements[0].reason.as_deref(),
+            Some("leading blank lines")
+        );
+        assert_eq!(
+            apply_replacements_to_string(source, &replacements),
+            "UNIT A;\nend."
+        );
+        assert_eq!(
+            output_pieces(source, &replacements),
+            vec!["", "UNIT", " A;\nend."]
+        );
+    }
+
     #[test]
     fn test_fill_gaps_single_replacement() {
         let source = "Hello, world!";
@@ -96,6 +375,8 @@ mod tests {
             start: 7,
             end: 12,
             text: "Rust".to_string(),
+            reason: None,
+            is_final: false,
         }];
         let result = compute_source_sections(source, &replacements, &[]);
         assert_eq!(
@@ -115,11 +396,15 @@ mod tests {
                 start: 4,
                 end: 9,
                 text: "slow".to_string(),
+                reason: None,
+                is_final: false,
             },
             TextReplacement {
                 start: 10,
                 end: 15,
                 text: "green".to_string(),
+                reason: None,
+                is_final: false,
             },
         ];
         let result = compute_source_sections(source, &replacements, &[]);
@@ -144,11 +429,15 @@ mod tests {
                 start: 1,
                 end: 3,
                 text: "XX".to_string(),
+                reason: None,
+                is_final: false,
             },
             TextReplacement {
                 start: 3,
                 end: 5,
                 text: "YY".to_string(),
+                reason: None,
+                is_final: false,
             },
         ];
         let result = compute_source_sections(source, &replacements, &[]);
@@ -168,11 +457,76 @@ mod tests {
             start: 0,
             end: source.len(),
             text: "replaced".to_string(),
+            reason: None,
+            is_final: false,
         }];
         let result = compute_source_sections(source, &replacements, &[]);
         assert_eq!(result, vec![]);
     }
 
+    #[test]
+    fn test_clamp_range_to_source() {
+        let source = "abc\u{e9}def";
+        assert_eq!(clamp_range_to_source(source, 1, 100), (1, source.len()));
+        assert_eq!(clamp_range_to_source(source, 4, 4), (3, 5));
+        assert_eq!(clamp_range_to_source(source, 6, 2), (6, 6));
+    }
+
+    #[test]
+    fn test_retain_valid_replacements_skips_off_boundary_spans() {
+        let source = "S := '\u{e9}t\u{e9}';";
+        let replacement = |start: usize, end: usize| TextReplacement {
+            start,
+            end,
+            text: "x".to_string(),
+            reason: None,
+            is_final: false,
+        };
+        let mut replacements = vec![
+            replacement(0, 1),
+            replacement(7, 8),
+            replacement(6, 9),
+            replacement(11, 40),
+        ];
+        retain_valid_replacements(source, &mut replacements);
+        assert_eq!(replacements.len(), 2);
+        assert_eq!(
+            apply_replacements_to_string(source, &replacements),
+            "x := 'x\u{e9}';"
+        );
+    }
+
+    #[test]
+    fn test_is_replacement_within_range() {
+        let replacement = TextReplacement {
+            start: 4,
+            end: 8,
+            text: "x".to_string(),
+            reason: None,
+            is_final: false,
+        };
+        assert!(is_replacement_within_range(&replacement, (4, 8)));
+        assert!(is_replacement_within_range(&replacement, (0, 10)));
+        assert!(!is_replacement_within_range(&replacement, (5, 10)));
+        assert!(!is_replacement_within_range(&replacement, (0, 7)));
+    }
+
+    #[test]
+    fn test_is_noop_replacement() {
+        let source = "uses A;";
+        let replacement = |start, end, text: &str| TextReplacement {
+            start,
+            end,
+            text: text.to_string(),
+            reason: None,
+            is_final: false,
+        };
+        assert!(is_noop_replacement(source, &replacement(5, 6, "A")));
+        assert!(is_noop_replacement(source, &replacement(7, 7, "")));
+        assert!(!is_noop_replacement(source, &replacement(5, 6, "B")));
+        assert!(!is_noop_replacement(source, &replacement(5, 5, "B")));
+    }
+
     #[test]
     fn test_fill_gaps_with_excluded_ranges() {
         let source = "abcdefghij";
@@ -180,6 +534,8 @@ mod tests {
             start: 2,
             end: 4,
             text: "XX".to_string(),
+            reason: None,
+            is_final: false,
         }];
         let excluded_ranges = vec![(6, 8)];
 
@@ -201,6 +557,8 @@ mod tests {
             start: 4,
             end: 4,
             text: "()".to_string(),
+            reason: None,
+            is_final: false,
         }];
         let excluded_ranges = vec![(4, 6)];
 
@@ -222,11 +580,15 @@ mod tests {
                 start: 4,
                 end: 9,
                 text: "slow".to_string(),
+                reason: None,
+                is_final: false,
             },
             TextReplacement {
                 start: 10,
                 end: 15,
                 text: "green".to_string(),
+                reason: None,
+                is_final: false,
             },
         ];
 
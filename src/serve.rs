@@ -0,0 +1,310 @@
+// Minimal LSP server that answers `textDocument/formatting` requests over stdin/stdout
+use crate::arguments::find_config_for_filename;
+use dfixxer::replacements::{TextReplacement, replacement_between};
+use dfixxer::{DFixxerError, PerformanceCollector, process_source};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INTERNAL_ERROR: i64 = -32603;
+const PARSE_ERROR: i64 = -32700;
+
+/// Largest message body accepted, well above any source file worth formatting
+const MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Documents opened by the client, keyed by URI
+struct ServerState {
+    config_path: Option<String>,
+    config_search_root: Option<PathBuf>,
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+/// Read one `Content-Length` framed message body, or `None` at end of input
+fn read_message(reader: &mut impl BufRead) -> Result<Option<String>, DFixxerError> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = Some(value.trim().parse::<usize>().map_err(|_| {
+                DFixxerError::ParseError(format!("Invalid Content-Length header: {}", header))
+            })?);
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        DFixxerError::ParseError("Message is missing a Content-Length header".to_string())
+    })?;
+    if content_length > MAX_CONTENT_LENGTH {
+        return Err(DFixxerError::ParseError(format!(
+            "Content-Length {} exceeds the limit of {} bytes",
+            content_length, MAX_CONTENT_LENGTH
+        )));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| DFixxerError::ParseError(format!("Message is not valid UTF-8: {}", e)))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> Result<(), DFixxerError> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Convert a `file://` URI to a path usable for config lookup
+fn uri_to_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    // `file:///C:/src/a.pas` names a Windows drive path
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => &path[1..],
+        _ => path,
+    };
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            if let Some(decoded) = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(decoded);
+                continue;
+            }
+            bytes.push(byte);
+            bytes.extend(hex);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Map a byte offset to an LSP position (0-based line, UTF-16 code units)
+fn lsp_position(source: &str, offset: usize) -> Value {
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+fn text_edit(source: &str, replacement: &TextReplacement) -> Value {
+    json!({
+        "range": {
+            "start": lsp_position(source, replacement.start),
+            "end": lsp_position(source, replacement.end),
+        },
+        "newText": replacement.text,
+    })
+}
+
+/// Format a document and describe the result as LSP `TextEdit`s
+fn format_document(state: &ServerState, uri: &str) -> Result<Vec<Value>, DFixxerError> {
+    let filename = uri_to_path(uri);
+    let source = match state.documents.get(uri) {
+        Some(source) => source.clone(),
+        None => std::fs::read_to_string(&filename)?,
+    };
+
+    // Without --config, look upward from the document like the command line does
+    let config_path = state
+        .config_path
+        .clone()
+        .or_else(|| find_config_for_filename(&filename, state.config_search_root.as_deref()));
+    let config_path = config_path.as_deref().unwrap_or("dfixxer.toml");
+    let (options, _) = crate::load_config(config_path)?;
+    if !crate::is_selected_by_config(&options, &filename, config_path) {
+        return Ok(Vec::new());
+    }
+
+    let options = crate::resolve_loaded_options(&options, &filename, config_path)?;
+    let mut timing = PerformanceCollector::new();
+    let (source, updated_source, _) =
+        process_source(&filename, source, options, None, &mut timing)?;
+    Ok(replacement_between(&source, &updated_source)
+        .iter()
+        .map(|replacement| text_edit(&source, replacement))
+        .collect())
+}
+
+/// Handle one message, returning the response for requests. Responses from the client
+/// carry an `id` but no `method` and are ignored, since the server sends no requests.
+fn handle_message(state: &mut ServerState, message: &Value) -> Option<Value> {
+    let method = message.get("method")?.as_str().unwrap_or_default();
+    let params = &message["params"];
+    let id = message.get("id")?;
+
+    let result = match method {
+        "initialize" => json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "documentFormattingProvider": true,
+            },
+            "serverInfo": { "name": "dfixxer", "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "textDocument/formatting" => {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            match format_document(state, uri) {
+                Ok(edits) => Value::Array(edits),
+                Err(e) => return Some(error_response(id, INTERNAL_ERROR, &e.to_string())),
+            }
+        }
+        "shutdown" => {
+            state.shutdown_requested = true;
+            Value::Null
+        }
+        _ => {
+            return Some(error_response(
+                id,
+                METHOD_NOT_FOUND,
+                &format!("Unsupported method: {}", method),
+            ));
+        }
+    };
+    Some(response(id, result))
+}
+
+/// Track document contents sent with `didOpen`, `didChange`, and `didClose`
+fn handle_notification(state: &mut ServerState, message: &Value) {
+    let params = &message["params"];
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+    match message["method"].as_str().unwrap_or_default() {
+        "textDocument/didOpen" => {
+            if let Some(text) = params["textDocument"]["text"].as_str() {
+                state.documents.insert(uri.to_string(), text.to_string());
+            }
+        }
+        "textDocument/didChange" => {
+            // Only full document sync is advertised, so the last change holds the whole text
+            if let Some(text) = params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str())
+            {
+                state.documents.insert(uri.to_string(), text.to_string());
+            }
+        }
+        "textDocument/didClose" => {
+            state.documents.remove(uri);
+        }
+        _ => {}
+    }
+}
+
+/// Serve LSP formatting requests on stdin until the client sends `exit`
+pub fn run_server(
+    config_path: Option<&str>,
+    config_search_root: Option<PathBuf>,
+) -> Result<i32, DFixxerError> {
+    let mut state = ServerState {
+        config_path: config_path.map(str::to_string),
+        config_search_root,
+        documents: HashMap::new(),
+        shutdown_requested: false,
+    };
+    let mut reader = std::io::stdin().lock();
+    let mut writer = std::io::stdout().lock();
+
+    while let Some(body) = read_message(&mut reader)? {
+        let message: Value = match serde_json::from_str(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = error_response(&Value::Null, PARSE_ERROR, &e.to_string());
+                write_message(&mut writer, &error)?;
+                continue;
+            }
+        };
+
+        if message["method"] == "exit" {
+            return Ok(if state.shutdown_requested { 0 } else { 1 });
+        }
+        if message.get("id").is_some() {
+            if let Some(response) = handle_message(&mut state, &message) {
+                write_message(&mut writer, &response)?;
+            }
+        } else {
+            handle_notification(&mut state, &message);
+        }
+    }
+
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message_uses_content_length() {
+        let input = "Content-Length: 2\r\nContent-Type: x\r\n\r\n{}Content-Length: 4\r\n\r\nnull";
+        let mut reader = std::io::Cursor::new(input);
+        assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("{}"));
+        assert_eq!(read_message(&mut reader).unwrap().as_deref(), Some("null"));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_message_rejects_oversized_content_length() {
+        let input = format!("Content-Length: {}\r\n\r\n{{}}", MAX_CONTENT_LENGTH + 1);
+        let mut reader = std::io::Cursor::new(input);
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_client_responses_get_no_reply() {
+        let mut state = ServerState {
+            config_path: None,
+            config_search_root: None,
+            documents: HashMap::new(),
+            shutdown_requested: false,
+        };
+        let client_response = json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+        assert_eq!(handle_message(&mut state, &client_response), None);
+        let unknown_request = json!({ "jsonrpc": "2.0", "id": 2, "method": "foo/bar" });
+        assert!(handle_message(&mut state, &unknown_request).is_some());
+    }
+
+    #[test]
+    fn test_lsp_position_counts_utf16_units() {
+        let source = "a\n// 😀 x";
+        assert_eq!(
+            lsp_position(source, source.len() - 1),
+            json!({ "line": 1, "character": 6 })
+        );
+        assert_eq!(
+            lsp_position(source, 0),
+            json!({ "line": 0, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn test_uri_to_path() {
+        assert_eq!(uri_to_path("file:///home/me/a%20b.pas"), "/home/me/a b.pas");
+        assert_eq!(uri_to_path("file:///C:/src/a.pas"), "C:/src/a.pas");
+    }
+}
@@ -0,0 +1,242 @@
+use crate::dfixxer_error::DFixxerError;
+use crate::options::SourceEncoding;
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use std::io::Write;
+
+/// Encoding a file was read with, so it can be written back in the same form
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub has_bom: bool,
+}
+
+impl FileEncoding {
+    pub const UTF8: FileEncoding = FileEncoding {
+        encoding: UTF_8,
+        has_bom: false,
+    };
+
+    /// Number of bytes the byte-order mark occupies in the file
+    pub fn bom_len(&self) -> usize {
+        if !self.has_bom {
+            0
+        } else if self.encoding == UTF_8 {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Offset in the decoded `text` of `byte_offset` in the file, which counts the
+    /// byte-order mark. An offset inside an encoded character maps to the end of it.
+    pub fn text_offset(&self, text: &str, byte_offset: usize) -> usize {
+        let byte_offset = byte_offset.saturating_sub(self.bom_len());
+        if self.encoding == UTF_8 {
+            return byte_offset;
+        }
+        let utf16 = self.encoding == UTF_16LE || self.encoding == UTF_16BE;
+        let mut file_offset = 0;
+        for (index, ch) in text.char_indices() {
+            if file_offset >= byte_offset {
+                return index;
+            }
+            // The other encodings are single-byte, with one character per byte
+            file_offset += if utf16 { ch.len_utf16() * 2 } else { 1 };
+        }
+        text.len()
+    }
+}
+
+fn configured_encoding(configured: SourceEncoding) -> &'static Encoding {
+    match configured {
+        SourceEncoding::Auto | SourceEncoding::Utf8 => UTF_8,
+        SourceEncoding::Utf16Le => UTF_16LE,
+        SourceEncoding::Latin1 => WINDOWS_1252,
+    }
+}
+
+/// Decode raw file bytes to text without a byte-order mark
+///
+/// A byte-order mark always wins. Otherwise the configured encoding is used; `Auto`
+/// reads UTF-8 and falls back to Latin-1 when the bytes are not valid UTF-8.
+pub fn decode_source(
+    bytes: &[u8],
+    configured: SourceEncoding,
+) -> Result<(String, FileEncoding), DFixxerError> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        let file_encoding = FileEncoding {
+            encoding,
+            has_bom: true,
+        };
+        return Ok((text.into_owned(), file_encoding));
+    }
+
+    let encoding = configured_encoding(configured);
+    if encoding == UTF_8 {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => return Ok((text.to_string(), FileEncoding::UTF8)),
+            Err(e) if configured == SourceEncoding::Utf8 => {
+                return Err(DFixxerError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("File is not valid UTF-8: {}", e),
+                )));
+            }
+            Err(_) => {
+                log::info!("File is not valid UTF-8, reading it as Latin-1");
+                return decode_source(bytes, SourceEncoding::Latin1);
+            }
+        }
+    }
+
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    let file_encoding = FileEncoding {
+        encoding,
+        has_bom: false,
+    };
+    Ok((text.into_owned(), file_encoding))
+}
+
+/// Encode text back to the bytes of `file_encoding`, re-adding its byte-order mark
+pub fn encode_source(text: &str, file_encoding: FileEncoding) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len() + file_encoding.bom_len());
+    write_source(&mut bytes, text, file_encoding).expect("writing to a Vec cannot fail");
+    bytes
+}
+
+/// Write text to `out` in `file_encoding`, re-adding its byte-order mark
+pub fn write_source(
+    out: &mut impl Write,
+    text: &str,
+    file_encoding: FileEncoding,
+) -> std::io::Result<()> {
+    write_source_pieces(out, [text], file_encoding)
+}
+
+/// Write text given as consecutive pieces, split on character boundaries, to `out` in
+/// `file_encoding`, re-adding its byte-order mark. Each piece is encoded on its own, so the
+/// whole text is never built in memory.
+pub fn write_source_pieces<'a>(
+    out: &mut impl Write,
+    pieces: impl IntoIterator<Item = &'a str>,
+    file_encoding: FileEncoding,
+) -> std::io::Result<()> {
+    let encoding = file_encoding.encoding;
+    if file_encoding.has_bom {
+        out.write_all(if encoding == UTF_16LE {
+            &[0xFF, 0xFE]
+        } else if encoding == UTF_16BE {
+            &[0xFE, 0xFF]
+        } else {
+            &[0xEF, 0xBB, 0xBF]
+        })?;
+    }
+
+    let mut had_errors = false;
+    for piece in pieces {
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            // encoding_rs only encodes to UTF-8 for UTF-16 labels, so encode by hand
+            for unit in piece.encode_utf16() {
+                if encoding == UTF_16LE {
+                    out.write_all(&unit.to_le_bytes())?;
+                } else {
+                    out.write_all(&unit.to_be_bytes())?;
+                }
+            }
+        } else if encoding == UTF_8 {
+            out.write_all(piece.as_bytes())?;
+        } else {
+            let (encoded, _, piece_had_errors) = encoding.encode(piece);
+            had_errors |= piece_had_errors;
+            out.write_all(&encoded)?;
+        }
+    }
+    if had_errors {
+        log::warn!(
+            "Some characters cannot be represented in {} and were written as character references",
+            encoding.name()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le_with_bom(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_utf8_without_bom() {
+        let (text, file_encoding) = decode_source(b"unit A;", SourceEncoding::Auto).unwrap();
+        assert_eq!(text, "unit A;");
+        assert_eq!(file_encoding, FileEncoding::UTF8);
+    }
+
+    #[test]
+    fn test_utf8_bom_round_trip() {
+        let bytes = b"\xEF\xBB\xBFunit A;\n";
+        let (text, file_encoding) = decode_source(bytes, SourceEncoding::Auto).unwrap();
+        assert_eq!(text, "unit A;\n");
+        assert_eq!(file_encoding.bom_len(), 3);
+        assert_eq!(encode_source(&text, file_encoding), bytes);
+    }
+
+    #[test]
+    fn test_utf16le_round_trip() {
+        let bytes = utf16le_with_bom("unit Ünïcode;\r\n");
+        let (text, file_encoding) = decode_source(&bytes, SourceEncoding::Auto).unwrap();
+        assert_eq!(text, "unit Ünïcode;\r\n");
+        assert_eq!(file_encoding.encoding, UTF_16LE);
+        assert_eq!(file_encoding.bom_len(), 2);
+        assert_eq!(encode_source(&text, file_encoding), bytes);
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_latin1() {
+        let bytes = b"// Caf\xE9\nunit A;";
+        let (text, file_encoding) = decode_source(bytes, SourceEncoding::Auto).unwrap();
+        assert_eq!(text, "// Café\nunit A;");
+        assert_eq!(file_encoding.encoding, WINDOWS_1252);
+        assert_eq!(encode_source(&text, file_encoding), bytes);
+    }
+
+    #[test]
+    fn test_text_offset_maps_file_bytes_to_decoded_text() {
+        let bytes = utf16le_with_bom("Ünï := 1;");
+        let (text, file_encoding) = decode_source(&bytes, SourceEncoding::Auto).unwrap();
+        // BOM, then three characters of two bytes each
+        assert_eq!(&text[file_encoding.text_offset(&text, 8)..], " := 1;");
+        assert_eq!(file_encoding.text_offset(&text, bytes.len()), text.len());
+
+        let (text, file_encoding) = decode_source(b"Caf\xE9 := 1;", SourceEncoding::Auto).unwrap();
+        assert_eq!(&text[file_encoding.text_offset(&text, 4)..], " := 1;");
+
+        let (text, file_encoding) =
+            decode_source(b"\xEF\xBB\xBFA := 1;", SourceEncoding::Auto).unwrap();
+        assert_eq!(&text[file_encoding.text_offset(&text, 4)..], " := 1;");
+    }
+
+    #[test]
+    fn test_configured_utf8_rejects_invalid_bytes() {
+        assert!(decode_source(b"Caf\xE9", SourceEncoding::Utf8).is_err());
+    }
+
+    #[test]
+    fn test_configured_utf16le_without_bom() {
+        let bytes: Vec<u8> = "end."
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        let (text, file_encoding) = decode_source(&bytes, SourceEncoding::Utf16Le).unwrap();
+        assert_eq!(text, "end.");
+        assert!(!file_encoding.has_bom);
+        assert_eq!(encode_source(&text, file_encoding), bytes);
+    }
+}
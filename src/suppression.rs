@@ -13,20 +13,39 @@ pub struct SuppressionWarning {
 }
 
 impl SuppressionWarning {
-    pub fn message(&self) -> String {
+    pub fn message(&self, markers: &SuppressionMarkers<'_>) -> String {
         match self.kind {
             SuppressionWarningKind::UnsupportedPlacement => {
                 "inline dfixxer directive must be a standalone single-line comment".to_string()
             }
-            SuppressionWarningKind::UnmatchedOn => {
-                "encountered 'dfixxer:on' while formatting is already enabled".to_string()
-            }
-            SuppressionWarningKind::RepeatedOff => {
-                "encountered 'dfixxer:off' while formatting is already disabled".to_string()
-            }
-            SuppressionWarningKind::UnterminatedOff => {
-                "encountered 'dfixxer:off' without a later matching 'dfixxer:on'".to_string()
-            }
+            SuppressionWarningKind::UnmatchedOn => format!(
+                "encountered '{}' while formatting is already enabled",
+                markers.on
+            ),
+            SuppressionWarningKind::RepeatedOff => format!(
+                "encountered '{}' while formatting is already disabled",
+                markers.off
+            ),
+            SuppressionWarningKind::UnterminatedOff => format!(
+                "encountered '{}' without a later matching '{}'",
+                markers.off, markers.on
+            ),
+        }
+    }
+}
+
+/// Comment texts that turn formatting off and back on, matched ignoring case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuppressionMarkers<'a> {
+    pub off: &'a str,
+    pub on: &'a str,
+}
+
+impl Default for SuppressionMarkers<'_> {
+    fn default() -> Self {
+        SuppressionMarkers {
+            off: "dfixxer:off",
+            on: "dfixxer:on",
         }
     }
 }
@@ -76,7 +95,10 @@ struct CommentToken<'a> {
     is_single_line: bool,
 }
 
-pub fn collect_suppression_context(source: &str) -> SuppressionContext {
+pub fn collect_suppression_context(
+    source: &str,
+    markers: &SuppressionMarkers<'_>,
+) -> SuppressionContext {
     let line_starts = build_line_starts(source);
     let mut context = SuppressionContext::default();
     let bytes = source.as_bytes();
@@ -98,6 +120,7 @@ pub fn collect_suppression_context(source: &str) -> SuppressionContext {
                 handle_comment(
                     source,
                     &line_starts,
+                    markers,
                     &mut context,
                     &mut disabled_start,
                     &mut disabled_origin_line,
@@ -136,6 +159,7 @@ pub fn collect_suppression_context(source: &str) -> SuppressionContext {
                 handle_comment(
                     source,
                     &line_starts,
+                    markers,
                     &mut context,
                     &mut disabled_start,
                     &mut disabled_origin_line,
@@ -166,6 +190,7 @@ pub fn collect_suppression_context(source: &str) -> SuppressionContext {
                 handle_comment(
                     source,
                     &line_starts,
+                    markers,
                     &mut context,
                     &mut disabled_start,
                     &mut disabled_origin_line,
@@ -201,6 +226,43 @@ pub fn collect_suppression_context(source: &str) -> SuppressionContext {
     context
 }
 
+/// True when a comment ahead of the first code in `source` reads `marker`, ignoring case and
+/// whitespace, as in `{ dfixxer: skip }`. Compiler directives may come before it.
+pub fn has_skip_file_marker(source: &str, marker: &str) -> bool {
+    let marker = without_whitespace(marker);
+    if marker.is_empty() {
+        return false;
+    }
+    let mut rest = source.trim_start_matches('\u{feff}');
+    loop {
+        rest = rest.trim_start();
+        let (body, after) = if let Some(comment) = rest.strip_prefix("//") {
+            let end = comment.find(['\r', '\n']).unwrap_or(comment.len());
+            (&comment[..end], &comment[end..])
+        } else if let Some(comment) = rest.strip_prefix("(*") {
+            let Some(end) = comment.find("*)") else {
+                return false;
+            };
+            (&comment[..end], &comment[end + 2..])
+        } else if let Some(comment) = rest.strip_prefix('{') {
+            let Some(end) = comment.find('}') else {
+                return false;
+            };
+            (&comment[..end], &comment[end + 1..])
+        } else {
+            return false;
+        };
+        if without_whitespace(body).eq_ignore_ascii_case(&marker) {
+            return true;
+        }
+        rest = after;
+    }
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.chars().filter(|ch| !ch.is_whitespace()).collect()
+}
+
 pub fn overlaps_range(ranges: &[(usize, usize)], start: usize, end: usize) -> bool {
     for &(range_start, range_end) in ranges {
         if range_start >= end {
@@ -229,13 +291,15 @@ pub fn contains_point(ranges: &[(usize, usize)], point: usize) -> bool {
 fn handle_comment(
     source: &str,
     line_starts: &[usize],
+    markers: &SuppressionMarkers<'_>,
     context: &mut SuppressionContext,
     disabled_start: &mut Option<usize>,
     disabled_origin_line: &mut Option<usize>,
     token: CommentToken<'_>,
 ) {
-    let directive_kind = comment_directive_kind(token.kind, token.text);
-    let warning_kind = comment_directive_kind_with_trimmed_whitespace(token.kind, token.text);
+    let directive_kind = comment_directive_kind(token.kind, token.text, markers);
+    let warning_kind =
+        comment_directive_kind_with_trimmed_whitespace(token.kind, token.text, markers);
     if directive_kind.is_none() && warning_kind.is_none() {
         return;
     }
@@ -331,17 +395,22 @@ fn consume_string(bytes: &[u8], mut index: usize) -> usize {
     index
 }
 
-fn comment_directive_kind(kind: CommentKind, text: &str) -> Option<DirectiveKind> {
+fn comment_directive_kind(
+    kind: CommentKind,
+    text: &str,
+    markers: &SuppressionMarkers<'_>,
+) -> Option<DirectiveKind> {
     let body = trim_horizontal(comment_body(kind, text));
-    parse_directive_kind(body)
+    parse_directive_kind(body, markers)
 }
 
 fn comment_directive_kind_with_trimmed_whitespace(
     kind: CommentKind,
     text: &str,
+    markers: &SuppressionMarkers<'_>,
 ) -> Option<DirectiveKind> {
     let body = comment_body(kind, text).trim();
-    parse_directive_kind(body)
+    parse_directive_kind(body, markers)
 }
 
 fn comment_body(kind: CommentKind, text: &str) -> &str {
@@ -352,10 +421,10 @@ fn comment_body(kind: CommentKind, text: &str) -> &str {
     }
 }
 
-fn parse_directive_kind(text: &str) -> Option<DirectiveKind> {
-    if text.eq_ignore_ascii_case("dfixxer:off") {
+fn parse_directive_kind(text: &str, markers: &SuppressionMarkers<'_>) -> Option<DirectiveKind> {
+    if text.eq_ignore_ascii_case(markers.off.trim()) {
         Some(DirectiveKind::Off)
-    } else if text.eq_ignore_ascii_case("dfixxer:on") {
+    } else if text.eq_ignore_ascii_case(markers.on.trim()) {
         Some(DirectiveKind::On)
     } else {
         None
@@ -494,7 +563,7 @@ mod tests {
     #[test]
     fn test_collect_line_comment_directives() {
         let source = "x := 1;\n// dfixxer:off\n  y:=1+2;\n// dfixxer:on\nz:=3+4;\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert_eq!(
             context.suppressed_ranges,
@@ -507,11 +576,43 @@ mod tests {
         assert!(context.warnings.is_empty());
     }
 
+    #[test]
+    fn test_collect_configured_markers() {
+        let source = "a:=1;\n// fmt: off\nb:=2;\n// dfixxer:off\n// fmt: on\nc:=3;\n";
+        let markers = SuppressionMarkers {
+            off: "fmt: off",
+            on: "fmt: on",
+        };
+        let context = collect_suppression_context(source, &markers);
+
+        // The default markers are plain comments once others are configured
+        assert_eq!(
+            context.suppressed_ranges,
+            vec![(
+                source.find("b:=2").unwrap(),
+                source.find("// fmt: on").unwrap()
+            )]
+        );
+        assert!(context.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_skip_file_marker_only_in_leading_comments() {
+        let source = "// Generated file\n{$MODE DELPHI}\n{ DFixxer: Skip }\nunit Gen;\n";
+        assert!(has_skip_file_marker(source, "dfixxer:skip"));
+        assert!(has_skip_file_marker("(* dfixxer:skip *)", "dfixxer: skip"));
+        assert!(!has_skip_file_marker(
+            "unit Gen;\n{ dfixxer: skip }\n",
+            "dfixxer:skip"
+        ));
+        assert!(!has_skip_file_marker("{ dfixxer: skip }\n", ""));
+    }
+
     #[test]
     fn test_collect_alias_directives() {
         let source =
             "{ dfixxer:off }\na:=1;\n{ dfixxer:on }\n(* dfixxer:off *)\nb:=2;\n(* dfixxer:on *)\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert_eq!(context.suppressed_ranges.len(), 2);
         assert!(context.directive_ranges.len() >= 3);
@@ -521,7 +622,7 @@ mod tests {
     #[test]
     fn test_ignores_hash_syntax_and_strings() {
         let source = "# dfixxer off\nmsg := '// dfixxer:off';\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert!(context.suppressed_ranges.is_empty());
         assert!(context.directive_ranges.is_empty());
@@ -531,7 +632,7 @@ mod tests {
     #[test]
     fn test_warns_for_inline_directive_placement() {
         let source = "x := 1; // dfixxer:off\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert!(context.suppressed_ranges.is_empty());
         assert_eq!(
@@ -546,7 +647,7 @@ mod tests {
     #[test]
     fn test_warns_for_repeated_off_and_splits_suppression_around_directive_line() {
         let source = "// dfixxer:off\na:=1;\n// dfixxer:off\nb:=2;\n// dfixxer:on\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
         let second_off = source
             .match_indices("// dfixxer:off")
             .nth(1)
@@ -575,7 +676,7 @@ mod tests {
     #[test]
     fn test_warns_for_on_without_off() {
         let source = "// dfixxer:on\nx:=1;\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert!(context.suppressed_ranges.is_empty());
         assert_eq!(
@@ -590,7 +691,7 @@ mod tests {
     #[test]
     fn test_unterminated_off_suppresses_until_eof() {
         let source = "// dfixxer:off\r\na:=1;\r\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert_eq!(
             context.suppressed_ranges,
@@ -608,7 +709,7 @@ mod tests {
     #[test]
     fn test_multiline_block_comment_with_exact_body_warns() {
         let source = "(*\n dfixxer:off\n*)\na:=1;\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
 
         assert!(context.suppressed_ranges.is_empty());
         assert_eq!(
@@ -623,7 +724,7 @@ mod tests {
     #[test]
     fn test_suppresses_zero_length_insertions_inside_ranges() {
         let source = "// dfixxer:off\na:=1;\n// dfixxer:on\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
         let point = source.find("a:=1;").unwrap() + 1;
 
         assert!(context.suppresses_replacement(point, point));
@@ -632,7 +733,7 @@ mod tests {
     #[test]
     fn test_insertion_at_suppression_end_is_allowed() {
         let source = "// dfixxer:off\na:=1;\n// dfixxer:on\n";
-        let context = collect_suppression_context(source);
+        let context = collect_suppression_context(source, &SuppressionMarkers::default());
         let point = source.find("// dfixxer:on").unwrap();
 
         assert!(!context.suppresses_replacement(point, point));
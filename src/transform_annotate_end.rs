@@ -0,0 +1,68 @@
+use crate::parser::RoutineEndContext;
+use crate::replacements::TextReplacement;
+
+/// Append `// <qualified name>` after the `end;` that closes each top-level method body.
+/// Only whitespace may follow the `;` on its line; a trailing comment (including an existing
+/// annotation) or further code leaves the line untouched.
+pub fn transform_annotate_end(source: &str, context: &RoutineEndContext) -> Vec<TextReplacement> {
+    context
+        .routines
+        .iter()
+        .filter_map(|routine| {
+            let start = routine.semicolon_end_byte;
+            let rest = &source[start..];
+            let line_end = rest.find(['\r', '\n']).unwrap_or(rest.len());
+            if !rest[..line_end].trim().is_empty() {
+                return None;
+            }
+            Some(TextReplacement {
+                start,
+                end: start + line_end,
+                text: format!(" // {}", routine.qualified_name),
+                reason: Some("routine name after end".to_string()),
+                is_final: true,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::RoutineEnd;
+    use crate::replacements::apply_replacements_to_string;
+
+    /// Each routine ends at the `end;` that starts the first occurrence of its marker
+    fn context(source: &str, ends: &[(&str, &str)]) -> RoutineEndContext {
+        RoutineEndContext {
+            routines: ends
+                .iter()
+                .map(|(name, end)| RoutineEnd {
+                    qualified_name: name.to_string(),
+                    semicolon_end_byte: source.find(end).unwrap() + "end;".len(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_method_end_gains_qualified_name() {
+        let source = "procedure TMyClass.DoThing;\nbegin\n  Foo;\nend;  \r\n";
+        let replacements =
+            transform_annotate_end(source, &context(source, &[("TMyClass.DoThing", "end;")]));
+        assert_eq!(
+            apply_replacements_to_string(source, &replacements),
+            "procedure TMyClass.DoThing;\nbegin\n  Foo;\nend; // TMyClass.DoThing\r\n"
+        );
+    }
+
+    #[test]
+    fn test_annotated_end_is_left_alone() {
+        let source = "procedure TMyClass.DoThing;\nbegin\nend; // TMyClass.DoThing\n\nprocedure TMyClass.Other;\nbegin\nend; { done }\n";
+        let context = context(
+            source,
+            &[("TMyClass.DoThing", "end;"), ("TMyClass.Other", "end; {")],
+        );
+        assert!(transform_annotate_end(source, &context).is_empty());
+    }
+}
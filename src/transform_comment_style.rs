@@ -0,0 +1,86 @@
+use crate::options::CommentStyle;
+use crate::replacements::TextReplacement;
+
+/// Rewrite the delimiters of the `{ }` or `(* *)` comment at `comment_range` to the configured
+/// style. `//` comments and `{$...}` / `(*$...*)` directives are left alone, as is any comment
+/// whose body contains a delimiter of the target style, since it would end (or, in Free Pascal,
+/// nest) the converted comment early.
+pub fn transform_comment_style(
+    source: &str,
+    comment_range: (usize, usize),
+    comment_style: &CommentStyle,
+) -> Option<TextReplacement> {
+    let (start, end) = comment_range;
+    let original = source.get(start..end)?;
+    let text = match comment_style {
+        CommentStyle::Preserve => return None,
+        CommentStyle::Brace => {
+            let body = original.strip_prefix("(*")?.strip_suffix("*)")?;
+            if body.starts_with('$') || body.contains(['{', '}']) {
+                return None;
+            }
+            format!("{{{}}}", body)
+        }
+        CommentStyle::ParenStar => {
+            let body = original.strip_prefix('{')?.strip_suffix('}')?;
+            if body.starts_with('$') || body.contains("*)") || body.contains("(*") {
+                return None;
+            }
+            format!("(*{}*)", body)
+        }
+    };
+    Some(TextReplacement {
+        start,
+        end,
+        text,
+        reason: Some("comment style".to_string()),
+        is_final: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements::apply_replacements_to_string;
+
+    /// Convert the comment that runs from the first `{` or `(` to the end of `source`
+    fn convert(source: &str, comment_style: CommentStyle) -> String {
+        let start = source.find(['{', '(']).unwrap();
+        let replacements: Vec<_> =
+            transform_comment_style(source, (start, source.len()), &comment_style)
+                .into_iter()
+                .collect();
+        apply_replacements_to_string(source, &replacements)
+    }
+
+    #[test]
+    fn test_brace_comments_become_paren_star() {
+        assert_eq!(
+            convert("x := 1; { call Foo(a) }", CommentStyle::ParenStar),
+            "x := 1; (* call Foo(a) *)"
+        );
+        // `*)` would close the converted comment early
+        assert_eq!(
+            convert("x := 1; { a*) b }", CommentStyle::ParenStar),
+            "x := 1; { a*) b }"
+        );
+        assert_eq!(
+            convert("{$IFDEF DEBUG}", CommentStyle::ParenStar),
+            "{$IFDEF DEBUG}"
+        );
+    }
+
+    #[test]
+    fn test_paren_star_comments_become_braces() {
+        assert_eq!(
+            convert("(* two\n   lines *)", CommentStyle::Brace),
+            "{ two\n   lines }"
+        );
+        assert_eq!(
+            convert("(* set {a} *)", CommentStyle::Brace),
+            "(* set {a} *)"
+        );
+        assert_eq!(convert("(*$R+*)", CommentStyle::Brace), "(*$R+*)");
+        assert_eq!(convert("{ kept }", CommentStyle::Preserve), "{ kept }");
+    }
+}
@@ -129,6 +129,8 @@ fn tail_replacement(
         start: candidate.body_end_byte,
         end: candidate.tail_end_byte,
         text,
+        reason: Some("wrapped body in begin/end".to_string()),
+        is_final: true,
     }
 }
 
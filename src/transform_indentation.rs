@@ -0,0 +1,171 @@
+use crate::options::Options;
+use crate::parser::BlockIndentationContext;
+use crate::replacements::TextReplacement;
+use std::collections::BTreeMap;
+
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect()
+}
+
+fn line_of(line_starts: &[usize], byte: usize) -> usize {
+    line_starts.partition_point(|start| *start <= byte) - 1
+}
+
+fn line_indent<'a>(source: &'a str, line_starts: &[usize], line: usize) -> &'a str {
+    let rest = &source[line_starts[line]..];
+    let end = rest
+        .find(|ch: char| ch != ' ' && ch != '\t')
+        .unwrap_or(rest.len());
+    &rest[..end]
+}
+
+fn is_blank_line(source: &str, line_starts: &[usize], line: usize) -> bool {
+    let start = line_starts[line];
+    let end = line_starts.get(line + 1).copied().unwrap_or(source.len());
+    source[start..end].trim().is_empty()
+}
+
+/// True when only indentation precedes `byte` on its line
+fn starts_line(source: &str, line_starts: &[usize], byte: usize) -> bool {
+    let line = line_of(line_starts, byte);
+    line_starts[line] + line_indent(source, line_starts, line).len() == byte
+}
+
+/// Re-indent `begin`/`end` blocks by nesting depth using `options.indentation`.
+/// Statements that start a line get one level more than the line holding their `begin`, and
+/// an `end` that starts a line gets the same indentation as its `begin` line. Continuation
+/// lines keep their offset relative to the first line of their statement. Lines where the
+/// block's tokens share a line with other code are left alone.
+pub fn transform_indentation(
+    source: &str,
+    context: &BlockIndentationContext,
+    options: &Options,
+) -> Vec<TextReplacement> {
+    let line_starts = line_starts(source);
+    // Enclosing blocks come first, so nested blocks override the lines they contain
+    let mut indents: BTreeMap<usize, String> = BTreeMap::new();
+
+    for block in &context.blocks {
+        let begin_line = line_of(&line_starts, block.begin_start_byte);
+        let base = indents
+            .get(&begin_line)
+            .cloned()
+            .unwrap_or_else(|| line_indent(source, &line_starts, begin_line).to_string());
+        let inner = format!("{}{}", base, options.indentation);
+
+        for &(start, end) in &block.statements {
+            if !starts_line(source, &line_starts, start) {
+                continue;
+            }
+            let first_line = line_of(&line_starts, start);
+            let old_indent = line_indent(source, &line_starts, first_line);
+            indents.insert(first_line, inner.clone());
+
+            let last_line = line_of(&line_starts, end.saturating_sub(1).max(start));
+            for line in first_line + 1..=last_line {
+                if is_blank_line(source, &line_starts, line) {
+                    continue;
+                }
+                if let Some(relative_indent) =
+                    line_indent(source, &line_starts, line).strip_prefix(old_indent)
+                {
+                    indents.insert(line, format!("{}{}", inner, relative_indent));
+                }
+            }
+        }
+
+        if starts_line(source, &line_starts, block.end_start_byte) {
+            indents.insert(line_of(&line_starts, block.end_start_byte), base);
+        }
+    }
+
+    indents
+        .into_iter()
+        .filter_map(|(line, indent)| {
+            let start = line_starts[line];
+            let current = line_indent(source, &line_starts, line);
+            (current != indent).then(|| TextReplacement {
+                start,
+                end: start + current.len(),
+                text: indent,
+                reason: Some("block indentation".to_string()),
+                is_final: true,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::IndentationBlock;
+    use crate::replacements::apply_replacements_to_string;
+
+    fn span(source: &str, text: &str) -> (usize, usize) {
+        let start = source.find(text).unwrap();
+        (start, start + text.len())
+    }
+
+    fn block(source: &str, begin: &str, end: &str, statements: &[&str]) -> IndentationBlock {
+        IndentationBlock {
+            begin_start_byte: span(source, begin).0,
+            end_start_byte: span(source, end).0,
+            statements: statements.iter().map(|text| span(source, text)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_reindents_nested_block() {
+        let source = "procedure Run;\nbegin\nFoo;\n    if A then\n    begin\n  Bar(1,\n      2);\n        end;\n  end;\n";
+        let mut outer = block(
+            source,
+            "begin",
+            "end",
+            &[
+                "Foo",
+                "if A then\n    begin\n  Bar(1,\n      2);\n        end",
+            ],
+        );
+        outer.end_start_byte = source.rfind("end").unwrap();
+        let context = BlockIndentationContext {
+            blocks: vec![
+                outer,
+                block(
+                    source,
+                    "begin\n  Bar",
+                    "end;\n  end;",
+                    &["Bar(1,\n      2)"],
+                ),
+            ],
+        };
+        let replacements = transform_indentation(source, &context, &Options::default());
+        assert_eq!(
+            apply_replacements_to_string(source, &replacements),
+            "procedure Run;\nbegin\n  Foo;\n  if A then\n  begin\n    Bar(1,\n        2);\n  end;\nend;\n"
+        );
+    }
+
+    #[test]
+    fn test_indented_block_yields_no_replacements() {
+        let source = "begin\n  Foo;\n  Bar;\nend;\n";
+        let context = BlockIndentationContext {
+            blocks: vec![block(source, "begin", "end", &["Foo", "Bar"])],
+        };
+        assert!(transform_indentation(source, &context, &Options::default()).is_empty());
+    }
+
+    #[test]
+    fn test_leaves_statements_sharing_a_line_with_begin() {
+        let source = "  if A then begin Foo;\n      Bar; end;\n";
+        let context = BlockIndentationContext {
+            blocks: vec![block(source, "begin", "end", &["Foo", "Bar"])],
+        };
+        let replacements = transform_indentation(source, &context, &Options::default());
+        assert_eq!(
+            apply_replacements_to_string(source, &replacements),
+            "  if A then begin Foo;\n    Bar; end;\n"
+        );
+    }
+}
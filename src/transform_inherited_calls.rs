@@ -22,6 +22,8 @@ pub fn transform_inherited_calls(context: &InheritedExpansionContext) -> Vec<Tex
             start: candidate.insert_at,
             end: candidate.insert_at,
             text: build_call_suffix(candidate),
+            reason: Some("expanded inherited call".to_string()),
+            is_final: false,
         })
         .collect()
 }
@@ -79,6 +79,8 @@ pub fn transform_inline_local_var_definitions(
             start: routine.local_start_byte,
             end: routine.local_end_byte,
             text: options.line_ending.to_string(),
+            reason: Some("inline var definition".to_string()),
+            is_final: false,
         });
 
         if let Some(text) = inserted_block_start_text(source, routine, options) {
@@ -86,6 +88,8 @@ pub fn transform_inline_local_var_definitions(
                 start: routine.begin_insert_at,
                 end: routine.begin_insert_at,
                 text,
+                reason: Some("inline var definition".to_string()),
+                is_final: false,
             });
         }
 
@@ -120,6 +124,8 @@ pub fn transform_inline_local_var_definitions(
                     expr_start_byte,
                     expr_end_byte,
                 ),
+                reason: Some("inline var definition".to_string()),
+                is_final: false,
             });
         }
     }
@@ -155,7 +161,8 @@ begin
   B := B + 1;
 end;"#;
 
-        let (_, _, _, _, _, inline_context) = parse_with_contexts(source).expect("Failed to parse");
+        let (_, _, _, _, _, inline_context, _, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
         let replacements =
             transform_inline_local_var_definitions(source, &inline_context, &make_options());
         let result = apply_replacements_to_string(source, &replacements);
@@ -182,7 +189,8 @@ begin
   A := 1;
 end;"#;
 
-        let (_, _, _, _, _, inline_context) = parse_with_contexts(source).expect("Failed to parse");
+        let (_, _, _, _, _, inline_context, _, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
         let replacements =
             transform_inline_local_var_definitions(source, &inline_context, &make_options());
 
@@ -201,7 +209,8 @@ begin
   B := 2;
 end;"#;
 
-        let (_, _, _, _, _, inline_context) = parse_with_contexts(source).expect("Failed to parse");
+        let (_, _, _, _, _, inline_context, _, _, _, _) =
+            parse_with_contexts(source).expect("Failed to parse");
         let replacements =
             transform_inline_local_var_definitions(source, &inline_context, &make_options());
 
@@ -0,0 +1,98 @@
+use crate::options::KeywordCase;
+use crate::replacements::TextReplacement;
+
+/// Apply the configured casing to a single keyword, or `None` when casing is preserved.
+fn apply_keyword_case(keyword: &str, keyword_case: &KeywordCase) -> Option<String> {
+    match keyword_case {
+        KeywordCase::Preserve => None,
+        KeywordCase::Lower => Some(keyword.to_ascii_lowercase()),
+        KeywordCase::Upper => Some(keyword.to_ascii_uppercase()),
+        KeywordCase::PascalInitial => {
+            let mut chars = keyword.chars();
+            let first = chars.next()?;
+            Some(format!(
+                "{}{}",
+                first.to_ascii_uppercase(),
+                chars.as_str().to_ascii_lowercase()
+            ))
+        }
+    }
+}
+
+/// Rewrite a reserved word at `keyword_range` to the configured casing if it differs.
+/// A word escaped with a leading `&` (e.g. `&begin`) is an identifier and is left alone.
+pub fn transform_keyword_case(
+    source: &str,
+    keyword_range: (usize, usize),
+    keyword_case: &KeywordCase,
+) -> Option<TextReplacement> {
+    let (start, end) = keyword_range;
+    let original = source.get(start..end)?;
+    if original.starts_with('&') || source[..start].ends_with('&') {
+        return None;
+    }
+    let updated = apply_keyword_case(original, keyword_case)?;
+    if updated == original {
+        return None;
+    }
+
+    Some(TextReplacement {
+        start,
+        end,
+        text: updated,
+        reason: Some("keyword case".to_string()),
+        is_final: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_keyword_case_lower() {
+        let source = "BEGIN Foo; END";
+        let replacement = transform_keyword_case(source, (0, 5), &KeywordCase::Lower).unwrap();
+        assert_eq!(replacement.start, 0);
+        assert_eq!(replacement.end, 5);
+        assert_eq!(replacement.text, "begin");
+    }
+
+    #[test]
+    fn test_transform_keyword_case_upper() {
+        let source = "begin Foo; end";
+        let replacement = transform_keyword_case(source, (11, 14), &KeywordCase::Upper).unwrap();
+        assert_eq!(replacement.start, 11);
+        assert_eq!(replacement.end, 14);
+        assert_eq!(replacement.text, "END");
+    }
+
+    #[test]
+    fn test_transform_keyword_case_pascal_initial() {
+        let source = "iMPLEMENTATION";
+        let replacement =
+            transform_keyword_case(source, (0, 14), &KeywordCase::PascalInitial).unwrap();
+        assert_eq!(replacement.text, "Implementation");
+    }
+
+    #[test]
+    fn test_transform_keyword_case_no_change_when_already_cased() {
+        let source = "begin end";
+        assert!(transform_keyword_case(source, (0, 5), &KeywordCase::Lower).is_none());
+        assert!(transform_keyword_case(source, (6, 9), &KeywordCase::Lower).is_none());
+    }
+
+    #[test]
+    fn test_transform_keyword_case_skips_ampersand_escaped_word() {
+        let source = "&Begin := 1; X.&END";
+        assert!(transform_keyword_case(source, (0, 6), &KeywordCase::Lower).is_none());
+        assert!(transform_keyword_case(source, (1, 6), &KeywordCase::Lower).is_none());
+        assert!(transform_keyword_case(source, (16, 19), &KeywordCase::Lower).is_none());
+    }
+
+    #[test]
+    fn test_transform_keyword_case_preserve_is_no_op() {
+        let source = "BeGiN";
+        assert!(transform_keyword_case(source, (0, 5), &KeywordCase::Preserve).is_none());
+    }
+}
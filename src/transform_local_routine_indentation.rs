@@ -116,6 +116,7 @@ pub fn transform_local_routine_indentation(
         .blocks
         .iter()
         .filter_map(|block| block_replacement(source, block, options))
+        .map(|replacement| replacement.with_reason("nested routine indentation"))
         .collect()
 }
 
@@ -36,6 +36,7 @@ pub fn transform_local_routine_spacing(
         .filter_map(|gap| {
             let replacement_text = normalize_gap_text(source, gap, options)?;
             create_text_replacement_if_different(source, gap.start, gap.end, replacement_text)
+                .map(|replacement| replacement.with_reason("blank lines around nested routine"))
         })
         .collect()
 }
@@ -0,0 +1,92 @@
+use crate::options::Options;
+use crate::parser::BlockIndentationContext;
+use crate::replacements::TextReplacement;
+use crate::transformer_utility::find_line_start;
+
+fn is_comment(text: &str) -> bool {
+    text.starts_with("//") || text.starts_with('{') || text.starts_with("(*")
+}
+
+/// Move each statement that follows another statement's `;` on the same line of a `begin`/`end`
+/// block onto a line of its own, indented like the line it was on. A comment after the `;`
+/// stays where it is, and statements separated by anything but one `;` and spaces are left
+/// alone.
+pub fn transform_one_statement_per_line(
+    source: &str,
+    context: &BlockIndentationContext,
+    options: &Options,
+) -> Vec<TextReplacement> {
+    context
+        .blocks
+        .iter()
+        .flat_map(|block| block.statements.windows(2))
+        .filter_map(|pair| {
+            let ((previous_start, previous_end), (next_start, _)) = (pair[0], pair[1]);
+            if is_comment(&source[previous_start..]) || is_comment(&source[next_start..]) {
+                return None;
+            }
+            let gap = source[previous_end..next_start]
+                .trim_start_matches([' ', '\t'])
+                .strip_prefix(';')?;
+            if !gap.chars().all(|ch| ch == ' ' || ch == '\t') {
+                return None;
+            }
+            let line_start = find_line_start(source, previous_end);
+            let line = &source[line_start..];
+            let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            Some(TextReplacement {
+                start: next_start - gap.len(),
+                end: next_start,
+                text: format!("{}{}", options.line_ending, indent),
+                reason: Some("one statement per line".to_string()),
+                is_final: true,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::LineEnding;
+    use crate::parser::IndentationBlock;
+    use crate::replacements::apply_replacements_to_string;
+
+    fn span(source: &str, text: &str) -> (usize, usize) {
+        let start = source.find(text).unwrap();
+        (start, start + text.len())
+    }
+
+    fn split(source: &str, statements: &[&str]) -> String {
+        let context = BlockIndentationContext {
+            blocks: vec![IndentationBlock {
+                begin_start_byte: span(source, "begin").0,
+                end_start_byte: source.rfind("end").unwrap(),
+                statements: statements.iter().map(|text| span(source, text)).collect(),
+            }],
+        };
+        let options = Options {
+            line_ending: LineEnding::Lf,
+            ..Default::default()
+        };
+        let replacements = transform_one_statement_per_line(source, &context, &options);
+        apply_replacements_to_string(source, &replacements)
+    }
+
+    #[test]
+    fn test_two_statement_line_is_split() {
+        assert_eq!(
+            split("begin\n  a := 1;  b := 2;\nend;\n", &["a := 1", "b := 2"]),
+            "begin\n  a := 1;\n  b := 2;\nend;\n"
+        );
+    }
+
+    #[test]
+    fn test_trailing_comment_and_separate_lines_are_kept() {
+        let source = "begin\n  a := 1; // one\n  b := 2; { two }\nend;\n";
+        assert_eq!(
+            split(source, &["a := 1", "// one", "b := 2", "{ two }"]),
+            source
+        );
+    }
+}
@@ -2,24 +2,53 @@ use crate::options::Options;
 use crate::parser::{CodeSection, Kind};
 use crate::replacements::TextReplacement;
 
+fn is_horizontal_whitespace(text: &str) -> bool {
+    text.chars().all(|ch| ch == ' ' || ch == '\t')
+}
+
 /// Transform procedure/function declaration sections by adding parentheses after identifier
+///
+/// Spaces and tabs between the keyword and the name collapse to one space, and spaces and tabs
+/// between the name and a directly following `;` are removed. Line breaks and comments in
+/// those gaps are left as written.
 pub fn transform_procedure_section(
     code_section: &CodeSection,
     _options: &Options,
-    _source: &str,
+    source: &str,
 ) -> Option<TextReplacement> {
     // Find the identifier in siblings
     let identifier_node = code_section
         .siblings
         .iter()
         .find(|node| node.kind == Kind::Identifier)?;
+    let name = &source[identifier_node.start_byte..identifier_node.end_byte];
+
+    // Replace the gap after the keyword only when it is not already a single space
+    let keyword_gap = &source[code_section.keyword.end_byte..identifier_node.start_byte];
+    let (start, prefix) = if keyword_gap != " " && is_horizontal_whitespace(keyword_gap) {
+        (code_section.keyword.end_byte, format!(" {}", name))
+    } else {
+        (identifier_node.end_byte, String::new())
+    };
+
+    // Pull a `;` that directly follows the name (procedures) up to the parentheses
+    let end = code_section
+        .siblings
+        .iter()
+        .find(|node| node.kind == Kind::Semicolon)
+        .filter(|semicolon| {
+            semicolon.start_byte >= identifier_node.end_byte
+                && is_horizontal_whitespace(&source[identifier_node.end_byte..semicolon.start_byte])
+        })
+        .map_or(identifier_node.end_byte, |semicolon| semicolon.start_byte);
 
-    // Create the replacement: insert "()" after the identifier and before semicolon
-    // We want to insert at the position right after the identifier ends
+    // Insert "()" after the identifier and before the semicolon
     Some(TextReplacement {
-        start: identifier_node.end_byte,
-        end: identifier_node.end_byte, // Insert, don't replace
-        text: "()".to_string(),
+        start,
+        end,
+        text: format!("{}()", prefix),
+        reason: Some("empty parameter list".to_string()),
+        is_final: false,
     })
 }
 
@@ -87,4 +116,73 @@ mod tests {
         assert_eq!(replacement.end, 12); // Insert, don't replace
         assert_eq!(replacement.text, "()".to_string());
     }
+
+    fn transformed(source: &str, keyword_kind: Kind, keyword: &str, name: &str) -> String {
+        let name_start = source.find(name).unwrap();
+        let semicolon_start = source.rfind(';').unwrap();
+        let code_section = CodeSection {
+            keyword: create_test_parsed_node(keyword_kind, 0, keyword.len()),
+            siblings: vec![
+                create_test_parsed_node(Kind::Identifier, name_start, name_start + name.len()),
+                create_test_parsed_node(Kind::Semicolon, semicolon_start, semicolon_start + 1),
+            ],
+        };
+        let replacement =
+            transform_procedure_section(&code_section, &Options::default(), source).unwrap();
+        crate::replacements::apply_replacements_to_string(source, &[replacement])
+    }
+
+    #[test]
+    fn test_normalizes_spaces_between_keyword_and_name() {
+        assert_eq!(
+            transformed(
+                "procedure    Foo;",
+                Kind::ProcedureDeclaration,
+                "procedure",
+                "Foo"
+            ),
+            "procedure Foo();"
+        );
+        assert_eq!(
+            transformed(
+                "procedure Foo  ;",
+                Kind::ProcedureDeclaration,
+                "procedure",
+                "Foo"
+            ),
+            "procedure Foo();"
+        );
+        assert_eq!(
+            transformed(
+                "function  Bar: Integer;",
+                Kind::FunctionDeclaration,
+                "function",
+                "Bar"
+            ),
+            "function Bar(): Integer;"
+        );
+    }
+
+    #[test]
+    fn test_normalizes_tabs_between_keyword_and_name() {
+        assert_eq!(
+            transformed(
+                "procedure\t\tFoo\t;",
+                Kind::ProcedureDeclaration,
+                "procedure",
+                "Foo"
+            ),
+            "procedure Foo();"
+        );
+        // A line break between the keyword and the name is kept
+        assert_eq!(
+            transformed(
+                "procedure\n  Foo;",
+                Kind::ProcedureDeclaration,
+                "procedure",
+                "Foo"
+            ),
+            "procedure\n  Foo();"
+        );
+    }
 }
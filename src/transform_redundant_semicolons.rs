@@ -0,0 +1,38 @@
+use crate::parser::RedundantSemicolonContext;
+use crate::replacements::TextReplacement;
+
+/// Remove empty statements that directly follow another `;` inside `begin`/`end` blocks,
+/// collapsing `x;; y` to `x; y`. A single `;` before `end` is never touched.
+pub fn transform_redundant_semicolons(context: &RedundantSemicolonContext) -> Vec<TextReplacement> {
+    context
+        .ranges
+        .iter()
+        .map(|&(start, end)| TextReplacement {
+            start,
+            end,
+            text: String::new(),
+            reason: Some("redundant semicolon".to_string()),
+            is_final: true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replacements::apply_replacements_to_string;
+
+    #[test]
+    fn test_collapses_doubled_semicolons() {
+        let source = "begin\n  x;; y;\n  z; ;\nend;";
+        let first = source.find(";;").unwrap() + 1;
+        let second = source.find("; ;").unwrap() + 1;
+        let context = RedundantSemicolonContext {
+            ranges: vec![(first, first + 1), (second, second + 2)],
+        };
+        assert_eq!(
+            apply_replacements_to_string(source, &transform_redundant_semicolons(&context)),
+            "begin\n  x; y;\n  z;\nend;"
+        );
+    }
+}
@@ -5,6 +5,31 @@ use crate::transformer_utility::{
     adjust_replacement_for_line_position, create_text_replacement_if_different,
 };
 
+/// Extend the replacement back over the whitespace before the keyword so exactly
+/// `blank_lines` empty lines separate it from the preceding code.
+/// Returns `None` at the top of the file, where no blank lines are inserted.
+fn replacement_with_blank_lines_before(
+    source: &str,
+    keyword_start: usize,
+    keyword_text: &str,
+    blank_lines: usize,
+    options: &Options,
+) -> Option<(usize, String)> {
+    let content_end = source[..keyword_start].trim_end().len();
+    if source[..content_end]
+        .trim_start_matches('\u{feff}')
+        .is_empty()
+    {
+        return None;
+    }
+
+    let line_ending = options.line_ending.to_string();
+    Some((
+        content_end,
+        format!("{}{}", line_ending.repeat(blank_lines + 1), keyword_text),
+    ))
+}
+
 /// Transform a single keyword section to lowercase if needed
 pub fn transform_single_keyword_section(
     source: &str,
@@ -22,8 +47,26 @@ pub fn transform_single_keyword_section(
     let keyword_end = code_section.keyword.end_byte;
     let original_keyword = &source[keyword_start..keyword_end];
 
-    // Check if the keyword is already lowercase
     let lowercase_keyword = original_keyword.to_lowercase();
+    if options.section_layout.enforce_blank_lines_before
+        && let Some((replacement_start, replacement_text)) = replacement_with_blank_lines_before(
+            source,
+            keyword_start,
+            &lowercase_keyword,
+            options.section_layout.blank_lines_before,
+            options,
+        )
+    {
+        return create_text_replacement_if_different(
+            source,
+            replacement_start,
+            keyword_end,
+            replacement_text,
+        )
+        .map(|replacement| replacement.with_reason("blank lines before section keyword"));
+    }
+
+    // Check if the keyword is already lowercase
     if original_keyword == lowercase_keyword {
         return None; // No transformation needed
     }
@@ -34,6 +77,7 @@ pub fn transform_single_keyword_section(
 
     // Create replacement if the text is different
     create_text_replacement_if_different(source, replacement_start, keyword_end, replacement_text)
+        .map(|replacement| replacement.with_reason("section keyword on its own line"))
 }
 
 #[cfg(test)]
@@ -135,6 +179,66 @@ mod tests {
         assert_eq!(replacement.text, "\ninterface".to_string()); // Should have newline prepended
     }
 
+    fn make_blank_line_options(blank_lines_before: usize) -> Options {
+        let mut options = make_options();
+        options.section_layout.enforce_blank_lines_before = true;
+        options.section_layout.blank_lines_before = blank_lines_before;
+        options
+    }
+
+    #[test]
+    fn test_blank_lines_before_inserted_when_missing() {
+        let source = "unit A;\ninterface";
+        let code_section = make_code_section(Kind::Interface, 8, 17);
+        let options = make_blank_line_options(1);
+
+        let replacement =
+            transform_single_keyword_section(source, &code_section, &options).unwrap();
+
+        assert_eq!(replacement.start, 7);
+        assert_eq!(replacement.end, 17);
+        assert_eq!(replacement.text, "\n\ninterface");
+    }
+
+    #[test]
+    fn test_blank_lines_before_collapses_extra_lines_and_indentation() {
+        let source = "end;\n\n\n\n  IMPLEMENTATION";
+        let code_section = make_code_section(Kind::Implementation, 10, 24);
+        let options = make_blank_line_options(2);
+
+        let replacement =
+            transform_single_keyword_section(source, &code_section, &options).unwrap();
+
+        assert_eq!(replacement.start, 4);
+        assert_eq!(replacement.end, 24);
+        assert_eq!(replacement.text, "\n\n\nimplementation");
+    }
+
+    #[test]
+    fn test_blank_lines_before_already_matching() {
+        let source = "uses A;\n\ninitialization";
+        let code_section = make_code_section(Kind::Initialization, 9, 23);
+        let options = make_blank_line_options(1);
+
+        let result = transform_single_keyword_section(source, &code_section, &options);
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_blank_lines_before_not_inserted_at_top_of_file() {
+        let source = "\u{feff}\n\nINTERFACE";
+        let bom_len = '\u{feff}'.len_utf8();
+        let code_section = make_code_section(Kind::Interface, bom_len + 2, bom_len + 11);
+        let options = make_blank_line_options(1);
+
+        let replacement =
+            transform_single_keyword_section(source, &code_section, &options).unwrap();
+
+        assert_eq!(replacement.start, bom_len + 2);
+        assert_eq!(replacement.text, "interface");
+    }
+
     #[test]
     fn test_skip_non_single_keyword_sections() {
         let source = "USES";
@@ -1,4 +1,4 @@
-use crate::options::{LineEnding, SpaceOperation, TextChangeOptions};
+use crate::options::{Dialect, HexCase, LineEnding, SpaceOperation, TextChangeOptions};
 use crate::parser::SpacingContext;
 use crate::replacements::TextReplacement;
 use std::collections::{BTreeMap, HashMap};
@@ -22,12 +22,34 @@ const RULE_ASSIGN_MUL: &str = "assign_mul";
 const RULE_ASSIGN_DIV: &str = "assign_div";
 const RULE_COLON: &str = "colon";
 const RULE_COLON_NUMERIC_EXCEPTION: &str = "colon_numeric_exception";
+const RULE_LABEL_COLON: &str = "label_colon";
+const RULE_PAREN_INNER: &str = "paren_inner";
+const RULE_BRACKET_INNER: &str = "bracket_inner";
+const RULE_CALL_PAREN: &str = "call_paren";
+const RULE_KEYWORD_PAREN: &str = "keyword_paren";
+const RULE_WORD_OPERATOR: &str = "word_operator";
+const RULE_AMP: &str = "amp";
+
+/// Reserved words that act as operators and take the `word_operator` spacing
+const WORD_OPERATORS: &[&str] = &["div", "mod", "and", "or", "not", "xor"];
+
+/// Keywords that are followed by a parenthesized expression rather than a call
+const PAREN_KEYWORDS: &[&str] = &[
+    "and", "as", "case", "div", "do", "downto", "else", "if", "in", "is", "mod", "not", "of", "or",
+    "repeat", "shl", "shr", "then", "to", "until", "while", "with", "xor",
+];
 const RULE_BRACE_COMMENT_SPACING: &str = "space_inside_brace_comments";
+const RULE_PRESERVE_DIRECTIVES: &str = "preserve_directives";
 const RULE_PAREN_STAR_COMMENT_SPACING: &str = "space_inside_paren_star_comments";
 const RULE_LINE_COMMENT_SLASH_SPACING: &str = "space_after_line_comment_slashes";
 const RULE_TRIM_TRAILING_WHITESPACE: &str = "trim_trailing_whitespace";
 const RULE_ENSURE_SINGLE_TRAILING_NEWLINE: &str = "ensure_single_trailing_newline";
+const RULE_NORMALIZE_LINE_ENDINGS: &str = "normalize_line_endings";
+const RULE_TRIM_LEADING_BLANK_LINES: &str = "trim_leading_blank_lines";
+const RULE_MAX_CONSECUTIVE_BLANK_LINES: &str = "max_consecutive_blank_lines";
 const RULE_ENFORCE_WORD_CASING: &str = "enforce_word_casing";
+const RULE_ALIGN_TRAILING_COMMENTS: &str = "align_trailing_comments";
+const RULE_HEX_CASE: &str = "hex_case";
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct TextRuleStats {
@@ -136,7 +158,7 @@ pub fn apply_text_transformation(
     text: &str,
     options: &TextChangeOptions,
 ) -> Option<TextReplacement> {
-    apply_text_transformation_with_context(start, end, text, options, None)
+    apply_text_transformation_with_context(start, end, text, options, Dialect::Delphi, None)
 }
 
 /// Apply text transformations using optional AST-derived spacing context.
@@ -145,27 +167,67 @@ pub fn apply_text_transformation_with_context(
     end: usize,
     text: &str,
     options: &TextChangeOptions,
+    dialect: Dialect,
     context: Option<&SpacingContext>,
 ) -> Option<TextReplacement> {
-    apply_text_changes(text, options, start, context, None).map(|modified| TextReplacement {
-        start,
-        end,
-        text: modified,
+    apply_text_changes(text, options, dialect, start, context, None).map(|modified| {
+        TextReplacement {
+            start,
+            end,
+            text: modified,
+            reason: Some("spacing and whitespace".to_string()),
+            is_final: true,
+        }
     })
 }
 
+/// Apply text transformations to the new text of every rewrite that is not final. Rewrites
+/// such as procedure headers are built from source code and still need the spacing rules;
+/// final ones, such as a sorted `uses` clause, are left as their transformer wrote them.
+pub fn apply_text_transformation_to_rewrites(
+    replacements: &mut [TextReplacement],
+    options: &TextChangeOptions,
+    dialect: Dialect,
+    context: Option<&SpacingContext>,
+    stats: &mut TextTransformationStats,
+) {
+    for replacement in replacements
+        .iter_mut()
+        .filter(|replacement| !replacement.is_final)
+    {
+        // Spacing fixes inside a rewrite keep the rewrite's reason
+        if let Some(transformed) = apply_text_transformation_with_context_and_stats(
+            replacement.start,
+            replacement.end,
+            &replacement.text,
+            options,
+            dialect,
+            context,
+            stats,
+        ) {
+            replacement.text = transformed.text;
+        }
+        replacement.is_final = true;
+    }
+}
+
 pub fn apply_text_transformation_with_context_and_stats(
     start: usize,
     end: usize,
     text: &str,
     options: &TextChangeOptions,
+    dialect: Dialect,
     context: Option<&SpacingContext>,
     stats: &mut TextTransformationStats,
 ) -> Option<TextReplacement> {
-    apply_text_changes(text, options, start, context, Some(stats)).map(|modified| TextReplacement {
-        start,
-        end,
-        text: modified,
+    apply_text_changes(text, options, dialect, start, context, Some(stats)).map(|modified| {
+        TextReplacement {
+            start,
+            end,
+            text: modified,
+            reason: Some("spacing and whitespace".to_string()),
+            is_final: true,
+        }
     })
 }
 
@@ -195,10 +257,73 @@ pub fn apply_file_level_text_changes_with_stats(
 
     let configured_line_ending = line_ending.to_string();
     let preferred_line_ending = preferred_line_ending_for_eof(text, &configured_line_ending);
-    let changed = ensure_single_trailing_newline(text, preferred_line_ending);
-    stats.record_file_level_run(changed.is_some());
-    stats.record_rule(RULE_ENSURE_SINGLE_TRAILING_NEWLINE, changed.is_some());
-    changed
+    let updated = ensure_single_trailing_newline(text, preferred_line_ending);
+    stats.record_rule(RULE_ENSURE_SINGLE_TRAILING_NEWLINE, updated.is_some());
+    stats.record_file_level_run(updated.is_some());
+    updated
+}
+
+/// Rewrite the line endings of a text-pass section or a replacement's text to `line_ending`.
+/// It runs on those pieces rather than on the finished file, so suppressed and unparsed
+/// regions keep their line endings.
+pub fn normalize_line_endings_with_stats(
+    text: &str,
+    line_ending: &LineEnding,
+    stats: &mut TextTransformationStats,
+) -> Option<String> {
+    let updated = normalize_line_endings(text, &line_ending.to_string());
+    stats.record_rule(RULE_NORMALIZE_LINE_ENDINGS, updated.is_some());
+    updated
+}
+
+/// Remove whitespace-only lines at the start of the file, keeping a leading byte-order mark.
+/// Only the start of the file is touched, so it is applied separately from the end-of-file changes.
+pub fn trim_leading_blank_lines_with_stats(
+    text: &str,
+    options: &TextChangeOptions,
+    stats: &mut TextTransformationStats,
+) -> Option<String> {
+    if !options.trim_leading_blank_lines {
+        return None;
+    }
+
+    let bom = if text.starts_with('\u{feff}') {
+        "\u{feff}"
+    } else {
+        ""
+    };
+    let body = &text[bom.len()..];
+    let mut first_line_start = 0;
+    for line in body.split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            break;
+        }
+        first_line_start += line.len();
+    }
+    // A file made only of blank lines is left for the trailing newline rule
+    let changed = first_line_start > 0 && first_line_start < body.len();
+    stats.record_rule(RULE_TRIM_LEADING_BLANK_LINES, changed);
+    changed.then(|| format!("{}{}", bom, &body[first_line_start..]))
+}
+
+/// Rewrite every `\r\n`, lone `\r`, and lone `\n` to `line_ending`
+fn normalize_line_endings(text: &str, line_ending: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                result.push_str(line_ending);
+            }
+            '\n' => result.push_str(line_ending),
+            _ => result.push(ch),
+        }
+    }
+
+    if result == text { None } else { Some(result) }
 }
 
 fn preferred_line_ending_for_eof<'a>(text: &str, fallback: &'a str) -> &'a str {
@@ -226,32 +351,6 @@ fn ensure_single_trailing_newline(text: &str, line_ending: &str) -> Option<Strin
     }
 }
 
-/// Helper function to determine if space should be added before a character/operator
-fn should_add_space_before(
-    operation: &SpaceOperation,
-    prev_char: Option<char>,
-    target_char: char,
-) -> bool {
-    match operation {
-        SpaceOperation::NoChange => false,
-        SpaceOperation::After => false, // Handled elsewhere
-        SpaceOperation::Before => {
-            if let Some(prev_ch) = prev_char {
-                !prev_ch.is_whitespace() && prev_ch != target_char
-            } else {
-                false
-            }
-        }
-        SpaceOperation::BeforeAndAfter => {
-            if let Some(prev_ch) = prev_char {
-                !prev_ch.is_whitespace() && prev_ch != target_char
-            } else {
-                false
-            }
-        }
-    }
-}
-
 type CharIter<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
 
 struct OperatorContext<'a, 'b, F>
@@ -259,7 +358,6 @@ where
     F: Fn(char, &mut String, &mut String),
 {
     chars: &'a mut CharIter<'b>,
-    prev_char: Option<char>,
     current_line: &'a mut String,
     result: &'a mut String,
     push_char: &'a F,
@@ -374,6 +472,58 @@ fn closes_brace_comment_on_current_line(chars: &CharIter<'_>) -> bool {
     false
 }
 
+/// Normalize the body of a single-line `{$...}` directive: collapse runs of spaces and tabs to
+/// one space, drop trailing whitespace, and apply the `comma` rule. Quoted strings are kept as is.
+fn normalize_directive_body(body: &str, comma: &SpaceOperation) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    let mut in_string = false;
+    while let Some(ch) = chars.next() {
+        if in_string {
+            out.push(ch);
+            in_string = ch != '\'';
+            continue;
+        }
+        match ch {
+            '\'' => {
+                out.push(ch);
+                in_string = true;
+            }
+            ' ' | '\t' => {
+                while matches!(chars.peek(), Some(' ' | '\t')) {
+                    chars.next();
+                }
+                if chars.peek().is_some() {
+                    out.push(' ');
+                }
+            }
+            ',' if is_rule_enabled(comma) => {
+                let trimmed_len = out.trim_end_matches([' ', '\t']).len();
+                out.truncate(trimmed_len);
+                if matches!(
+                    comma,
+                    SpaceOperation::Before | SpaceOperation::BeforeAndAfter
+                ) {
+                    out.push(' ');
+                }
+                out.push(',');
+                while matches!(chars.peek(), Some(' ' | '\t')) {
+                    chars.next();
+                }
+                if matches!(
+                    comma,
+                    SpaceOperation::After | SpaceOperation::BeforeAndAfter
+                ) && chars.peek().is_some()
+                {
+                    out.push(' ');
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
 fn closes_paren_star_comment_on_current_line(chars: &CharIter<'_>) -> bool {
     let probe = chars.clone();
     let mut prev_was_star = false;
@@ -493,6 +643,21 @@ fn flush_line_ending(
     }
 }
 
+/// Check whether the line written so far (since the last line break) is blank
+fn current_line_is_blank(buf: &str) -> bool {
+    buf.rsplit(['\n', '\r'])
+        .next()
+        .unwrap_or("")
+        .trim()
+        .is_empty()
+}
+
+/// Remove the partially written current line from `buf`
+fn drop_current_line(buf: &mut String) {
+    let line_start = buf.rfind(['\n', '\r']).map_or(0, |idx| idx + 1);
+    buf.truncate(line_start);
+}
+
 /// Generic handler for two-character operators
 fn handle_two_char_operator<'a, 'b, F>(
     first_char: char,
@@ -508,14 +673,8 @@ where
     let mut changed = false;
     match operation {
         SpaceOperation::NoChange => {
-            if should_add_space_before(operation, ctx.prev_char, first_char) {
-                push_char(' ', ctx.current_line, ctx.result);
-            }
             push_char(first_char, ctx.current_line, ctx.result);
             push_char(second_char, ctx.current_line, ctx.result);
-            if should_add_space_after(operation, ctx.chars.peek().map(|(_, ch)| *ch), second_char) {
-                push_char(' ', ctx.current_line, ctx.result);
-            }
         }
         _ => {
             let buf = active_buf(ctx.do_trim, ctx.current_line, ctx.result);
@@ -537,6 +696,148 @@ where
     changed
 }
 
+/// Pick the rule for the space before '(' from the token that precedes it on the current line
+///
+/// A keyword such as `if` selects `keyword_paren`; any other identifier is treated as a call and
+/// selects `call_paren`. Other tokens (operators, brackets, line starts) select no rule.
+fn paren_spacing_rule<'a>(
+    buf: &str,
+    options: &'a TextChangeOptions,
+) -> Option<(&'a SpaceOperation, &'static str)> {
+    let line = buf.rsplit(['\n', '\r']).next().unwrap_or("");
+    let token_end = line.trim_end_matches([' ', '\t']);
+    let word_start = token_end
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_identifier_continue(*ch))
+        .last()
+        .map(|(idx, _)| idx)?;
+    let word = &token_end[word_start..];
+    if !word.starts_with(is_identifier_start) {
+        return None;
+    }
+
+    let escaped = token_end[..word_start].ends_with('&');
+    if !escaped && PAREN_KEYWORDS.contains(&word.to_lowercase().as_str()) {
+        Some((&options.keyword_paren, RULE_KEYWORD_PAREN))
+    } else {
+        Some((&options.call_paren, RULE_CALL_PAREN))
+    }
+}
+
+/// Check whether a word operator needs a space against `neighbor` to stay a separate token
+fn word_operator_needs_separator(neighbor: Option<char>) -> bool {
+    neighbor.is_some_and(|ch| is_identifier_continue(ch) || ch == '\'' || ch == '&')
+}
+
+/// Apply spacing before a word operator such as `div` that is about to be pushed
+fn apply_word_operator_spacing_before(operation: &SpaceOperation, buf: &mut String) -> bool {
+    let mut changed = remove_trailing_ws(buf);
+    let last = buf.chars().last();
+    let wants_space = matches!(
+        operation,
+        SpaceOperation::Before | SpaceOperation::BeforeAndAfter
+    ) && !matches!(last, Some('(' | '['));
+    if (wants_space || word_operator_needs_separator(last)) && current_line_has_non_ws(buf) {
+        changed |= ensure_one_space_before(buf);
+    }
+    changed
+}
+
+/// Apply spacing after a word operator such as `div` that was just pushed
+fn apply_word_operator_spacing_after(
+    operation: &SpaceOperation,
+    chars: &mut CharIter<'_>,
+    buf: &mut String,
+) -> bool {
+    let consumed = consume_following_ws(chars);
+    let Some((_, next)) = chars.peek().copied() else {
+        return consumed > 0;
+    };
+    if next == '\n' || next == '\r' {
+        return consumed > 0;
+    }
+
+    let wants_space = matches!(
+        operation,
+        SpaceOperation::After | SpaceOperation::BeforeAndAfter
+    ) && !matches!(next, ')' | ']' | ';' | ',');
+    if wants_space || word_operator_needs_separator(Some(next)) {
+        buf.push(' ');
+        consumed != 1
+    } else {
+        consumed > 0
+    }
+}
+
+/// Apply spacing before an `&` identifier escape that is about to be pushed
+///
+/// Only the space before `&` is managed: `Before` and `BeforeAndAfter` keep one space there and
+/// `Remove` drops it. The escaped word always stays attached to the `&`.
+fn apply_amp_spacing_before(operation: &SpaceOperation, buf: &mut String) -> bool {
+    if !matches!(
+        operation,
+        SpaceOperation::Before | SpaceOperation::BeforeAndAfter | SpaceOperation::Remove
+    ) || !current_line_has_non_ws(buf)
+    {
+        return false;
+    }
+
+    let removed = remove_trailing_horizontal_ws(buf);
+    let last = buf.chars().last();
+    let wants_space = !matches!(operation, SpaceOperation::Remove)
+        && !matches!(last, Some('(' | '[' | '.' | '@'));
+    // `and &begin` must not become `and&begin`
+    if wants_space || (removed > 0 && word_operator_needs_separator(last)) {
+        buf.push(' ');
+        removed != 1
+    } else {
+        removed > 0
+    }
+}
+
+/// Apply inner spacing after an opening '(' or '[' that was just pushed
+///
+/// `After` and `BeforeAndAfter` keep one space after the opening bracket; `Remove` drops it.
+fn apply_opening_bracket_inner_spacing(
+    operation: &SpaceOperation,
+    closing_char: char,
+    chars: &mut CharIter<'_>,
+    buf: &mut String,
+) -> bool {
+    let mut changed = consume_following_ws(chars) > 0;
+    if matches!(
+        operation,
+        SpaceOperation::After | SpaceOperation::BeforeAndAfter
+    ) && let Some((_, nc)) = chars.peek().copied()
+        && !nc.is_whitespace()
+        && nc != closing_char
+    {
+        buf.push(' ');
+        changed = true;
+    }
+    changed
+}
+
+/// Apply inner spacing before a closing ')' or ']' that is about to be pushed
+///
+/// `Before` and `BeforeAndAfter` keep one space before the closing bracket; `Remove` drops it.
+fn apply_closing_bracket_inner_spacing(
+    operation: &SpaceOperation,
+    opening_char: char,
+    buf: &mut String,
+) -> bool {
+    let mut changed = remove_trailing_ws(buf);
+    if matches!(
+        operation,
+        SpaceOperation::Before | SpaceOperation::BeforeAndAfter
+    ) && current_line_has_non_ws(buf)
+    {
+        changed |= one_space_before_if_needed(buf, opening_char);
+    }
+    changed
+}
+
 /// Helper function to handle multi-character operators
 fn handle_operator<'a, 'b, F>(
     current_char: char,
@@ -563,32 +864,6 @@ where
     }
 }
 
-/// Helper function to determine if space should be added after a character
-fn should_add_space_after(
-    operation: &SpaceOperation,
-    next_char: Option<char>,
-    target_char: char,
-) -> bool {
-    match operation {
-        SpaceOperation::NoChange => false,
-        SpaceOperation::After => {
-            if let Some(next_ch) = next_char {
-                !next_ch.is_whitespace() && next_ch != target_char
-            } else {
-                false
-            }
-        }
-        SpaceOperation::Before => false, // Handled elsewhere
-        SpaceOperation::BeforeAndAfter => {
-            if let Some(next_ch) = next_char {
-                !next_ch.is_whitespace() && next_ch != target_char
-            } else {
-                false
-            }
-        }
-    }
-}
-
 /// Helper function to check if a character is numeric (digit)
 fn is_numeric_char(ch: char) -> bool {
     ch.is_ascii_digit()
@@ -610,6 +885,24 @@ fn should_skip_colon_spacing(
     }
 }
 
+/// Check whether the line written so far holds only indentation and a numeric label.
+fn is_numeric_label_prefix(buf: &str) -> bool {
+    let line = buf.rsplit(['\n', '\r']).next().unwrap_or("");
+    let label = line.trim();
+    !label.is_empty() && label.chars().all(|ch| ch.is_ascii_digit())
+}
+
+/// Check whether the ':' at `abs_pos` terminates a statement label rather than a declaration.
+fn is_label_colon(context: Option<&SpacingContext>, abs_pos: usize, line_so_far: &str) -> bool {
+    if context.is_some_and(|ctx| ctx.label_colon_positions.contains(&abs_pos)) {
+        return true;
+    }
+    if context.is_some_and(|ctx| ctx.case_label_colon_positions.contains(&abs_pos)) {
+        return false;
+    }
+    is_numeric_label_prefix(line_so_far)
+}
+
 fn is_negative_literal_minus(context: Option<&SpacingContext>, abs_pos: usize) -> bool {
     context.is_some_and(|ctx| ctx.negative_literal_minus_positions.contains(&abs_pos))
 }
@@ -626,6 +919,20 @@ fn is_positive_literal_plus(context: Option<&SpacingContext>, abs_pos: usize) ->
     context.is_some_and(|ctx| ctx.positive_literal_plus_positions.contains(&abs_pos))
 }
 
+/// Spacing after a unary sign; the space before it belongs to the preceding token.
+fn unary_sign_space_after(
+    op: &SpaceOperation,
+    chars: &mut CharIter<'_>,
+    buf: &mut String,
+    this_char: char,
+) {
+    if *op == SpaceOperation::NoChange {
+        return;
+    }
+    let _ = consume_following_ws(chars);
+    let _ = space_after_if_needed(op, chars, buf, this_char);
+}
+
 fn is_exponent_sign(context: Option<&SpacingContext>, abs_pos: usize) -> bool {
     context.is_some_and(|ctx| ctx.exponent_sign_positions.contains(&abs_pos))
 }
@@ -729,6 +1036,7 @@ fn overlapping_error_ranges(
 fn apply_text_changes(
     text: &str,
     options: &TextChangeOptions,
+    dialect: Dialect,
     start_offset: usize,
     context: Option<&SpacingContext>,
     mut stats: Option<&mut TextTransformationStats>,
@@ -736,8 +1044,14 @@ fn apply_text_changes(
     with_text_stats(&mut stats, |stats| stats.record_section(text.len()));
     let error_ranges = overlapping_error_ranges(context, start_offset, text.len());
     if error_ranges.is_empty() {
-        let changed =
-            apply_text_changes_core(text, options, start_offset, context, stats.as_deref_mut());
+        let changed = apply_text_changes_core(
+            text,
+            options,
+            dialect,
+            start_offset,
+            context,
+            stats.as_deref_mut(),
+        );
         if changed.is_some() {
             with_text_stats(&mut stats, |stats| stats.record_changed_section());
         }
@@ -759,6 +1073,7 @@ fn apply_text_changes(
             if let Some(changed) = apply_text_changes_core(
                 clean_segment,
                 options,
+                dialect,
                 cursor_abs,
                 context,
                 stats.as_deref_mut(),
@@ -781,6 +1096,7 @@ fn apply_text_changes(
         if let Some(changed) = apply_text_changes_core(
             clean_segment,
             options,
+            dialect,
             cursor_abs,
             context,
             stats.as_deref_mut(),
@@ -799,10 +1115,76 @@ fn apply_text_changes(
     }
 }
 
+/// Byte offsets of string literals in `text` that are still open at a line break.
+/// Only strings and comments are tracked, so unlike the text pass this also scans parser error
+/// spans, which is where such strings end up.
+pub fn unterminated_string_offsets(text: &str, dialect: Dialect) -> Vec<usize> {
+    let bytes = text.as_bytes();
+    let nests = dialect == Dialect::FreePascal;
+    let mut offsets = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => {
+                let start = i;
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        // Doubled quotes escape a quote inside the string
+                        Some(b'\'') if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+                        Some(b'\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(b'\r' | b'\n') => {
+                            offsets.push(start);
+                            break;
+                        }
+                        Some(_) => i += 1,
+                        None => break,
+                    }
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && !matches!(bytes[i], b'\r' | b'\n') {
+                    i += 1;
+                }
+            }
+            b'{' => i = comment_end(bytes, i + 1, b"{", b"}", nests),
+            b'(' if bytes.get(i + 1) == Some(&b'*') => {
+                i = comment_end(bytes, i + 2, b"(*", b"*)", nests)
+            }
+            _ => i += 1,
+        }
+    }
+    offsets
+}
+
+/// Index just past the comment whose body starts at `i`; nested openers count when `nests`
+fn comment_end(bytes: &[u8], mut i: usize, open: &[u8], close: &[u8], nests: bool) -> usize {
+    let mut depth = 1usize;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(close) {
+            i += close.len();
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        } else if nests && bytes[i..].starts_with(open) {
+            i += open.len();
+            depth += 1;
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
+
 /// Apply all text changes to a text string based on the given options
 fn apply_text_changes_core(
     text: &str,
     options: &TextChangeOptions,
+    dialect: Dialect,
     start_offset: usize,
     context: Option<&SpacingContext>,
     mut stats: Option<&mut TextTransformationStats>,
@@ -826,7 +1208,15 @@ fn apply_text_changes_core(
     let mut brace_comment_apply_single_line_spacing = false;
     let mut brace_comment_spacing_changed = false;
     let mut paren_star_comment_apply_single_line_spacing = false;
+    // Free Pascal nests `(* *)` and `{ }` comments; Delphi ends them at the first closer
+    let mut paren_star_comment_depth = 0usize;
+    let mut brace_comment_depth = 0usize;
     let mut paren_star_comment_spacing_changed = false;
+    // Blank lines seen in a row; a section starting mid-line never counts its first line as blank
+    let mut consecutive_blank_lines = 0usize;
+    let mut first_line_is_partial = start_offset > 0;
+    // Output offset of every `//` that opens a line comment from code
+    let mut line_comment_starts: Vec<usize> = Vec::new();
     let enforce_word_casing_rules: HashMap<String, String> = options
         .enforce_word_casing
         .iter()
@@ -849,6 +1239,8 @@ fn apply_text_changes_core(
 
     while let Some((idx, ch)) = chars.next() {
         let abs_pos = start_offset + idx;
+        // Lines ending inside comments or strings are never blank, and their blank lines are kept
+        let line_break_outside_code = (ch == '\n' || ch == '\r') && state != State::Code;
         match state {
             State::Code => {
                 match ch {
@@ -857,9 +1249,34 @@ fn apply_text_changes_core(
                         push_char(ch, &mut current_line, &mut result);
                         state = State::StringLiteral;
                     }
+                    '{' if !options.preserve_directives
+                        && matches!(chars.peek(), Some((_, '$')))
+                        && closes_brace_comment_on_current_line(&chars) =>
+                    {
+                        // Single-line directive whose body is normalized like code
+                        let mut body = String::new();
+                        for (_, body_char) in chars.by_ref() {
+                            if body_char == '}' {
+                                break;
+                            }
+                            body.push(body_char);
+                        }
+                        let normalized = normalize_directive_body(&body, &options.comma);
+                        with_text_stats(&mut stats, |stats| {
+                            stats.record_rule(RULE_PRESERVE_DIRECTIVES, normalized != body)
+                        });
+                        push_char('{', &mut current_line, &mut result);
+                        for normalized_char in normalized.chars() {
+                            push_char(normalized_char, &mut current_line, &mut result);
+                        }
+                        push_char('}', &mut current_line, &mut result);
+                        prev_char = Some('}');
+                        continue;
+                    }
                     '{' => {
                         // Brace comment
                         push_char(ch, &mut current_line, &mut result);
+                        brace_comment_depth = 1;
                         brace_comment_apply_single_line_spacing = false;
                         brace_comment_spacing_changed = false;
                         if options.space_inside_brace_comments {
@@ -899,6 +1316,7 @@ fn apply_text_changes_core(
                             let (_, star) = chars.next().unwrap();
                             push_char('(', &mut current_line, &mut result);
                             push_char(star, &mut current_line, &mut result);
+                            paren_star_comment_depth = 1;
                             paren_star_comment_apply_single_line_spacing = false;
                             paren_star_comment_spacing_changed = false;
                             if options.space_inside_paren_star_comments {
@@ -931,13 +1349,81 @@ fn apply_text_changes_core(
                             }
                             state = State::ParenStarComment;
                         } else {
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            if let Some((operation, rule)) = paren_spacing_rule(buf, options)
+                                && is_rule_enabled(operation)
+                            {
+                                let mut changed = remove_trailing_ws(buf);
+                                if matches!(
+                                    operation,
+                                    SpaceOperation::Before | SpaceOperation::BeforeAndAfter
+                                ) {
+                                    changed |= ensure_one_space_before(buf);
+                                }
+                                with_text_stats(&mut stats, |stats| {
+                                    stats.record_rule(rule, changed)
+                                });
+                            }
                             push_char('(', &mut current_line, &mut result);
+                            if is_rule_enabled(&options.paren_inner) {
+                                let buf = active_buf(do_trim, &mut current_line, &mut result);
+                                let changed = apply_opening_bracket_inner_spacing(
+                                    &options.paren_inner,
+                                    ')',
+                                    &mut chars,
+                                    buf,
+                                );
+                                with_text_stats(&mut stats, |stats| {
+                                    stats.record_rule(RULE_PAREN_INNER, changed)
+                                });
+                            }
+                        }
+                    }
+                    ')' | ']' => {
+                        let (operation, opening_char, rule) = if ch == ')' {
+                            (&options.paren_inner, '(', RULE_PAREN_INNER)
+                        } else {
+                            (&options.bracket_inner, '[', RULE_BRACKET_INNER)
+                        };
+                        if is_rule_enabled(operation) {
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            let changed =
+                                apply_closing_bracket_inner_spacing(operation, opening_char, buf);
+                            with_text_stats(&mut stats, |stats| stats.record_rule(rule, changed));
+                        }
+                        push_char(ch, &mut current_line, &mut result);
+                    }
+                    '&' => {
+                        if is_rule_enabled(&options.amp) {
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            let changed = apply_amp_spacing_before(&options.amp, buf);
+                            with_text_stats(&mut stats, |stats| {
+                                stats.record_rule(RULE_AMP, changed)
+                            });
+                        }
+                        push_char('&', &mut current_line, &mut result);
+                    }
+                    '[' => {
+                        push_char('[', &mut current_line, &mut result);
+                        if is_rule_enabled(&options.bracket_inner) {
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            let changed = apply_opening_bracket_inner_spacing(
+                                &options.bracket_inner,
+                                ']',
+                                &mut chars,
+                                buf,
+                            );
+                            with_text_stats(&mut stats, |stats| {
+                                stats.record_rule(RULE_BRACKET_INNER, changed)
+                            });
                         }
                     }
                     '/' => {
                         if let Some((_, '/')) = chars.peek().copied() {
                             // line comment
                             let (_, slash2) = chars.next().unwrap();
+                            line_comment_starts
+                                .push(result.len() + if do_trim { current_line.len() } else { 0 });
                             push_char('/', &mut current_line, &mut result);
                             push_char(slash2, &mut current_line, &mut result);
                             if options.space_after_line_comment_slashes {
@@ -966,7 +1452,6 @@ fn apply_text_changes_core(
                         } else if let Some(_changed) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -982,17 +1467,7 @@ fn apply_text_changes_core(
                         } else {
                             match options.fdiv {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.fdiv, prev_char, '/') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('/', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.fdiv,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '/',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1025,17 +1500,7 @@ fn apply_text_changes_core(
                     ',' => {
                         match options.comma {
                             SpaceOperation::NoChange => {
-                                if should_add_space_before(&options.comma, prev_char, ',') {
-                                    push_char(' ', &mut current_line, &mut result);
-                                }
                                 push_char(',', &mut current_line, &mut result);
-                                if should_add_space_after(
-                                    &options.comma,
-                                    chars.peek().map(|(_, ch)| *ch),
-                                    ',',
-                                ) {
-                                    push_char(' ', &mut current_line, &mut result);
-                                }
                             }
                             ref op => {
                                 let buf = if do_trim {
@@ -1081,17 +1546,7 @@ fn apply_text_changes_core(
                     }
                     ';' => match options.semi_colon {
                         SpaceOperation::NoChange => {
-                            if should_add_space_before(&options.semi_colon, prev_char, ';') {
-                                push_char(' ', &mut current_line, &mut result);
-                            }
                             push_char(';', &mut current_line, &mut result);
-                            if should_add_space_after(
-                                &options.semi_colon,
-                                chars.peek().map(|(_, ch)| *ch),
-                                ';',
-                            ) {
-                                push_char(' ', &mut current_line, &mut result);
-                            }
                         }
                         ref op => {
                             let buf = if do_trim {
@@ -1138,7 +1593,6 @@ fn apply_text_changes_core(
                         } else if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1154,7 +1608,6 @@ fn apply_text_changes_core(
                         } else if let Some(_changed) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1170,17 +1623,7 @@ fn apply_text_changes_core(
                         } else {
                             match options.lt {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.lt, prev_char, '<') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('<', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.lt,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '<',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1212,17 +1655,7 @@ fn apply_text_changes_core(
                     }
                     '=' => match options.eq {
                         SpaceOperation::NoChange => {
-                            if should_add_space_before(&options.eq, prev_char, '=') {
-                                push_char(' ', &mut current_line, &mut result);
-                            }
                             push_char('=', &mut current_line, &mut result);
-                            if should_add_space_after(
-                                &options.eq,
-                                chars.peek().map(|(_, ch)| *ch),
-                                '=',
-                            ) {
-                                push_char(' ', &mut current_line, &mut result);
-                            }
                         }
                         ref op => {
                             let buf = if do_trim {
@@ -1272,7 +1705,6 @@ fn apply_text_changes_core(
                         } else if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1288,17 +1720,7 @@ fn apply_text_changes_core(
                         } else {
                             match options.gt {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.gt, prev_char, '>') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('>', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.gt,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '>',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1332,7 +1754,6 @@ fn apply_text_changes_core(
                         if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1345,27 +1766,25 @@ fn apply_text_changes_core(
                                     stats.record_rule(RULE_ASSIGN_ADD, false)
                                 });
                             }
-                        } else if is_unary_plus(context, abs_pos)
-                            || is_positive_literal_plus(context, abs_pos)
-                            || is_exponent_sign(context, abs_pos)
+                        } else if is_exponent_sign(context, abs_pos)
                             || is_exponent_sign_lexical(text, idx)
                         {
                             push_char('+', &mut current_line, &mut result);
                             let _ = consume_following_ws(&mut chars);
+                        } else if is_unary_plus(context, abs_pos)
+                            || is_positive_literal_plus(context, abs_pos)
+                        {
+                            push_char('+', &mut current_line, &mut result);
+                            let buf = if do_trim {
+                                &mut current_line
+                            } else {
+                                &mut result
+                            };
+                            unary_sign_space_after(&options.unary_sign, &mut chars, buf, '+');
                         } else {
                             match options.add {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.add, prev_char, '+') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('+', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.add,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '+',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1399,7 +1818,6 @@ fn apply_text_changes_core(
                         if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1412,27 +1830,25 @@ fn apply_text_changes_core(
                                     stats.record_rule(RULE_ASSIGN_SUB, false)
                                 });
                             }
-                        } else if is_negative_literal_minus(context, abs_pos)
-                            || is_unary_minus(context, abs_pos)
-                            || is_exponent_sign(context, abs_pos)
+                        } else if is_exponent_sign(context, abs_pos)
                             || is_exponent_sign_lexical(text, idx)
                         {
                             push_char('-', &mut current_line, &mut result);
                             let _ = consume_following_ws(&mut chars);
+                        } else if is_negative_literal_minus(context, abs_pos)
+                            || is_unary_minus(context, abs_pos)
+                        {
+                            push_char('-', &mut current_line, &mut result);
+                            let buf = if do_trim {
+                                &mut current_line
+                            } else {
+                                &mut result
+                            };
+                            unary_sign_space_after(&options.unary_sign, &mut chars, buf, '-');
                         } else {
                             match options.sub {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.sub, prev_char, '-') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('-', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.sub,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '-',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1466,7 +1882,6 @@ fn apply_text_changes_core(
                         if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1482,17 +1897,7 @@ fn apply_text_changes_core(
                         } else {
                             match options.mul {
                                 SpaceOperation::NoChange => {
-                                    if should_add_space_before(&options.mul, prev_char, '*') {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char('*', &mut current_line, &mut result);
-                                    if should_add_space_after(
-                                        &options.mul,
-                                        chars.peek().map(|(_, ch)| *ch),
-                                        '*',
-                                    ) {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
                                 ref op => {
                                     let buf = if do_trim {
@@ -1526,7 +1931,6 @@ fn apply_text_changes_core(
                         if let Some(_handled) = {
                             let mut ctx = OperatorContext {
                                 chars: &mut chars,
-                                prev_char,
                                 current_line: &mut current_line,
                                 result: &mut result,
                                 push_char: &push_char,
@@ -1541,36 +1945,34 @@ fn apply_text_changes_core(
                             }
                         } else {
                             // Single ':' operator
-                            // Check if we should skip spacing due to numeric exception (e.g., time format like "12:34")
-                            let skip_spacing = should_skip_colon_spacing(
-                                options.colon_numeric_exception,
-                                prev_char,
-                                chars.peek().map(|(_, ch)| *ch),
+                            // Statement labels ("10: WriteLn;" or "Done:") use their own rule
+                            let is_label = is_label_colon(
+                                context,
+                                abs_pos,
+                                if do_trim { &current_line } else { &result },
                             );
+                            let (colon_op, colon_rule) = if is_label {
+                                (&options.label_colon, RULE_LABEL_COLON)
+                            } else {
+                                (&options.colon, RULE_COLON)
+                            };
+                            // Check if we should skip spacing due to numeric exception (e.g., time format like "12:34")
+                            let skip_spacing = !is_label
+                                && should_skip_colon_spacing(
+                                    options.colon_numeric_exception,
+                                    prev_char,
+                                    chars.peek().map(|(_, ch)| *ch),
+                                );
                             if skip_spacing {
                                 with_text_stats(&mut stats, |stats| {
                                     stats.record_skip(RULE_COLON_NUMERIC_EXCEPTION)
                                 });
                             }
-                            match options.colon {
+                            match colon_op {
                                 SpaceOperation::NoChange => {
-                                    if !skip_spacing
-                                        && should_add_space_before(&options.colon, prev_char, ':')
-                                    {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                     push_char(':', &mut current_line, &mut result);
-                                    if !skip_spacing
-                                        && should_add_space_after(
-                                            &options.colon,
-                                            chars.peek().map(|(_, ch)| *ch),
-                                            ':',
-                                        )
-                                    {
-                                        push_char(' ', &mut current_line, &mut result);
-                                    }
                                 }
-                                ref op => {
+                                op => {
                                     let buf = if do_trim {
                                         &mut current_line
                                     } else {
@@ -1600,7 +2002,7 @@ fn apply_text_changes_core(
                                     }
                                     if !skip_spacing {
                                         with_text_stats(&mut stats, |stats| {
-                                            stats.record_rule(RULE_COLON, false)
+                                            stats.record_rule(colon_rule, false)
                                         });
                                     }
                                 }
@@ -1608,10 +2010,62 @@ fn apply_text_changes_core(
                         }
                     }
                     '\n' | '\r' => {
+                        // The '\n' of a CRLF pair ends the line already counted at '\r'
+                        let continues_crlf = ch == '\n' && prev_char == Some('\r');
+                        if options.max_consecutive_blank_lines > 0 && !continues_crlf {
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            if !first_line_is_partial && current_line_is_blank(buf) {
+                                consecutive_blank_lines += 1;
+                            } else {
+                                consecutive_blank_lines = 0;
+                            }
+                            if consecutive_blank_lines > options.max_consecutive_blank_lines {
+                                drop_current_line(buf);
+                                if ch == '\r' && matches!(chars.peek(), Some((_, '\n'))) {
+                                    chars.next();
+                                }
+                                with_text_stats(&mut stats, |stats| {
+                                    stats.record_rule(RULE_MAX_CONSECUTIVE_BLANK_LINES, true)
+                                });
+                                first_line_is_partial = false;
+                                prev_char = Some('\n');
+                                continue;
+                            }
+                        }
+                        first_line_is_partial = false;
                         flush_line_ending(ch, do_trim, &mut current_line, &mut result, &mut stats);
                     }
+                    '$' if options.hex_case != HexCase::Preserve => {
+                        // `$` starts a hexadecimal literal; a `$` without digits is kept as-is
+                        push_char(ch, &mut current_line, &mut result);
+                        let mut last_char = ch;
+                        let mut changed = false;
+                        while let Some((_, digit)) = chars.peek().copied()
+                            && digit.is_ascii_hexdigit()
+                        {
+                            chars.next();
+                            let normalized = if options.hex_case == HexCase::Upper {
+                                digit.to_ascii_uppercase()
+                            } else {
+                                digit.to_ascii_lowercase()
+                            };
+                            changed |= normalized != digit;
+                            push_char(normalized, &mut current_line, &mut result);
+                            last_char = digit;
+                        }
+                        if last_char != ch {
+                            with_text_stats(&mut stats, |stats| {
+                                stats.record_rule(RULE_HEX_CASE, changed)
+                            });
+                        }
+                        prev_char = Some(last_char);
+                        continue;
+                    }
                     _ => {
-                        if !enforce_word_casing_rules.is_empty() && is_identifier_start(ch) {
+                        if (!enforce_word_casing_rules.is_empty()
+                            || is_rule_enabled(&options.word_operator))
+                            && is_identifier_start(ch)
+                        {
                             let mut identifier = String::new();
                             identifier.push(ch);
                             let mut last_identifier_char = ch;
@@ -1626,11 +2080,16 @@ fn apply_text_changes_core(
                                 }
                             }
 
+                            // `&begin` escapes a keyword into an identifier and is kept verbatim
+                            let escaped = prev_char == Some('&');
                             let normalized_identifier = identifier.to_lowercase();
                             let output = enforce_word_casing_rules
                                 .get(&normalized_identifier)
+                                .filter(|_| !escaped)
                                 .unwrap_or(&identifier);
-                            if enforce_word_casing_rules.contains_key(&normalized_identifier) {
+                            if !escaped
+                                && enforce_word_casing_rules.contains_key(&normalized_identifier)
+                            {
                                 with_text_stats(&mut stats, |stats| {
                                     stats.record_rule(
                                         RULE_ENFORCE_WORD_CASING,
@@ -1639,10 +2098,30 @@ fn apply_text_changes_core(
                                 });
                             }
 
-                            if do_trim {
-                                current_line.push_str(output);
-                            } else {
-                                result.push_str(output);
+                            // Word operators are whole tokens, never part of `xand` or `Obj.Mod`
+                            let is_word_operator = is_rule_enabled(&options.word_operator)
+                                && WORD_OPERATORS.contains(&normalized_identifier.as_str())
+                                && !matches!(
+                                    prev_char,
+                                    Some(c) if is_identifier_continue(c) || c == '&' || c == '.'
+                                );
+
+                            let buf = active_buf(do_trim, &mut current_line, &mut result);
+                            let mut word_operator_changed = false;
+                            if is_word_operator {
+                                word_operator_changed |=
+                                    apply_word_operator_spacing_before(&options.word_operator, buf);
+                            }
+                            buf.push_str(output);
+                            if is_word_operator {
+                                word_operator_changed |= apply_word_operator_spacing_after(
+                                    &options.word_operator,
+                                    &mut chars,
+                                    buf,
+                                );
+                                with_text_stats(&mut stats, |stats| {
+                                    stats.record_rule(RULE_WORD_OPERATOR, word_operator_changed)
+                                });
                             }
 
                             prev_char = Some(last_identifier_char);
@@ -1687,6 +2166,13 @@ fn apply_text_changes_core(
                 if ch == '\n' || ch == '\r' {
                     // Handle newlines in brace comments consistently
                     flush_line_ending(ch, do_trim, &mut current_line, &mut result, &mut stats);
+                } else if ch == '{' && dialect == Dialect::FreePascal {
+                    push_char(ch, &mut current_line, &mut result);
+                    brace_comment_depth += 1;
+                } else if ch == '}' && brace_comment_depth > 1 {
+                    // Closes a nested comment; the outer one continues
+                    push_char(ch, &mut current_line, &mut result);
+                    brace_comment_depth -= 1;
                 } else if ch == '}' {
                     if brace_comment_apply_single_line_spacing {
                         let buf = active_buf(do_trim, &mut current_line, &mut result);
@@ -1701,6 +2187,7 @@ fn apply_text_changes_core(
                     push_char(ch, &mut current_line, &mut result);
                     brace_comment_apply_single_line_spacing = false;
                     brace_comment_spacing_changed = false;
+                    brace_comment_depth = 0;
                     state = State::Code;
                 } else {
                     push_char(ch, &mut current_line, &mut result);
@@ -1710,25 +2197,43 @@ fn apply_text_changes_core(
                 if ch == '\n' || ch == '\r' {
                     // Handle newlines in paren-star comments consistently
                     flush_line_ending(ch, do_trim, &mut current_line, &mut result, &mut stats);
+                } else if ch == '('
+                    && dialect == Dialect::FreePascal
+                    && matches!(chars.peek(), Some((_, '*')))
+                {
+                    let (_, star) = chars.next().unwrap();
+                    push_char(ch, &mut current_line, &mut result);
+                    push_char(star, &mut current_line, &mut result);
+                    paren_star_comment_depth += 1;
                 } else if ch == '*' {
                     // Look ahead for ) to end comment
                     if let Some((_, ')')) = chars.peek().copied() {
-                        if paren_star_comment_apply_single_line_spacing {
-                            let buf = active_buf(do_trim, &mut current_line, &mut result);
-                            paren_star_comment_spacing_changed |= ensure_single_trailing_space(buf);
-                            with_text_stats(&mut stats, |stats| {
-                                stats.record_rule(
-                                    RULE_PAREN_STAR_COMMENT_SPACING,
-                                    paren_star_comment_spacing_changed,
-                                )
-                            });
+                        if paren_star_comment_depth > 1 {
+                            // Closes a nested comment; the outer one continues
+                            push_char(ch, &mut current_line, &mut result);
+                            let (_, closing_paren) = chars.next().unwrap();
+                            push_char(closing_paren, &mut current_line, &mut result);
+                            paren_star_comment_depth -= 1;
+                        } else {
+                            if paren_star_comment_apply_single_line_spacing {
+                                let buf = active_buf(do_trim, &mut current_line, &mut result);
+                                paren_star_comment_spacing_changed |=
+                                    ensure_single_trailing_space(buf);
+                                with_text_stats(&mut stats, |stats| {
+                                    stats.record_rule(
+                                        RULE_PAREN_STAR_COMMENT_SPACING,
+                                        paren_star_comment_spacing_changed,
+                                    )
+                                });
+                            }
+                            push_char(ch, &mut current_line, &mut result);
+                            let (_, closing_paren) = chars.next().unwrap();
+                            push_char(closing_paren, &mut current_line, &mut result);
+                            paren_star_comment_apply_single_line_spacing = false;
+                            paren_star_comment_spacing_changed = false;
+                            paren_star_comment_depth = 0;
+                            state = State::Code;
                         }
-                        push_char(ch, &mut current_line, &mut result);
-                        let (_, closing_paren) = chars.next().unwrap();
-                        push_char(closing_paren, &mut current_line, &mut result);
-                        paren_star_comment_apply_single_line_spacing = false;
-                        paren_star_comment_spacing_changed = false;
-                        state = State::Code;
                     } else {
                         push_char(ch, &mut current_line, &mut result);
                     }
@@ -1738,6 +2243,11 @@ fn apply_text_changes_core(
             }
         }
 
+        if line_break_outside_code {
+            consecutive_blank_lines = 0;
+            first_line_is_partial = false;
+        }
+
         // Update previous character for next iteration
         prev_char = Some(ch);
     }
@@ -1753,9 +2263,98 @@ fn apply_text_changes_core(
         });
         result.push_str(trimmed);
     }
+    if options.align_trailing_comments {
+        let aligned = align_trailing_comments(&result, &line_comment_starts, start_offset > 0);
+        with_text_stats(&mut stats, |stats| {
+            stats.record_rule(RULE_ALIGN_TRAILING_COMMENTS, aligned.is_some())
+        });
+        if let Some(aligned) = aligned {
+            result = aligned;
+        }
+    }
     if result == text { None } else { Some(result) }
 }
 
+/// Pad the code in front of trailing `//` comments so that each run of consecutive lines ending
+/// in one starts its comments in the same column, one space past the widest code. A blank line,
+/// a line without a trailing comment, or a change of indentation ends the run, so comments are
+/// never aligned across block boundaries. Runs that are already aligned are left alone.
+/// `comment_starts` holds the offset of every `//` that opened a line comment from code; the
+/// first line is skipped when the text starts mid-line, since its column is unknown.
+fn align_trailing_comments(
+    text: &str,
+    comment_starts: &[usize],
+    skip_first_line: bool,
+) -> Option<String> {
+    struct TrailingComment<'a> {
+        code_end: usize,
+        comment_start: usize,
+        indent: &'a str,
+        width: usize,
+        column: usize,
+    }
+
+    let mut lines: Vec<Option<TrailingComment>> = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let line_end = line_start + line.len();
+        let index = comment_starts.partition_point(|start| *start < line_start);
+        let comment = comment_starts
+            .get(index)
+            .filter(|start| **start < line_end)
+            .and_then(|&comment_start| {
+                let prefix = &text[line_start..comment_start];
+                let code = prefix.trim_end();
+                if code.trim_start().is_empty() {
+                    return None;
+                }
+                let indent_len = code.len() - code.trim_start().len();
+                Some(TrailingComment {
+                    code_end: line_start + code.len(),
+                    comment_start,
+                    indent: &code[..indent_len],
+                    width: code.chars().count(),
+                    column: prefix.chars().count(),
+                })
+            });
+        lines.push(comment.filter(|_| !(skip_first_line && line_start == 0)));
+        line_start = line_end;
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    let mut rest = lines.as_slice();
+    while let Some(first) = rest.first() {
+        let run_len = match first {
+            Some(first) => rest
+                .iter()
+                .take_while(|line| {
+                    line.as_ref()
+                        .is_some_and(|line| line.indent == first.indent)
+                })
+                .count(),
+            None => 1,
+        };
+        let (run, tail) = rest.split_at(run_len);
+        rest = tail;
+        let run: Vec<&TrailingComment> = run.iter().flatten().collect();
+        if run.len() < 2 || run.iter().all(|line| line.column == run[0].column) {
+            continue;
+        }
+        let column = run.iter().map(|line| line.width).max().unwrap_or(0) + 1;
+        for line in run {
+            result.push_str(&text[copied_to..line.code_end]);
+            result.push_str(&" ".repeat(column - line.width));
+            copied_to = line.comment_start;
+        }
+    }
+    if copied_to == 0 {
+        return None;
+    }
+    result.push_str(&text[copied_to..]);
+    (result != text).then_some(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1780,6 +2379,8 @@ mod tests {
             start: 0,
             end: 8,
             text: "A,B,C".to_string(),
+            reason: None,
+            is_final: false,
         };
         let options = TextChangeOptions {
             comma: SpaceOperation::After,
@@ -1818,6 +2419,8 @@ mod tests {
             start: 11,
             end: 15,
             text: " and ".to_string(),
+            reason: None,
+            is_final: false,
         };
         let result2 = apply_text_transformation(
             replacement2.start,
@@ -1832,6 +2435,8 @@ mod tests {
             start: 15,
             end: 23,
             text: "Baz,Qux".to_string(),
+            reason: None,
+            is_final: false,
         };
         let result3 = apply_text_transformation(
             replacement3.start,
@@ -1856,6 +2461,8 @@ mod tests {
             start: 0,
             end: 11,
             text: "uses,System".to_string(),
+            reason: None,
+            is_final: false,
         };
         let result1 = apply_text_transformation(
             uses_replacement.start,
@@ -1871,6 +2478,8 @@ mod tests {
             start: 11,
             end: 23,
             text: " test,code".to_string(),
+            reason: None,
+            is_final: false,
         };
         let result2 = apply_text_transformation(
             regular_replacement.start,
@@ -1881,6 +2490,31 @@ mod tests {
         assert_eq!(result2.unwrap().text, " test, code".to_string());
     }
 
+    #[test]
+    fn test_text_pass_skips_final_rewrites() {
+        let rewrite = |text: &str, is_final| TextReplacement {
+            start: 0,
+            end: 4,
+            text: text.to_string(),
+            reason: Some("rewrite".to_string()),
+            is_final,
+        };
+        let mut replacements = vec![rewrite("uses A,B;", true), rewrite("Foo(a,b);", false)];
+
+        apply_text_transformation_to_rewrites(
+            &mut replacements,
+            &TextChangeOptions::default(),
+            Dialect::Delphi,
+            None,
+            &mut TextTransformationStats::default(),
+        );
+
+        assert_eq!(replacements[0].text, "uses A,B;");
+        assert_eq!(replacements[1].text, "Foo(a, b);");
+        assert_eq!(replacements[1].reason.as_deref(), Some("rewrite"));
+        assert!(replacements.iter().all(|replacement| replacement.is_final));
+    }
+
     #[test]
     fn test_apply_text_changes_comma_only() {
         let options = TextChangeOptions {
@@ -1890,7 +2524,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a, b;c, d");
     }
 
@@ -1903,7 +2537,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a,b; c,d");
     }
 
@@ -1916,7 +2550,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a, b; c, d");
     }
 
@@ -1929,16 +2563,43 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_apply_text_changes_comma_no_change_leaves_spacing_untouched() {
+        let options = TextChangeOptions {
+            comma: SpaceOperation::NoChange,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "a , b ,c  ,  d";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_apply_text_changes_two_char_operator_no_change_leaves_spacing_untouched() {
+        let options = TextChangeOptions {
+            assign: SpaceOperation::NoChange,
+            lte: SpaceOperation::NoChange,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "a:=b;c  :=d;e<=  f";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "a:=b; c  :=d; e<=  f");
+    }
+
     #[test]
     fn test_apply_text_transformation_with_options() {
         let replacement = TextReplacement {
             start: 0,
             end: 8,
             text: "a,b;c".to_string(),
+            reason: None,
+            is_final: false,
         };
         let options = TextChangeOptions {
             comma: SpaceOperation::After,
@@ -1976,6 +2637,8 @@ mod tests {
             start: 0,
             end: 8,
             text: "a,b;c".to_string(),
+            reason: None,
+            is_final: false,
         };
         let options = TextChangeOptions {
             comma: SpaceOperation::After,
@@ -2002,7 +2665,7 @@ mod tests {
             ..Default::default()
         };
         let text = "Line 1   \nLine 2\t\t\nLine 3 ";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "Line 1\nLine 2\nLine 3");
     }
 
@@ -2016,6 +2679,35 @@ mod tests {
         assert_eq!(result.unwrap(), "end.\n");
     }
 
+    #[test]
+    fn test_trim_leading_blank_lines() {
+        let options = TextChangeOptions {
+            trim_leading_blank_lines: true,
+            ..Default::default()
+        };
+        let mut stats = TextTransformationStats::default();
+        let trim = |text: &str, stats: &mut TextTransformationStats| {
+            trim_leading_blank_lines_with_stats(text, &options, stats)
+        };
+
+        assert_eq!(
+            trim("\n  \r\n\t\nunit Foo;\n", &mut stats).unwrap(),
+            "unit Foo;\n"
+        );
+        assert_eq!(
+            trim("\u{feff}\n\n\nunit Foo;\n", &mut stats).unwrap(),
+            "\u{feff}unit Foo;\n"
+        );
+        assert!(trim("#!/usr/bin/env instantfpc\n\nprogram Foo;\n", &mut stats).is_none());
+        assert!(trim("unit Foo;\n", &mut stats).is_none());
+        assert!(trim("\n\n", &mut stats).is_none());
+
+        let disabled = TextChangeOptions::default();
+        assert!(
+            trim_leading_blank_lines_with_stats("\n\nunit Foo;", &disabled, &mut stats).is_none()
+        );
+    }
+
     #[test]
     fn test_apply_file_level_text_changes_collapses_extra_trailing_newlines() {
         let text = "unit Foo;\ninterface\nend.\n\n\n";
@@ -2039,6 +2731,45 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_normalize_line_endings_rewrites_mixed_line_endings_to_lf() {
+        let text = "unit Foo;\r\ninterface\nimplementation\rend.\r\n";
+
+        let result = normalize_line_endings_with_stats(
+            text,
+            &LineEnding::Lf,
+            &mut TextTransformationStats::default(),
+        );
+
+        assert_eq!(
+            result.unwrap(),
+            "unit Foo;\ninterface\nimplementation\nend.\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize_line_endings_rewrites_line_endings_to_crlf() {
+        let text = "unit Foo;\ninterface\r\nend.";
+
+        let result = normalize_line_endings_with_stats(
+            text,
+            &LineEnding::Crlf,
+            &mut TextTransformationStats::default(),
+        );
+
+        assert_eq!(result.unwrap(), "unit Foo;\r\ninterface\r\nend.");
+    }
+
+    #[test]
+    fn test_apply_file_level_text_changes_normalize_disabled_keeps_mixed_endings() {
+        let text = "unit Foo;\r\ninterface\nend.\n";
+        let options = TextChangeOptions::default();
+
+        let result = apply_file_level_text_changes(text, &options, &LineEnding::Lf);
+
+        assert!(result.is_none());
+    }
+
     #[test]
     fn test_apply_text_changes_combined_comma_and_trim() {
         let options = TextChangeOptions {
@@ -2048,7 +2779,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b,c   \nd,e,f\t\t";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a, b, c\nd, e, f");
     }
 
@@ -2061,7 +2792,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d   \ne,f;g,h\t\t";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a, b; c, d\ne, f; g, h");
     }
 
@@ -2071,6 +2802,8 @@ mod tests {
             start: 0,
             end: 11,
             text: "a,b;c   \nd,e;f\t\t".to_string(),
+            reason: None,
+            is_final: false,
         };
         let options = TextChangeOptions {
             comma: SpaceOperation::After,
@@ -2122,6 +2855,8 @@ mod tests {
             start: 0,
             end: 8,
             text: "Hello, World".to_string(),
+            reason: None,
+            is_final: false,
         }; // Already properly formatted
         let options = TextChangeOptions {
             comma: SpaceOperation::After,
@@ -2151,7 +2886,7 @@ mod tests {
         };
         // Test escaped single quotes in Delphi/Pascal strings
         let text = "s := 'It''s a test',x;y";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         // The comma/semicolon inside the string should not be spaced
         assert_eq!(result.unwrap(), "s := 'It''s a test', x; y");
     }
@@ -2166,13 +2901,34 @@ mod tests {
         };
         // Multiple escaped quotes and code after
         let text = "msg := 'Can''t say ''hello'', sorry',next";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "msg := 'Can''t say ''hello'', sorry', next"
         );
     }
 
+    #[test]
+    fn test_unterminated_string_offsets() {
+        let text = "S := 'it''s';\nT := 'open;\nU := 'ok';\n";
+        assert_eq!(
+            unterminated_string_offsets(text, Dialect::Delphi),
+            vec![text.find("'open").unwrap()]
+        );
+        assert!(unterminated_string_offsets("S := 'it''s' + '';\n", Dialect::Delphi).is_empty());
+
+        // Quotes inside comments are not strings
+        let commented = "// don't\n{ it's }\n(* isn't *)\nS := 'ok';\n";
+        assert!(unterminated_string_offsets(commented, Dialect::Delphi).is_empty());
+        // Delphi ends a brace comment at the first `}`, Free Pascal nests them
+        let nested = "{ { } it's }\n";
+        assert_eq!(
+            unterminated_string_offsets(nested, Dialect::Delphi),
+            vec![nested.find('\'').unwrap()]
+        );
+        assert!(unterminated_string_offsets(nested, Dialect::FreePascal).is_empty());
+    }
+
     #[test]
     fn test_unterminated_string_with_line_break() {
         let options = TextChangeOptions {
@@ -2183,7 +2939,7 @@ mod tests {
         };
         // Unterminated string that breaks at newline
         let text = "s := 'unterminated\ncode,after;break";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         // After line break, spacing should be applied
         assert_eq!(result.unwrap(), "s := 'unterminated\ncode, after; break");
     }
@@ -2198,7 +2954,7 @@ mod tests {
         };
         // Test multiline brace comments
         let text = "{ multi\nline,comment;here }\ncode,after";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "{ multi\nline,comment;here }\ncode, after");
     }
 
@@ -2212,7 +2968,7 @@ mod tests {
         };
         // Test multiline (* *) comments
         let text = "(* multi\nline,comment;here *)\ncode,after";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "(* multi\nline,comment;here *)\ncode, after"
@@ -2229,7 +2985,7 @@ mod tests {
         };
         // Test trimming with both LF and CRLF
         let text = "line1   \r\nline2\t\t\nline3   ";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "line1\r\nline2\nline3");
     }
 
@@ -2244,7 +3000,7 @@ mod tests {
         };
         let text = "'a,b;c',x;y";
         // Only commas/semicolons outside the quotes should be spaced.
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "'a,b;c', x; y");
     }
 
@@ -2258,7 +3014,7 @@ mod tests {
             ..Default::default()
         };
         let text = "{a,b;c},x;y";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "{a,b;c}, x; y");
     }
 
@@ -2272,7 +3028,7 @@ mod tests {
             ..Default::default()
         };
         let text = "(*a,b;c*),x;y";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "(*a,b;c*), x; y");
     }
 
@@ -2285,7 +3041,7 @@ mod tests {
             ..Default::default()
         };
         let text = "// a,b;c\nx,y;z";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         // Only second line is transformed.
         assert_eq!(result.unwrap(), "// a,b;c\nx, y; z");
     }
@@ -2294,7 +3050,7 @@ mod tests {
     fn test_default_comment_spacing_options_apply() {
         let options = TextChangeOptions::default();
         let text = "{NoSpace}\n(*NoSpace*)\n//NoSpace\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "{ NoSpace }\n(* NoSpace *)\n// NoSpace\n");
     }
 
@@ -2308,7 +3064,7 @@ mod tests {
             ..Default::default()
         };
         let text = "//abc\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "// abc\n");
     }
 
@@ -2322,7 +3078,7 @@ mod tests {
             ..Default::default()
         };
         let text = "//////////abc\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "////////// abc\n");
     }
 
@@ -2336,7 +3092,7 @@ mod tests {
             ..Default::default()
         };
         let text = "/////\t\t  abc\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
@@ -2350,7 +3106,7 @@ mod tests {
             ..Default::default()
         };
         let text = "//       comment\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
@@ -2364,10 +3120,22 @@ mod tests {
             ..Default::default()
         };
         let text = "////////\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_space_after_line_comment_slashes_leaves_banner_lines() {
+        let options = TextChangeOptions {
+            space_after_line_comment_slashes: true,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "//// banner ////\n//x\nFoo; //y\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "//// banner ////\n// x\nFoo; // y\n");
+    }
+
     #[test]
     fn test_mixed_code_and_comments_and_strings() {
         let options = TextChangeOptions {
@@ -2377,7 +3145,7 @@ mod tests {
             ..Default::default()
         };
         let text = "val:='a,b'; // c,d;e\n{ x,y;z } foo,bar;baz (* p,q;r *) qux,quux";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "val := 'a,b'; // c,d;e\n{ x,y;z } foo, bar; baz (* p,q;r *) qux, quux"
@@ -2394,7 +3162,7 @@ mod tests {
             ..Default::default()
         };
         let text = "{NoSpace} {  TooManySpaces   } {}";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "{ NoSpace } { TooManySpaces } { }");
     }
 
@@ -2408,10 +3176,36 @@ mod tests {
             ..Default::default()
         };
         let text = "{First line\nSecond line}\n{\nSecond line\n}\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_directives_are_preserved_by_default() {
+        let options = TextChangeOptions {
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "{$DEFINE A,B}\n{$I  'my file.inc' }\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_directives_are_normalized_when_not_preserved() {
+        let options = TextChangeOptions {
+            preserve_directives: false,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "{$DEFINE A,B}\n{$I  'my  file.inc' }\n{$IFDEF DEBUG\n}{ Plain,comment }\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "{$DEFINE A, B}\n{$I 'my  file.inc'}\n{$IFDEF DEBUG\n}{ Plain,comment }\n"
+        );
+    }
+
     #[test]
     fn test_space_inside_brace_comments_skips_directives() {
         let options = TextChangeOptions {
@@ -2422,7 +3216,7 @@ mod tests {
             ..Default::default()
         };
         let text = "{$IFDEF DEBUG}\n{  $IFDEF DEBUG  }\n{NormalComment}\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "{$IFDEF DEBUG}\n{  $IFDEF DEBUG  }\n{ NormalComment }\n"
@@ -2439,7 +3233,7 @@ mod tests {
             ..Default::default()
         };
         let text = "(*NoSpace*) (*  TooManySpaces   *) (**)";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "(* NoSpace *) (* TooManySpaces *) (* *)");
     }
 
@@ -2453,7 +3247,7 @@ mod tests {
             ..Default::default()
         };
         let text = "(*First line\nSecond line*)\n(*\nSecond line\n*)\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
@@ -2467,7 +3261,7 @@ mod tests {
             ..Default::default()
         };
         let text = "(*$IFDEF DEBUG*)\n(*  $IFDEF DEBUG  *)\n(*NormalComment*)\n";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "(*$IFDEF DEBUG*)\n(*  $IFDEF DEBUG  *)\n(* NormalComment *)\n"
@@ -2484,7 +3278,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b,c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a ,b ,c");
     }
 
@@ -2497,7 +3291,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a;b;c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a ;b ;c");
     }
 
@@ -2510,7 +3304,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b,c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a , b , c");
     }
 
@@ -2523,7 +3317,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a;b;c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a ; b ; c");
     }
 
@@ -2537,7 +3331,7 @@ mod tests {
         };
         // Already has spaces before punctuation - should not add more
         let text = "a ,b ;c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none()); // No change because space already exists
     }
 
@@ -2551,7 +3345,7 @@ mod tests {
         };
         // Already has spaces after punctuation - should not add more
         let text = "a, b; c";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none()); // No change because space already exists
     }
 
@@ -2565,7 +3359,7 @@ mod tests {
         };
         // Comma/semicolon at the beginning should not add space before
         let text = ",a;b";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), ",a ;b"); // No space before first comma
     }
 
@@ -2578,7 +3372,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a,b;c,d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a ,b ; c ,d");
     }
 
@@ -2595,7 +3389,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a:=5+b+=c-=d*=e/=f";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "a := 5 + b += c -= d *= e /= f");
     }
 
@@ -2612,7 +3406,7 @@ mod tests {
             ..Default::default()
         };
         let text = "if a<b=c<>d>e<=f>=g then";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "if a < b = c <> d > e <= f >= g then");
     }
 
@@ -2627,7 +3421,7 @@ mod tests {
             ..Default::default()
         };
         let text = "result:=a+b-c*d/e";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "result := a + b - c * d / e");
     }
 
@@ -2639,7 +3433,7 @@ mod tests {
             ..Default::default()
         };
         let text = "var x:Integer;y:String;z:Boolean";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "var x: Integer; y: String; z: Boolean");
     }
 
@@ -2655,7 +3449,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a+b-c*d/e=f";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none()); // Should remain unchanged for these operators
     }
 
@@ -2669,7 +3463,7 @@ mod tests {
             ..Default::default()
         };
         let text = "msg:='a:=b+c'; // Comment with := and + and =\nresult:=x=y+z";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         // Operators inside string and comments should not be spaced
         assert_eq!(
             result.unwrap(),
@@ -2687,7 +3481,7 @@ mod tests {
             ..Default::default()
         };
         let text = "a++b--c==d";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         // Consecutive same operators should not have space between them (correct behavior)
         assert_eq!(result.unwrap(), "a ++ b -- c == d");
     }
@@ -2703,7 +3497,7 @@ mod tests {
         };
         // Time format - should not have spaces when numeric exception is enabled
         let text = "time := 12:34:56;";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert!(result.is_none());
     }
 
@@ -2717,7 +3511,7 @@ mod tests {
         };
         // When exception is disabled, spaces should be added around all colons
         let text = "time := 12:34:56;";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "time := 12 : 34 : 56;");
     }
 
@@ -2731,7 +3525,7 @@ mod tests {
         };
         // Mix of numeric (no space) and non-numeric (with space) colons
         let text = "var x: Integer; time := 12:34;";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "var x : Integer; time := 12:34;");
     }
 
@@ -2746,7 +3540,7 @@ mod tests {
         };
         // Ensure ':=' assignment is handled separately from single ':'
         let text = "time:=12:34; x:Integer;";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "time := 12:34; x : Integer;");
     }
 
@@ -2760,7 +3554,7 @@ mod tests {
         };
         // Test edge cases: colon at start, end, and with non-digits
         let text = ":start x:y 3:z end: 12:34";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), ": start x : y 3 : z end : 12:34");
     }
 
@@ -2774,10 +3568,296 @@ mod tests {
         };
         // Test with only 'After' spacing - numeric exception should still work
         let text = "x:Integer; time := 12:34;";
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "x: Integer; time := 12:34;");
     }
 
+    #[test]
+    fn test_paren_inner_remove_with_comma_rule() {
+        let options = TextChangeOptions {
+            comma: SpaceOperation::After,
+            paren_inner: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "foo( a , b );";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "foo(a, b);");
+    }
+
+    #[test]
+    fn test_paren_inner_before_and_after_pads_contents() {
+        let options = TextChangeOptions {
+            paren_inner: SpaceOperation::BeforeAndAfter,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "foo(a, b); bar();";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "foo( a, b ); bar();");
+    }
+
+    #[test]
+    fn test_paren_inner_skips_comments_strings_and_line_starts() {
+        let options = TextChangeOptions {
+            paren_inner: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "(* keep *) s := '( x )';\nfoo(\n  a\n  );";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_keyword_paren_and_call_paren() {
+        let options = TextChangeOptions {
+            call_paren: SpaceOperation::Remove,
+            keyword_paren: SpaceOperation::Before,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "if(x) then Foo (x);\nwhile not(Done) do Obj.Run (1);";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "if (x) then Foo(x);\nwhile not (Done) do Obj.Run(1);"
+        );
+    }
+
+    #[test]
+    fn test_call_paren_ignores_non_identifier_tokens() {
+        let options = TextChangeOptions {
+            call_paren: SpaceOperation::Remove,
+            keyword_paren: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        // Operators, line starts, and comments keep their spacing before '('
+        let text = "x := (a + b) * (c);\nFoo\n  (x); { Bar (x) }";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_word_operator_before_and_after() {
+        let options = TextChangeOptions {
+            word_operator: SpaceOperation::BeforeAndAfter,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := a div b + (c)mod(d) + e  xor   f;\nif not(Done) and(Ready) then";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "x := a div b + (c) mod (d) + e xor f;\nif not (Done) and (Ready) then"
+        );
+    }
+
+    #[test]
+    fn test_word_operator_ignores_identifiers_containing_operators() {
+        let options = TextChangeOptions {
+            word_operator: SpaceOperation::BeforeAndAfter,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "xand := band + android + Obj.Mod(1) + &div; // a div(b)";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_word_operator_remove_keeps_token_separation() {
+        let options = TextChangeOptions {
+            word_operator: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := (a) div (b) + c mod d;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "x := (a)div(b) + c mod d;");
+    }
+
+    #[test]
+    fn test_ampersand_escaped_keywords_are_preserved_verbatim() {
+        let options = TextChangeOptions {
+            word_operator: SpaceOperation::BeforeAndAfter,
+            keyword_paren: SpaceOperation::Before,
+            enforce_word_casing: vec!["Begin".to_string(), "Mod".to_string()],
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "&begin := &mod(1) + Obj.&end; s := 'a & b'";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_amp_spacing_before_escape() {
+        let options = TextChangeOptions {
+            amp: SpaceOperation::BeforeAndAfter,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := Foo(&begin)+  &end+Obj.&type; s := 'a&b'";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "x := Foo(&begin) + &end + Obj.&type; s := 'a&b'"
+        );
+
+        let options = TextChangeOptions {
+            amp: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := Foo( &begin) + a and &end;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "x := Foo(&begin) + a and &end;");
+    }
+
+    #[test]
+    fn test_max_consecutive_blank_lines_collapses_three_to_one() {
+        let options = TextChangeOptions {
+            max_consecutive_blank_lines: 1,
+            ..Default::default()
+        };
+        let text = "a := 1;\n\n  \n\nb := 2;\r\n\r\n\r\n\r\nc := 3;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "a := 1;\n\nb := 2;\r\n\r\nc := 3;");
+    }
+
+    #[test]
+    fn test_max_consecutive_blank_lines_keeps_blank_lines_in_comments() {
+        let options = TextChangeOptions {
+            max_consecutive_blank_lines: 1,
+            ..Default::default()
+        };
+        let text = "{ first\n\n\n\nlast }\n(* a\n\n\nb *)\nx := 1;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_align_trailing_comments_pads_run_to_widest_code() {
+        let options = TextChangeOptions {
+            align_trailing_comments: true,
+            ..Default::default()
+        };
+        let text = "x := 1; // count\nLongerName := 'a//b'; // name\ny := 22;   // size\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "x := 1;               // count\nLongerName := 'a//b'; // name\ny := 22;              // size\n"
+        );
+    }
+
+    #[test]
+    fn test_align_trailing_comments_stops_at_blank_lines_and_blocks() {
+        let options = TextChangeOptions {
+            align_trailing_comments: true,
+            ..Default::default()
+        };
+        let text = "a := 1; // a\n\nbb := 2; // b\nbegin // start\n  ccc := 3; // c\n  d := 4; // d\nend;\n// whole line\ne := 5; // e\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "a := 1; // a\n\nbb := 2; // b\nbegin    // start\n  ccc := 3; // c\n  d := 4;   // d\nend;\n// whole line\ne := 5; // e\n"
+        );
+    }
+
+    #[test]
+    fn test_hex_case_upper_normalizes_hex_digits() {
+        let options = TextChangeOptions {
+            hex_case: HexCase::Upper,
+            ..Default::default()
+        };
+        let text = "x := $ab + #$0d;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "x := $AB + #$0D;");
+    }
+
+    #[test]
+    fn test_hex_case_leaves_strings_directives_and_bare_dollar() {
+        let options = TextChangeOptions {
+            hex_case: HexCase::Lower,
+            ..Default::default()
+        };
+        let text = "{$IFDEF DEBUG}\ns := '$AB'; x := $FF; y := $;\n{$ENDIF}";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(
+            result.unwrap(),
+            "{$IFDEF DEBUG}\ns := '$AB'; x := $ff; y := $;\n{$ENDIF}"
+        );
+    }
+
+    #[test]
+    fn test_max_consecutive_blank_lines_zero_is_unlimited() {
+        let options = TextChangeOptions::default();
+        let text = "a := 1;\n\n\n\nb := 2;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_bracket_inner_remove() {
+        let options = TextChangeOptions {
+            bracket_inner: SpaceOperation::Remove,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := arr[ i ] + set[ ];";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "x := arr[i] + set[];");
+    }
+
+    #[test]
+    fn test_label_colon_numeric_label_at_line_start() {
+        let options = TextChangeOptions {
+            colon: SpaceOperation::BeforeAndAfter,
+            label_colon: SpaceOperation::After,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        // Numeric label at line start uses label_colon, declaration mid-line uses colon
+        let text = "var x:Integer;\n  10:WriteLn;\n";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "var x : Integer;\n  10: WriteLn;\n");
+    }
+
+    #[test]
+    fn test_label_colon_no_change_keeps_label_spacing() {
+        let options = TextChangeOptions {
+            colon: SpaceOperation::After,
+            label_colon: SpaceOperation::NoChange,
+            colon_numeric_exception: true,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "10 :WriteLn;\nx:Integer;";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "10 :WriteLn;\nx: Integer;");
+    }
+
+    #[test]
+    fn test_label_colon_identifier_label_from_context() {
+        let source = "program Test;\nlabel Done;\nbegin\n  goto Done;\n  Done :WriteLn;\nend.";
+        let options = TextChangeOptions {
+            label_colon: SpaceOperation::NoChange,
+            ..Default::default()
+        };
+        let (_, context) = crate::parser::parse_with_spacing_context(source).unwrap();
+        let result = apply_text_transformation_with_context(
+            0,
+            source.len(),
+            source,
+            &options,
+            Dialect::Delphi,
+            Some(&context),
+        );
+        assert!(result.is_none());
+    }
+
     #[test]
     fn test_generic_angle_brackets_no_spacing() {
         let source = "unit Test;\ninterface\nconst\n  AStructures: TEnumerable < TStructure >;\nimplementation\nend.";
@@ -2788,6 +3868,7 @@ mod tests {
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2799,13 +3880,17 @@ mod tests {
     #[test]
     fn test_unary_minus_no_spacing() {
         let source = "unit Test;\ninterface\nconst\n  A = - 1;\nimplementation\nbegin\n  A := - 1;\n  A := - Foo;\n  A := -Foo(1);\nend.";
-        let options = TextChangeOptions::default();
+        let options = TextChangeOptions {
+            unary_sign: SpaceOperation::Remove,
+            ..Default::default()
+        };
         let (_, context) = crate::parser::parse_with_spacing_context(source).unwrap();
         let result = apply_text_transformation_with_context(
             0,
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2814,6 +3899,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unary_sign_option_controls_space_after() {
+        let source = "unit Test;\ninterface\nimplementation\nbegin\n  result := - x;\n  c := (-y) - b;\nend.";
+        let (_, context) = crate::parser::parse_with_spacing_context(source).unwrap();
+        let transform = |unary_sign| {
+            let options = TextChangeOptions {
+                unary_sign,
+                ..Default::default()
+            };
+            apply_text_transformation_with_context(
+                0,
+                source.len(),
+                source,
+                &options,
+                Dialect::Delphi,
+                Some(&context),
+            )
+            .unwrap()
+            .text
+        };
+        assert_eq!(
+            transform(SpaceOperation::Remove),
+            "unit Test;\ninterface\nimplementation\nbegin\n  result := -x;\n  c := (-y) - b;\nend."
+        );
+        assert_eq!(
+            transform(SpaceOperation::After),
+            "unit Test;\ninterface\nimplementation\nbegin\n  result := - x;\n  c := (- y) - b;\nend."
+        );
+    }
+
     #[test]
     fn test_generic_angle_brackets_nested() {
         let source = "unit Test;\ninterface\ntype\n  TMap = TDictionary < String, TList < Integer > >;\n  TNested = TOuter < TInner < Integer > >;\nimplementation\nend.";
@@ -2824,6 +3939,7 @@ mod tests {
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2842,6 +3958,7 @@ mod tests {
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2853,13 +3970,17 @@ mod tests {
     #[test]
     fn test_unary_plus_spacing() {
         let source = "unit Test;\ninterface\nconst\n  A = + 1;\nimplementation\nbegin\n  A := + Foo;\n  A := +Foo(1);\n  A := + (1 + 2);\nend.";
-        let options = TextChangeOptions::default();
+        let options = TextChangeOptions {
+            unary_sign: SpaceOperation::Remove,
+            ..Default::default()
+        };
         let (_, context) = crate::parser::parse_with_spacing_context(source).unwrap();
         let result = apply_text_transformation_with_context(
             0,
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2878,6 +3999,7 @@ mod tests {
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         );
         assert_eq!(
@@ -2886,6 +4008,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_exponent_sign_no_spacing_without_context() {
+        let options = TextChangeOptions {
+            add: SpaceOperation::BeforeAndAfter,
+            sub: SpaceOperation::BeforeAndAfter,
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+        let text = "x := 1e-5 + 2.5E+10 - a-e - b1e+c";
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(result.unwrap(), "x := 1e-5 + 2.5E+10 - a - e - b1e + c");
+    }
+
     #[test]
     fn test_lt_gt_spacing_requires_expr_binary_positions_with_context() {
         let text = "TArray<Integer>; if A<10 then x:=1; if B>5 then y:=2;";
@@ -2915,8 +4050,14 @@ mod tests {
             .expr_binary_gt_positions
             .insert(text.find("B>5").unwrap() + 1);
 
-        let result =
-            apply_text_transformation_with_context(0, text.len(), text, &options, Some(&context));
+        let result = apply_text_transformation_with_context(
+            0,
+            text.len(),
+            text,
+            &options,
+            Dialect::Delphi,
+            Some(&context),
+        );
         assert_eq!(
             result.unwrap().text,
             "TArray<Integer>; if A < 10 then x:=1; if B > 5 then y:=2;"
@@ -2953,6 +4094,7 @@ end."#;
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         )
         .map(|r| r.text)
@@ -3004,6 +4146,7 @@ end."#;
             source.len(),
             source,
             &options,
+            Dialect::Delphi,
             Some(&context),
         )
         .map(|r| r.text)
@@ -3016,13 +4159,84 @@ end."#;
     fn test_preserve_indentation_for_multiline_expression_operators() {
         let text = "begin\n  X :=\n      A\n    -  B\n    +  C;\nend.";
         let options = TextChangeOptions::default();
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "begin\n  X :=\n      A\n    - B\n    + C;\nend."
         );
     }
 
+    #[test]
+    fn test_nested_paren_star_comments_follow_dialect() {
+        let text = "x := a*b; (* a (* b *) c*d *)\n";
+        let options = TextChangeOptions {
+            mul: SpaceOperation::BeforeAndAfter,
+            ..Default::default()
+        };
+
+        // Delphi ends the comment at the first `*)`, so `c*d *)` is spaced as code
+        let delphi = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(delphi.unwrap(), "x := a * b; (* a (* b *) c * d * )\n");
+
+        // Free Pascal keeps the whole nested comment as written
+        let free_pascal = apply_text_changes(text, &options, Dialect::FreePascal, 0, None, None);
+        assert_eq!(free_pascal.unwrap(), "x := a * b; (* a (* b *) c*d *)\n");
+    }
+
+    #[test]
+    fn test_nested_brace_comments_follow_dialect() {
+        let text = "x := a*b; { a { b } c*d }\n";
+        let options = TextChangeOptions {
+            mul: SpaceOperation::BeforeAndAfter,
+            ..Default::default()
+        };
+
+        // Delphi ends the comment at the first `}`, so `c*d }` is spaced as code
+        let delphi = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+        assert_eq!(delphi.unwrap(), "x := a * b; { a { b } c * d }\n");
+
+        // Free Pascal keeps the whole nested comment as written
+        let free_pascal = apply_text_changes(text, &options, Dialect::FreePascal, 0, None, None);
+        assert_eq!(free_pascal.unwrap(), "x := a * b; { a { b } c*d }\n");
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace_before_final_line_ending() {
+        let options = TextChangeOptions {
+            ensure_single_trailing_newline: false,
+            ..Default::default()
+        };
+        for (text, expected) in [
+            ("text  \r", "text\r"),
+            ("text  \r\n", "text\r\n"),
+            ("text \t", "text"),
+            ("a  \rb  \r", "a\rb\r"),
+            ("x := 1; // c  \r", "x := 1; // c\r"),
+            ("{ c  \r  }  \r", "{ c\r  }\r"),
+        ] {
+            let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+            assert_eq!(result.as_deref().unwrap_or(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_wrapped_assignment_keeps_continuation_indentation() {
+        let text = "begin\n  x\n    :=  5;\r\n  if y\n\t<=z then\nend.";
+        for trim_trailing_whitespace in [true, false] {
+            let options = TextChangeOptions {
+                assign: SpaceOperation::BeforeAndAfter,
+                lte: SpaceOperation::BeforeAndAfter,
+                trim_trailing_whitespace,
+                ..Default::default()
+            };
+            let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
+            assert_eq!(
+                result.unwrap(),
+                "begin\n  x\n    := 5;\r\n  if y\n\t<= z then\nend."
+            );
+        }
+    }
+
     #[test]
     fn test_enforce_word_casing_case_insensitive_for_identifiers() {
         let text = "HTTPCLIENT := httpclient + HttpClient;";
@@ -3032,7 +4246,7 @@ end."#;
             ..Default::default()
         };
 
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "HTTPClient := HTTPClient + HTTPClient;");
     }
 
@@ -3045,7 +4259,7 @@ end."#;
             ..Default::default()
         };
 
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(result.unwrap(), "HTTPClientHelper := HTTPClient;");
     }
 
@@ -3058,7 +4272,7 @@ end."#;
             ..Default::default()
         };
 
-        let result = apply_text_changes(text, &options, 0, None, None);
+        let result = apply_text_changes(text, &options, Dialect::Delphi, 0, None, None);
         assert_eq!(
             result.unwrap(),
             "msg := 'httpclient'; // httpclient\nHTTPClient := 1;"
@@ -3087,6 +4301,7 @@ end."#;
             text.len(),
             text,
             &options,
+            Dialect::Delphi,
             None,
             &mut stats,
         )
@@ -63,6 +63,7 @@ pub fn transform_unit_program_section(
         semicolon_end_byte,
         replacement_text,
     )
+    .map(|replacement| replacement.with_reason("unit header on one line"))
 }
 
 #[cfg(test)]
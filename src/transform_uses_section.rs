@@ -1,4 +1,5 @@
-use crate::options::Options;
+use crate::dfixxer_error::DFixxerError;
+use crate::options::{Options, UsesCommentPolicy, UsesSortKey, UsesSortPreset};
 use crate::parser::{CodeSection, Kind};
 use crate::replacements::TextReplacement;
 use crate::transformer_utility::{
@@ -9,10 +10,22 @@ use icu_collator::options::{CollatorOptions, Strength};
 use log::warn;
 use std::cmp::Ordering;
 
-// Formats the replacement text for a uses section given the modules and options.
-fn format_uses_replacement(modules: &[String], options: &Options) -> String {
+// The section keyword, followed by an empty line before the first module when
+// `blank_line_after_uses` is set.
+fn uses_keyword(keyword: &str, modules: &[String], line_ending: &str, options: &Options) -> String {
+    if options.uses_section.blank_line_after_uses && !modules.is_empty() {
+        format!("{}{}", keyword, line_ending)
+    } else {
+        keyword.to_string()
+    }
+}
+
+// Formats the replacement text for a uses (or requires/contains) section given the keyword,
+// modules, and options.
+fn format_uses_replacement(keyword: &str, modules: &[String], options: &Options) -> String {
     use crate::options::UsesSectionStyle;
     let line_ending = options.line_ending.to_string();
+    let keyword = uses_keyword(keyword, modules, &line_ending, options);
     match options.uses_section.uses_section_style {
         UsesSectionStyle::CommaAtTheBeginning => {
             let mut lines = Vec::new();
@@ -24,21 +37,170 @@ fn format_uses_replacement(modules: &[String], options: &Options) -> String {
                     lines.push(format!("{}, {}", options.indentation, module));
                 }
             }
-            lines.push(format!("{};", options.indentation));
+            match lines.last_mut() {
+                Some(last) if !options.uses_section.semicolon_on_own_line => last.push(';'),
+                _ => lines.push(format!("{};", options.indentation)),
+            }
             let joined_lines = lines.join(&line_ending);
-            format!("uses{}{}", line_ending, joined_lines)
+            format!("{}{}{}", keyword, line_ending, joined_lines)
         }
         UsesSectionStyle::CommaAtTheEnd => {
             let separator = format!(",{}{}", line_ending, options.indentation);
             let modules_text = modules.join(&separator);
             format!(
-                "uses{}{}{};",
-                line_ending, options.indentation, modules_text
+                "{}{}{}{};",
+                keyword, line_ending, options.indentation, modules_text
             )
         }
     }
 }
 
+// Formats a uses section that keeps the original number of modules on each line.
+fn format_uses_replacement_keeping_lines(
+    keyword: &str,
+    modules: &[String],
+    line_sizes: &[usize],
+    first_on_keyword_line: bool,
+    options: &Options,
+) -> String {
+    use crate::options::UsesSectionStyle;
+    let line_ending = options.line_ending.to_string();
+    let mut remaining = modules.iter().map(String::as_str);
+    let lines: Vec<String> = line_sizes
+        .iter()
+        .map(|size| {
+            remaining
+                .by_ref()
+                .take(*size)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+
+    // Modules that share the keyword line leave no room for a blank line after it
+    let mut text = if first_on_keyword_line {
+        keyword.to_string()
+    } else {
+        uses_keyword(keyword, modules, &line_ending, options)
+    };
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 && first_on_keyword_line {
+            text.push(' ');
+        } else {
+            text.push_str(&line_ending);
+            text.push_str(&options.indentation);
+            if options.uses_section.uses_section_style == UsesSectionStyle::CommaAtTheBeginning {
+                text.push_str(if index == 0 { "  " } else { ", " });
+            }
+        }
+        text.push_str(line);
+        if options.uses_section.uses_section_style == UsesSectionStyle::CommaAtTheEnd {
+            text.push(if index + 1 == lines.len() { ';' } else { ',' });
+        }
+    }
+    if options.uses_section.uses_section_style == UsesSectionStyle::CommaAtTheBeginning {
+        if options.uses_section.semicolon_on_own_line || lines.is_empty() {
+            text.push_str(&line_ending);
+            text.push_str(&options.indentation);
+        }
+        text.push(';');
+    }
+    text
+}
+
+/// Append `comments` after `line`, starting a new line after a `//` comment
+fn push_line_with_comments(
+    lines: &mut Vec<String>,
+    mut line: String,
+    comments: &[String],
+    indentation: &str,
+) {
+    let mut after_line_comment = false;
+    for comment in comments {
+        if after_line_comment {
+            lines.push(line);
+            line = format!("{}{}", indentation, comment);
+        } else {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        after_line_comment = comment.starts_with("//");
+    }
+    lines.push(line);
+}
+
+// Formats a section with one module per line, keeping each module's comments with it and
+// `closing` comments on their own lines after the last module.
+fn format_uses_replacement_with_comments(
+    keyword: &str,
+    entries: &[UsesEntry],
+    closing: &[String],
+    options: &Options,
+) -> String {
+    use crate::options::UsesSectionStyle;
+    let line_ending = options.line_ending.to_string();
+    let indentation = &options.indentation;
+    let comma_at_the_beginning =
+        options.uses_section.uses_section_style == UsesSectionStyle::CommaAtTheBeginning;
+    let semicolon_on_own_line = entries.is_empty()
+        || (comma_at_the_beginning && options.uses_section.semicolon_on_own_line);
+
+    let mut lines = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        for comment in &entry.leading {
+            lines.push(format!("{}{}", indentation, comment));
+        }
+        let is_last = index + 1 == entries.len();
+        let prefix = match (comma_at_the_beginning, index) {
+            (false, _) => "",
+            (true, 0) => "  ",
+            (true, _) => ", ",
+        };
+        let suffix = if is_last && !semicolon_on_own_line {
+            ";"
+        } else if !is_last && !comma_at_the_beginning {
+            ","
+        } else {
+            ""
+        };
+        let line = format!("{}{}{}{}", indentation, prefix, entry.module, suffix);
+        push_line_with_comments(&mut lines, line, &entry.trailing, indentation);
+    }
+    for comment in closing {
+        lines.push(format!("{}{}", indentation, comment));
+    }
+    if semicolon_on_own_line {
+        lines.push(format!("{};", indentation));
+    }
+
+    let modules: Vec<String> = entries.iter().map(|entry| entry.module.clone()).collect();
+    let keyword = uses_keyword(keyword, &modules, &line_ending, options);
+    format!("{}{}{}", keyword, line_ending, lines.join(&line_ending))
+}
+
+/// Number of modules on each source line, and whether the first line is the `uses` line
+fn module_line_sizes(code_section: &CodeSection) -> (Vec<usize>, bool) {
+    let mut line_sizes = Vec::new();
+    let mut last_row = None;
+    let mut first_row = None;
+    for module in code_section
+        .siblings
+        .iter()
+        .filter(|sibling| sibling.kind == Kind::Module)
+    {
+        first_row.get_or_insert(module.start_row);
+        match line_sizes.last_mut() {
+            Some(size) if last_row == Some(module.start_row) => *size += 1,
+            _ => line_sizes.push(1),
+        }
+        last_row = Some(module.start_row);
+    }
+    (
+        line_sizes,
+        first_row == Some(code_section.keyword.start_row),
+    )
+}
+
 fn build_base_collator() -> Option<CollatorBorrowed<'static>> {
     let mut options = CollatorOptions::default();
     options.strength = Some(Strength::Primary);
@@ -57,15 +219,119 @@ fn fallback_module_compare(
     a.to_lowercase().cmp(&b.to_lowercase())
 }
 
-fn sort_modules(modules: &[String], options: &Options) -> Vec<String> {
-    let mut modules = modules.to_owned();
+/// Compare two sort keys with the configured preset
+fn preset_module_compare(
+    a: &str,
+    b: &str,
+    preset: UsesSortPreset,
+    collator: Option<&CollatorBorrowed<'static>>,
+) -> Ordering {
+    match preset {
+        UsesSortPreset::Alphabetical => fallback_module_compare(a, b, collator),
+        UsesSortPreset::AlphabeticalCaseSensitive => a.cmp(b),
+        UsesSortPreset::LengthThenAlpha => a
+            .chars()
+            .count()
+            .cmp(&b.chars().count())
+            .then_with(|| fallback_module_compare(a, b, collator)),
+        UsesSortPreset::Rtl => a
+            .to_lowercase()
+            .chars()
+            .rev()
+            .cmp(b.to_lowercase().chars().rev()),
+    }
+}
+
+/// Path of a `.dpr` project reference such as `Foo in 'src\\Foo.pas'`
+fn in_clause_path(module: &str) -> Option<&str> {
+    let mut words = module.split_whitespace();
+    let _name = words.next()?;
+    if !words.next()?.eq_ignore_ascii_case("in") {
+        return None;
+    }
+    let start = module.find('\'')? + 1;
+    let end = start + module[start..].find('\'')?;
+    Some(&module[start..end])
+}
+
+/// True for units `project_units_last` moves to the end: those starting with a configured
+/// prefix (ignoring case), or without a dot when no prefixes are configured
+fn is_project_unit(module: &str, prefixes: &[String]) -> bool {
+    let name = module.split_whitespace().next().unwrap_or(module);
+    if prefixes.is_empty() {
+        return !name.contains('.');
+    }
+    prefixes.iter().any(|prefix| {
+        name.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    })
+}
+
+/// Text that orders a module once override prefixes have been applied
+fn module_sort_key(module: &str, sort_key: &UsesSortKey) -> String {
+    match sort_key {
+        UsesSortKey::InPath => in_clause_path(module).unwrap_or(module).to_string(),
+        UsesSortKey::UnitName => module.to_string(),
+    }
+}
+
+/// A module together with the comments `uses_comment_policy = "Preserve"` keeps with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UsesEntry {
+    module: String,
+    /// Comments on their own lines before the module
+    leading: Vec<String>,
+    /// Comments after the module on the same line, or before the closing `;`
+    trailing: Vec<String>,
+}
+
+impl UsesEntry {
+    fn new(module: String) -> Self {
+        UsesEntry {
+            module,
+            leading: Vec::new(),
+            trailing: Vec::new(),
+        }
+    }
+}
+
+fn sort_entries(mut entries: Vec<UsesEntry>, options: &Options) -> Vec<UsesEntry> {
+    // Apply module_renames first so a renamed unit can still pick up its namespace below
+    let mut renamed = false;
+    for (old_name, new_name) in &options.uses_section.module_renames {
+        for entry in entries.iter_mut() {
+            if entry.module.eq_ignore_ascii_case(old_name) {
+                entry.module = new_name.clone();
+                renamed = true;
+            }
+        }
+    }
+    if renamed {
+        // Two units renamed to the same target (or a rename onto a listed unit) keep one entry,
+        // which also keeps the comments of the dropped one
+        let mut merged: Vec<UsesEntry> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let lowercase = entry.module.to_lowercase();
+            match merged
+                .iter_mut()
+                .find(|kept| kept.module.to_lowercase() == lowercase)
+            {
+                Some(kept) => {
+                    kept.leading.extend(entry.leading);
+                    kept.trailing.extend(entry.trailing);
+                }
+                None => merged.push(entry),
+            }
+        }
+        entries = merged;
+    }
 
     // Apply module_names_to_update: e.g. "System:Classes" means replace "Classes" with "System.Classes"
     for mapping in &options.uses_section.module_names_to_update {
         if let Some((prefix, name)) = mapping.split_once(':') {
-            for module in modules.iter_mut() {
-                if module.eq_ignore_ascii_case(name) {
-                    *module = format!("{}.{}", prefix, name);
+            for entry in entries.iter_mut() {
+                if entry.module.eq_ignore_ascii_case(name) {
+                    entry.module = format!("{}.{}", prefix, name);
                 }
             }
         }
@@ -82,8 +348,31 @@ fn sort_modules(modules: &[String], options: &Options) -> Vec<String> {
         .map(|ns| ns.to_lowercase())
         .collect();
     let collator = build_base_collator();
+    // Pinned units come first in their configured order; everything else ranks after them
+    let pin_rank = |module: &str| {
+        options
+            .uses_section
+            .pinned_first_units
+            .iter()
+            .position(|pinned| pinned.eq_ignore_ascii_case(module.trim()))
+            .unwrap_or(usize::MAX)
+    };
+
+    entries.sort_by(|a, b| {
+        let (a, b) = (&a.module, &b.module);
+        let pinned = pin_rank(a).cmp(&pin_rank(b));
+        if pinned != Ordering::Equal {
+            return pinned;
+        }
+        // Project units form a final group, sorted by the same rules as the rest
+        if options.uses_section.project_units_last {
+            let prefixes = &options.uses_section.project_unit_prefixes;
+            let group = is_project_unit(a, prefixes).cmp(&is_project_unit(b, prefixes));
+            if group != Ordering::Equal {
+                return group;
+            }
+        }
 
-    modules.sort_by(|a, b| {
         let normalized_a = a.trim().to_lowercase();
         let normalized_b = b.trim().to_lowercase();
 
@@ -99,47 +388,88 @@ fn sort_modules(modules: &[String], options: &Options) -> Vec<String> {
             }
         }
 
-        fallback_module_compare(a, b, collator.as_ref())
+        let sort_key = &options.uses_section.uses_sort_key;
+        preset_module_compare(
+            &module_sort_key(a, sort_key),
+            &module_sort_key(b, sort_key),
+            options.uses_section.sort_preset,
+            collator.as_ref(),
+        )
     });
 
-    modules
+    entries
+}
+
+/// Keyword written at the start of a formatted module list section
+fn section_keyword(kind: &Kind) -> Option<&'static str> {
+    match kind {
+        Kind::Uses => Some("uses"),
+        Kind::Requires => Some("requires"),
+        Kind::Contains => Some("contains"),
+        _ => None,
+    }
 }
 
-/// Transform a parser::CodeSection to TextReplacement (only for uses sections)
-/// Skips code sections that are not uses sections or contain comments or preprocessor nodes
+fn skip_warning(code_section: &CodeSection, sibling: &crate::parser::ParsedNode, what: &str) {
+    warn!(
+        "Skipping uses section at byte range {}-{} due to presence of {} node",
+        code_section.keyword.start_byte, sibling.end_byte, what
+    );
+}
+
+/// Transform a parser::CodeSection to TextReplacement (only for uses sections and the
+/// `requires` / `contains` clauses of packages)
+/// Skips other code sections and sections that contain preprocessor nodes. Sections with
+/// comments are skipped, rejected or sorted with their comments by `uses_comment_policy`.
 pub fn transform_uses_section(
     code_section: &CodeSection,
     options: &Options,
     source: &str,
-) -> Option<TextReplacement> {
-    // Only process uses sections
-    if code_section.keyword.kind != Kind::Uses {
-        return None;
+) -> Result<Option<TextReplacement>, DFixxerError> {
+    // Only process module list sections
+    let Some(keyword) = section_keyword(&code_section.keyword.kind) else {
+        return Ok(None);
+    };
+
+    // Skip this uses section if it contains preprocessor directives
+    if let Some(sibling) = code_section
+        .siblings
+        .iter()
+        .find(|sibling| sibling.kind == Kind::Preprocessor)
+    {
+        skip_warning(code_section, sibling, "preprocessor");
+        return Ok(None);
     }
 
-    // Check if any sibling contains comments or preprocessor nodes
-    for sibling in &code_section.siblings {
-        match sibling.kind {
-            Kind::Comment | Kind::Preprocessor => {
-                // Skip this uses section if it contains comments or preprocessor directives
-                warn!(
-                    "Skipping uses section at byte range {}-{} due to presence of {} node",
-                    code_section.keyword.start_byte,
-                    sibling.end_byte,
-                    match sibling.kind {
-                        Kind::Comment => "comment",
-                        Kind::Preprocessor => "preprocessor",
-                        _ => "unknown",
-                    }
-                );
-                return None;
+    if let Some(sibling) = code_section
+        .siblings
+        .iter()
+        .find(|sibling| sibling.kind == Kind::Comment)
+    {
+        match options.uses_section.uses_comment_policy {
+            UsesCommentPolicy::Skip => {
+                skip_warning(code_section, sibling, "comment");
+                return Ok(None);
             }
-            _ => continue,
+            UsesCommentPolicy::Error => {
+                return Err(DFixxerError::PolicyError {
+                    filename: None,
+                    line: sibling.start_row + 1,
+                    message: format!(
+                        "{} section contains a comment (uses_comment_policy is \"Error\")",
+                        keyword
+                    ),
+                });
+            }
+            UsesCommentPolicy::Preserve => {}
         }
     }
 
-    // Extract module names from siblings (excluding semicolon)
-    let mut modules = Vec::new();
+    // Extract module names from siblings (excluding semicolon), attaching comments on a
+    // module's line to it and comments on their own lines to the module that follows
+    let mut entries: Vec<UsesEntry> = Vec::new();
+    let mut pending_comments = Vec::new();
+    let mut last_module_row = None;
     let mut semicolon_end_byte = code_section.keyword.end_byte; // default to keyword end if no semicolon found
 
     for sibling in &code_section.siblings {
@@ -147,7 +477,21 @@ pub fn transform_uses_section(
             Kind::Module => {
                 // Extract the module text from the source using byte positions
                 let module_text = &source[sibling.start_byte..sibling.end_byte];
-                modules.push(module_text.to_string());
+                let mut entry = UsesEntry::new(module_text.to_string());
+                entry.leading = std::mem::take(&mut pending_comments);
+                entries.push(entry);
+                last_module_row = Some(sibling.end_row);
+            }
+            Kind::Comment => {
+                let comment = source[sibling.start_byte..sibling.end_byte]
+                    .trim_end()
+                    .to_string();
+                match entries.last_mut() {
+                    Some(entry) if last_module_row == Some(sibling.start_row) => {
+                        entry.trailing.push(comment)
+                    }
+                    _ => pending_comments.push(comment),
+                }
             }
             Kind::Semicolon => {
                 // Remember the semicolon's end position for replacement range
@@ -156,12 +500,38 @@ pub fn transform_uses_section(
             _ => continue,
         }
     }
+    let module_count = entries.len();
+    let has_comments = !pending_comments.is_empty()
+        || entries
+            .iter()
+            .any(|entry| !entry.leading.is_empty() || !entry.trailing.is_empty());
 
     // Sort modules according to options
-    let sorted_modules = sort_modules(&modules, options);
+    let sorted_entries = sort_entries(entries, options);
+    let sorted_modules: Vec<String> = sorted_entries
+        .iter()
+        .map(|entry| entry.module.clone())
+        .collect();
 
     // Format the replacement text
-    let replacement_text = format_uses_replacement(&sorted_modules, options);
+    // Renames that merge entries leave fewer modules than lines, so those sections are reflowed
+    let replacement_text = if has_comments {
+        format_uses_replacement_with_comments(keyword, &sorted_entries, &pending_comments, options)
+    } else if options.uses_section.reflow
+        || sorted_modules.is_empty()
+        || sorted_modules.len() != module_count
+    {
+        format_uses_replacement(keyword, &sorted_modules, options)
+    } else {
+        let (line_sizes, first_on_keyword_line) = module_line_sizes(code_section);
+        format_uses_replacement_keeping_lines(
+            keyword,
+            &sorted_modules,
+            &line_sizes,
+            first_on_keyword_line,
+            options,
+        )
+    };
 
     // Determine the actual start position for replacement and adjust text if needed
     let (replacement_start, replacement_text) = adjust_replacement_for_line_position(
@@ -172,12 +542,13 @@ pub fn transform_uses_section(
     );
 
     // Create the text replacement if different from original
-    create_text_replacement_if_different(
+    Ok(create_text_replacement_if_different(
         source,
         replacement_start,
         semicolon_end_byte,
         replacement_text,
     )
+    .map(|replacement| replacement.with_reason("sorted uses")))
 }
 
 #[cfg(test)]
@@ -185,6 +556,14 @@ mod tests {
     use super::*;
     use crate::options::{Options, UsesSectionStyle};
 
+    fn sort_modules(modules: &[String], options: &Options) -> Vec<String> {
+        let entries = modules.iter().cloned().map(UsesEntry::new).collect();
+        sort_entries(entries, options)
+            .into_iter()
+            .map(|entry| entry.module)
+            .collect()
+    }
+
     fn make_options(
         style: UsesSectionStyle,
         indentation: &str,
@@ -195,6 +574,16 @@ mod tests {
                 uses_section_style: style,
                 override_sorting_order: Vec::new(),
                 module_names_to_update: Vec::new(),
+                module_renames: Vec::new(),
+                uses_sort_key: crate::options::UsesSortKey::UnitName,
+                sort_preset: crate::options::UsesSortPreset::Alphabetical,
+                reflow: true,
+                semicolon_on_own_line: true,
+                blank_line_after_uses: false,
+                project_units_last: false,
+                project_unit_prefixes: Vec::new(),
+                uses_comment_policy: crate::options::UsesCommentPolicy::Skip,
+                pinned_first_units: Vec::new(),
             },
             indentation: indentation.to_string(),
             line_ending,
@@ -216,7 +605,7 @@ mod tests {
         );
         // With the new style, the first unit has two extra spaces beyond indentation
         let expected = "uses\r\n    UnitA\r\n  , UnitB\r\n  , UnitC\r\n  ;";
-        let result = format_uses_replacement(&modules, &options);
+        let result = format_uses_replacement("uses", &modules, &options);
         assert_eq!(result, expected);
     }
 
@@ -233,10 +622,74 @@ mod tests {
             crate::options::LineEnding::Crlf,
         );
         let expected = "uses\r\n    UnitA,\r\n    UnitB,\r\n    UnitC;";
-        let result = format_uses_replacement(&modules, &options);
+        let result = format_uses_replacement("uses", &modules, &options);
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_format_uses_replacement_semicolon_after_last_module() {
+        let modules = vec!["UnitA".to_string(), "UnitB".to_string()];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheBeginning,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        options.uses_section.semicolon_on_own_line = false;
+        assert_eq!(
+            format_uses_replacement("uses", &modules, &options),
+            "uses\n    UnitA\n  , UnitB;"
+        );
+        assert_eq!(
+            format_uses_replacement_keeping_lines("uses", &modules, &[2], true, &options),
+            "uses UnitA, UnitB;"
+        );
+        // Without modules there is no line to attach the semicolon to
+        assert_eq!(format_uses_replacement("uses", &[], &options), "uses\n  ;");
+
+        options.uses_section.uses_section_style = UsesSectionStyle::CommaAtTheEnd;
+        assert_eq!(
+            format_uses_replacement("uses", &modules, &options),
+            "uses\n  UnitA,\n  UnitB;"
+        );
+        assert_eq!(
+            format_uses_replacement_keeping_lines("uses", &modules, &[1, 1], false, &options),
+            "uses\n  UnitA,\n  UnitB;"
+        );
+    }
+
+    #[test]
+    fn test_format_uses_replacement_blank_line_after_uses() {
+        let modules = vec!["UnitA".to_string(), "UnitB".to_string()];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Crlf,
+        );
+        options.uses_section.blank_line_after_uses = true;
+        assert_eq!(
+            format_uses_replacement("uses", &modules, &options),
+            "uses\r\n\r\n  UnitA,\r\n  UnitB;"
+        );
+        assert_eq!(
+            format_uses_replacement_keeping_lines("uses", &modules, &[2], false, &options),
+            "uses\r\n\r\n  UnitA, UnitB;"
+        );
+        assert_eq!(
+            format_uses_replacement_keeping_lines("uses", &modules, &[1, 1], true, &options),
+            "uses UnitA,\r\n  UnitB;"
+        );
+
+        options.uses_section.uses_section_style = UsesSectionStyle::CommaAtTheBeginning;
+        assert_eq!(
+            format_uses_replacement("uses", &modules, &options),
+            "uses\r\n\r\n    UnitA\r\n  , UnitB\r\n  ;"
+        );
+        assert_eq!(
+            format_uses_replacement("uses", &[], &options),
+            "uses\r\n  ;"
+        );
+    }
+
     #[test]
     fn test_format_uses_replacement_empty_modules() {
         let modules: Vec<String> = vec![];
@@ -246,7 +699,7 @@ mod tests {
             crate::options::LineEnding::Crlf,
         );
         let expected = "uses\r\n  ;";
-        let result = format_uses_replacement(&modules, &options);
+        let result = format_uses_replacement("uses", &modules, &options);
         assert_eq!(result, expected);
     }
 
@@ -265,6 +718,28 @@ mod tests {
         assert_eq!(sorted, expected);
     }
 
+    #[test]
+    fn test_sort_modules_applies_renames_and_deduplicates() {
+        let modules = vec![
+            "oldUnit".to_string(),
+            "NewUnit".to_string(),
+            "Legacy".to_string(),
+            "Classes".to_string(),
+        ];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheBeginning,
+            "    ",
+            crate::options::LineEnding::Crlf,
+        );
+        options.uses_section.module_renames = vec![
+            ("OldUnit".to_string(), "NewUnit".to_string()),
+            ("LEGACY".to_string(), "Classes".to_string()),
+        ];
+        options.uses_section.module_names_to_update = vec!["System:Classes".to_string()];
+        let sorted = sort_modules(&modules, &options);
+        assert_eq!(sorted, vec!["NewUnit", "System.Classes"]);
+    }
+
     #[test]
     fn test_sort_modules_applies_default_vcl_module_name_updates() {
         let modules = vec![
@@ -317,6 +792,236 @@ mod tests {
         assert_eq!(sorted, expected);
     }
 
+    #[test]
+    fn test_pinned_first_units_precede_sorted_units() {
+        let modules: Vec<String> = [
+            "Vcl.Forms",
+            "madExcept",
+            "System.SysUtils",
+            "fastmm4",
+            "Classes",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        options.uses_section.pinned_first_units = vec!["FastMM4".to_string()];
+        assert_eq!(
+            sort_modules(&modules, &options),
+            vec![
+                "fastmm4",
+                "Classes",
+                "madExcept",
+                "System.SysUtils",
+                "Vcl.Forms"
+            ]
+        );
+
+        // Pins keep the configured order and come before project units are grouped last
+        options.uses_section.pinned_first_units =
+            vec!["madExcept".to_string(), "FastMM4".to_string()];
+        options.uses_section.project_units_last = true;
+        assert_eq!(
+            sort_modules(&modules, &options),
+            vec![
+                "madExcept",
+                "fastmm4",
+                "System.SysUtils",
+                "Vcl.Forms",
+                "Classes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_formatted_single_module_uses_yields_no_replacement() {
+        let source = "uses\n  System.Classes;";
+        let node = |kind, start_byte, end_byte| crate::parser::ParsedNode {
+            kind,
+            start_byte,
+            end_byte,
+            start_row: 0,
+            start_column: 0,
+            end_row: 0,
+            end_column: 0,
+        };
+        let code_section = CodeSection {
+            keyword: node(Kind::Uses, 0, 4),
+            siblings: vec![node(Kind::Module, 7, 21), node(Kind::Semicolon, 21, 22)],
+        };
+        let options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        assert!(
+            transform_uses_section(&code_section, &options, source)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    fn commented_uses_section(source: &str) -> CodeSection {
+        let node = |kind, text: &str| {
+            let start_byte = source.find(text).unwrap();
+            let row = source[..start_byte].matches('\n').count();
+            crate::parser::ParsedNode {
+                kind,
+                start_byte,
+                end_byte: start_byte + text.len(),
+                start_row: row,
+                start_column: 0,
+                end_row: row,
+                end_column: 0,
+            }
+        };
+        CodeSection {
+            keyword: node(Kind::Uses, "uses"),
+            siblings: vec![
+                node(Kind::Module, "Zeta"),
+                node(Kind::Comment, "// last"),
+                node(Kind::Comment, "{ first }"),
+                node(Kind::Module, "Alpha"),
+                node(Kind::Semicolon, ";"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_uses_comment_policy() {
+        use crate::options::UsesCommentPolicy;
+        let source = "uses\n  Zeta, // last\n  { first }\n  Alpha;";
+        let code_section = commented_uses_section(source);
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+
+        options.uses_section.uses_comment_policy = UsesCommentPolicy::Skip;
+        assert!(
+            transform_uses_section(&code_section, &options, source)
+                .unwrap()
+                .is_none()
+        );
+
+        options.uses_section.uses_comment_policy = UsesCommentPolicy::Error;
+        let err = transform_uses_section(&code_section, &options, source).unwrap_err();
+        assert!(
+            matches!(err, DFixxerError::PolicyError { line: 2, .. }),
+            "{}",
+            err
+        );
+
+        options.uses_section.uses_comment_policy = UsesCommentPolicy::Preserve;
+        let replacement = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            replacement.text,
+            "uses\n  { first }\n  Alpha,\n  Zeta; // last"
+        );
+
+        options.uses_section.uses_section_style = UsesSectionStyle::CommaAtTheBeginning;
+        let replacement = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            replacement.text,
+            "uses\n  { first }\n    Alpha\n  , Zeta // last\n  ;"
+        );
+    }
+
+    #[test]
+    fn test_package_requires_and_contains_are_sorted() {
+        let source =
+            "requires\n  vcl,\n  rtl;\n\ncontains\n  UnitB in 'UnitB.pas', UnitA in 'UnitA.pas';";
+        let node_at = |kind, start_byte: usize, len: usize| crate::parser::ParsedNode {
+            kind,
+            start_byte,
+            end_byte: start_byte + len,
+            start_row: 0,
+            start_column: 0,
+            end_row: 0,
+            end_column: 0,
+        };
+        let node = |kind, text: &str| node_at(kind, source.find(text).unwrap(), text.len());
+        let requires = CodeSection {
+            keyword: node(Kind::Requires, "requires"),
+            siblings: vec![
+                node(Kind::Module, "vcl"),
+                node(Kind::Module, "rtl"),
+                node_at(Kind::Semicolon, source.find(';').unwrap(), 1),
+            ],
+        };
+        let contains = CodeSection {
+            keyword: node(Kind::Contains, "contains"),
+            siblings: vec![
+                node(Kind::Module, "UnitB in 'UnitB.pas'"),
+                node(Kind::Module, "UnitA in 'UnitA.pas'"),
+                node_at(Kind::Semicolon, source.rfind(';').unwrap(), 1),
+            ],
+        };
+        // The default module name updates must not qualify package names
+        let options = Options::default();
+
+        let replacement = transform_uses_section(&requires, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.text, "requires\n  rtl,\n  vcl;");
+        let replacement = transform_uses_section(&contains, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            replacement.text,
+            "contains\n  UnitA in 'UnitA.pas',\n  UnitB in 'UnitB.pas';"
+        );
+    }
+
+    #[test]
+    fn test_sort_modules_by_in_clause_path() {
+        let modules = vec![
+            "Zeta in 'a\\Zeta.pas'".to_string(),
+            "Beta".to_string(),
+            "Alpha in 'z\\Alpha.pas'".to_string(),
+            "Gamma IN 'c\\Gamma.pas' {GammaForm}".to_string(),
+        ];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Crlf,
+        );
+        options.uses_section.uses_sort_key = crate::options::UsesSortKey::InPath;
+        let sorted = sort_modules(&modules, &options);
+        let expected = vec![
+            "Zeta in 'a\\Zeta.pas'",
+            "Beta",
+            "Gamma IN 'c\\Gamma.pas' {GammaForm}",
+            "Alpha in 'z\\Alpha.pas'",
+        ];
+        let expected: Vec<String> = expected.into_iter().map(|s| s.to_string()).collect();
+        assert_eq!(sorted, expected);
+
+        options.uses_section.uses_sort_key = crate::options::UsesSortKey::UnitName;
+        let sorted = sort_modules(&modules, &options);
+        assert_eq!(sorted[0], "Alpha in 'z\\Alpha.pas'");
+        assert_eq!(sorted[3], "Zeta in 'a\\Zeta.pas'");
+    }
+
+    #[test]
+    fn test_in_clause_path() {
+        assert_eq!(
+            in_clause_path("Foo in 'src\\Foo.pas'"),
+            Some("src\\Foo.pas")
+        );
+        assert_eq!(in_clause_path("Foo"), None);
+        assert_eq!(in_clause_path("System.Classes"), None);
+    }
+
     #[test]
     fn test_sort_modules_without_override_namespaces() {
         let modules = vec!["B".to_string(), "A".to_string(), "C".to_string()];
@@ -332,6 +1037,70 @@ mod tests {
         assert_eq!(sorted, expected);
     }
 
+    #[test]
+    fn test_sort_modules_length_then_alpha_preset() {
+        let modules = vec![
+            "System.SysUtils".to_string(),
+            "Vcl.Forms".to_string(),
+            "Math".to_string(),
+            "Types".to_string(),
+            "Winapi.Windows".to_string(),
+            "Vcl.Controls".to_string(),
+        ];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        options.uses_section.sort_preset = crate::options::UsesSortPreset::LengthThenAlpha;
+        let sorted = sort_modules(&modules, &options);
+        assert_eq!(
+            sorted,
+            vec![
+                "Math",
+                "Types",
+                "Vcl.Forms",
+                "Vcl.Controls",
+                "Winapi.Windows",
+                "System.SysUtils",
+            ]
+        );
+
+        // Override namespaces still come first; the preset orders within each group
+        options.uses_section.override_sorting_order = vec!["System".to_string()];
+        let sorted = sort_modules(&modules, &options);
+        assert_eq!(sorted[0], "System.SysUtils");
+        assert_eq!(sorted[1], "Math");
+    }
+
+    #[test]
+    fn test_sort_modules_project_units_last() {
+        let modules = vec![
+            "MyLocalUnit".to_string(),
+            "ThirdParty.Y".to_string(),
+            "System.X".to_string(),
+            "AppCore.Logging".to_string(),
+        ];
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        options.uses_section.project_units_last = true;
+        assert_eq!(
+            sort_modules(&modules, &options),
+            vec!["AppCore.Logging", "System.X", "ThirdParty.Y", "MyLocalUnit"]
+        );
+
+        // With prefixes, only matching units move to the end
+        options.uses_section.project_unit_prefixes = vec!["appcore".to_string()];
+        options.uses_section.override_sorting_order = vec!["System".to_string()];
+        assert_eq!(
+            sort_modules(&modules, &options),
+            vec!["System.X", "MyLocalUnit", "ThirdParty.Y", "AppCore.Logging"]
+        );
+    }
+
     #[test]
     fn test_sort_modules_without_dot_boundary_for_override_namespace() {
         let modules = vec![
@@ -405,7 +1174,95 @@ mod tests {
             crate::options::LineEnding::Lf,
         );
         let expected = "uses\n  UnitA,\n  UnitB;";
-        let result = format_uses_replacement(&modules, &options);
+        let result = format_uses_replacement("uses", &modules, &options);
         assert_eq!(result, expected);
     }
+
+    fn uses_section_with_rows(source: &str, modules: &[(&str, usize)]) -> CodeSection {
+        let node = |kind, start_byte: usize, end_byte: usize, row| crate::parser::ParsedNode {
+            kind,
+            start_byte,
+            end_byte,
+            start_row: row,
+            start_column: 0,
+            end_row: row,
+            end_column: 0,
+        };
+        let mut siblings: Vec<_> = modules
+            .iter()
+            .map(|(name, row)| {
+                let start = source.find(name).unwrap();
+                node(Kind::Module, start, start + name.len(), *row)
+            })
+            .collect();
+        let semicolon = source.rfind(';').unwrap();
+        let last_row = source.lines().count() - 1;
+        siblings.push(node(Kind::Semicolon, semicolon, semicolon + 1, last_row));
+        CodeSection {
+            keyword: node(Kind::Uses, 0, 4, 0),
+            siblings,
+        }
+    }
+
+    #[test]
+    fn test_reflow_off_keeps_line_grouping() {
+        let source = "uses Zeta, Beta,\n  Alpha, Delta;";
+        let code_section = uses_section_with_rows(
+            source,
+            &[("Zeta", 0), ("Beta", 0), ("Alpha", 1), ("Delta", 1)],
+        );
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+
+        let reflowed = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reflowed.text, "uses\n  Alpha,\n  Beta,\n  Delta,\n  Zeta;");
+
+        options.uses_section.reflow = false;
+        let kept = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.text, "uses Alpha, Beta,\n  Delta, Zeta;");
+
+        options.uses_section.uses_section_style = UsesSectionStyle::CommaAtTheBeginning;
+        let kept = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.text, "uses Alpha, Beta\n  , Delta, Zeta\n  ;");
+    }
+
+    #[test]
+    fn test_uses_replacement_is_tagged_with_reason() {
+        let source = "uses B, A;";
+        let code_section = uses_section_with_rows(source, &[("B", 0), ("A", 0)]);
+        let options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        let replacement = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.reason.as_deref(), Some("sorted uses"));
+    }
+
+    #[test]
+    fn test_reflow_off_fixes_comma_placement_only() {
+        let source = "uses\n    B\n  , A, C\n  ;";
+        let code_section = uses_section_with_rows(source, &[("B", 1), ("A", 2), ("C", 2)]);
+        let mut options = make_options(
+            UsesSectionStyle::CommaAtTheEnd,
+            "  ",
+            crate::options::LineEnding::Lf,
+        );
+        options.uses_section.reflow = false;
+        let replacement = transform_uses_section(&code_section, &options, source)
+            .unwrap()
+            .unwrap();
+        assert_eq!(replacement.text, "uses\n  A,\n  B, C;");
+    }
 }
@@ -0,0 +1,290 @@
+use crate::options::{Options, SpaceOperation};
+use crate::parser::{CodeSection, Kind, ParsedNode};
+use crate::replacements::TextReplacement;
+
+/// A single-line declaration split at its `:` or `=` separator
+struct AlignableDeclaration<'a> {
+    row: usize,
+    start_byte: usize,
+    end_byte: usize,
+    name: &'a str,
+    separator: char,
+    /// Whitespace between the separator and `rest` as written
+    space_after: &'a str,
+    rest: &'a str,
+}
+
+/// Split `a, b: Integer;` or `A = 1;` into name, separator, and the text after the separator.
+/// Returns None for declarations that span lines or carry attributes or other prefixes, and
+/// for a `:` between digits like `X1:1..5` when `colon_numeric_exception` leaves it as written.
+fn split_declaration<'a>(
+    declaration: &ParsedNode,
+    options: &Options,
+    source: &'a str,
+) -> Option<AlignableDeclaration<'a>> {
+    if declaration.start_row != declaration.end_row {
+        return None;
+    }
+    let text = &source[declaration.start_byte..declaration.end_byte];
+    let separator_index = text.find([':', '='])?;
+    let name = text[..separator_index].trim_end();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '&' | ',' | ' ' | '\t'))
+    {
+        return None;
+    }
+    let separator = text[separator_index..].chars().next()?;
+    let after = &text[separator_index + 1..];
+    if separator == ':'
+        && options.text_changes.colon_numeric_exception
+        && text[..separator_index].ends_with(|ch: char| ch.is_ascii_digit())
+        && after.starts_with(|ch: char| ch.is_ascii_digit())
+    {
+        return None;
+    }
+    let rest = after.trim_start();
+    Some(AlignableDeclaration {
+        row: declaration.start_row,
+        start_byte: declaration.start_byte,
+        end_byte: declaration.end_byte,
+        name,
+        separator,
+        space_after: &after[..after.len() - rest.len()],
+        rest,
+    })
+}
+
+/// The separator with the spacing its rule asks for; the padding before it does the aligning
+fn format_separator(declaration: &AlignableDeclaration, options: &Options) -> String {
+    let separator = declaration.separator;
+    let operation = match separator {
+        ':' => &options.text_changes.colon,
+        _ => &options.text_changes.eq,
+    };
+    match operation {
+        SpaceOperation::Before => format!(" {}", separator),
+        SpaceOperation::BeforeAndAfter => format!(" {} ", separator),
+        SpaceOperation::Remove => separator.to_string(),
+        SpaceOperation::After => format!("{} ", separator),
+        SpaceOperation::NoChange => format!("{}{}", separator, declaration.space_after),
+    }
+}
+
+/// Rewrite one run of declarations so their separators line up
+fn align_group(group: &[AlignableDeclaration], options: &Options, source: &str) -> TextReplacement {
+    let width = group
+        .iter()
+        .map(|declaration| declaration.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut text = String::new();
+    for (index, declaration) in group.iter().enumerate() {
+        if index > 0 {
+            // Keep the line break and indentation between declarations as written
+            text.push_str(&source[group[index - 1].end_byte..declaration.start_byte]);
+        }
+        let padding = width - declaration.name.chars().count();
+        text.push_str(declaration.name);
+        text.push_str(&" ".repeat(padding));
+        text.push_str(&format_separator(declaration, options));
+        text.push_str(declaration.rest);
+    }
+
+    TextReplacement {
+        start: group[0].start_byte,
+        end: group[group.len() - 1].end_byte,
+        text,
+        reason: Some("aligned declarations".to_string()),
+        is_final: true,
+    }
+}
+
+/// Vertically align `:` and `=` in consecutive declarations of a `var` or `const` section.
+/// Declarations on consecutive lines sharing the same separator form a group.
+/// Sections containing comments or preprocessor directives are skipped.
+/// Every aligned group yields a replacement, even when unchanged, so text spacing rules
+/// do not collapse the alignment padding afterwards.
+pub fn transform_var_alignment(
+    code_section: &CodeSection,
+    options: &Options,
+    source: &str,
+) -> Vec<TextReplacement> {
+    if !matches!(code_section.keyword.kind, Kind::Var | Kind::Const) {
+        return Vec::new();
+    }
+    if code_section
+        .siblings
+        .iter()
+        .any(|sibling| sibling.kind != Kind::Declaration)
+    {
+        return Vec::new();
+    }
+
+    let mut replacements = Vec::new();
+    let mut group: Vec<AlignableDeclaration> = Vec::new();
+    for sibling in &code_section.siblings {
+        let declaration = split_declaration(sibling, options, source);
+        let continues_group = match (&declaration, group.last()) {
+            (Some(declaration), Some(last)) => {
+                declaration.row == last.row + 1 && declaration.separator == last.separator
+            }
+            _ => false,
+        };
+        if !continues_group {
+            if group.len() > 1 {
+                replacements.push(align_group(&group, options, source));
+            }
+            group.clear();
+        }
+        if let Some(declaration) = declaration {
+            group.push(declaration);
+        }
+    }
+    if group.len() > 1 {
+        replacements.push(align_group(&group, options, source));
+    }
+
+    replacements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declaration_node(source: &str, text: &str, row: usize) -> ParsedNode {
+        let start_byte = source.find(text).unwrap();
+        ParsedNode {
+            kind: Kind::Declaration,
+            start_byte,
+            end_byte: start_byte + text.len(),
+            start_row: row,
+            start_column: 0,
+            end_row: row,
+            end_column: 0,
+        }
+    }
+
+    fn section(kind: Kind, source: &str, declarations: &[(&str, usize)]) -> CodeSection {
+        CodeSection {
+            keyword: ParsedNode {
+                kind,
+                start_byte: 0,
+                end_byte: 3,
+                start_row: 0,
+                start_column: 0,
+                end_row: 0,
+                end_column: 3,
+            },
+            siblings: declarations
+                .iter()
+                .map(|(text, row)| declaration_node(source, text, *row))
+                .collect(),
+        }
+    }
+
+    fn apply(source: &str, replacements: &[TextReplacement]) -> String {
+        crate::replacements::apply_replacements_to_string(source, replacements)
+    }
+
+    #[test]
+    fn test_aligns_three_line_var_block() {
+        let source = "var\n  a: Integer;\n  bbb : String;\n  cc:Boolean;\n";
+        let code_section = section(
+            Kind::Var,
+            source,
+            &[("a: Integer;", 1), ("bbb : String;", 2), ("cc:Boolean;", 3)],
+        );
+        let replacements = transform_var_alignment(&code_section, &Options::default(), source);
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(
+            apply(source, &replacements),
+            "var\n  a  : Integer;\n  bbb: String;\n  cc : Boolean;\n"
+        );
+    }
+
+    #[test]
+    fn test_aligned_block_is_kept_as_identity_replacement() {
+        let source = "var\n  a  : Integer;\n  bbb: String;\n";
+        let code_section = section(
+            Kind::Var,
+            source,
+            &[("a  : Integer;", 1), ("bbb: String;", 2)],
+        );
+        let replacements = transform_var_alignment(&code_section, &Options::default(), source);
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(apply(source, &replacements), source);
+    }
+
+    #[test]
+    fn test_blank_line_splits_groups_and_const_aligns_on_eq() {
+        let source = "const\n  A = 1;\n  Long = 2;\n\n  B = 3;\n";
+        let code_section = section(
+            Kind::Const,
+            source,
+            &[("A = 1;", 1), ("Long = 2;", 2), ("B = 3;", 4)],
+        );
+        let replacements = transform_var_alignment(&code_section, &Options::default(), source);
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(
+            apply(source, &replacements),
+            "const\n  A    = 1;\n  Long = 2;\n\n  B = 3;\n"
+        );
+    }
+
+    #[test]
+    fn test_no_change_keeps_spacing_after_separator() {
+        let source = "var\n  a:Integer;\n  bbb:  String;\n";
+        let code_section = section(
+            Kind::Var,
+            source,
+            &[("a:Integer;", 1), ("bbb:  String;", 2)],
+        );
+        let mut options = Options::default();
+        options.text_changes.colon = SpaceOperation::NoChange;
+        let replacements = transform_var_alignment(&code_section, &options, source);
+        assert_eq!(
+            apply(source, &replacements),
+            "var\n  a  :Integer;\n  bbb:  String;\n"
+        );
+    }
+
+    #[test]
+    fn test_colon_numeric_exception_leaves_declaration_unaligned() {
+        let source = "var\n  a: Integer;\n  X1:1..5;\n  bbb: String;\n";
+        let code_section = section(
+            Kind::Var,
+            source,
+            &[("a: Integer;", 1), ("X1:1..5;", 2), ("bbb: String;", 3)],
+        );
+        let mut options = Options::default();
+        assert!(transform_var_alignment(&code_section, &options, source).is_empty());
+
+        options.text_changes.colon_numeric_exception = false;
+        let replacements = transform_var_alignment(&code_section, &options, source);
+        assert_eq!(
+            apply(source, &replacements),
+            "var\n  a  : Integer;\n  X1 : 1..5;\n  bbb: String;\n"
+        );
+    }
+
+    #[test]
+    fn test_skips_sections_with_comments() {
+        let source = "var\n  a: Integer; // note\n  bbb: String;\n";
+        let mut code_section = section(
+            Kind::Var,
+            source,
+            &[("a: Integer;", 1), ("bbb: String;", 2)],
+        );
+        code_section.siblings.insert(
+            1,
+            ParsedNode {
+                kind: Kind::Comment,
+                ..declaration_node(source, "// note", 1)
+            },
+        );
+        assert!(transform_var_alignment(&code_section, &Options::default(), source).is_empty());
+    }
+}
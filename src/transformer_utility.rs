@@ -81,6 +81,8 @@ pub fn create_text_replacement_if_different(
         start: replacement_start,
         end: replacement_end,
         text: replacement_text,
+        reason: None,
+        is_final: true,
     })
 }
 
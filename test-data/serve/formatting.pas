@@ -0,0 +1,12 @@
+unit Serve;
+
+interface
+
+implementation
+
+procedure Run;
+begin
+  Foo(a,b);
+end;
+
+end.
@@ -0,0 +1,18 @@
+unit AlignDeclarations;
+
+interface
+
+const
+  A = 1;
+  LongName=2;
+
+var
+  X: Integer;
+  LongerName : string;
+  Y:Boolean;
+
+  Z: Integer;
+
+implementation
+
+end.
@@ -0,0 +1,19 @@
+unit AlignInlineVars;
+
+interface
+
+var
+  X         : Integer;
+  LongerName: string;
+
+implementation
+
+procedure Test;
+begin
+  var Spare: Integer;
+  const First: Integer = 1;
+  var Counter: Integer := 2;
+  Counter := Counter + 1;
+end;
+
+end.
@@ -0,0 +1,22 @@
+unit AlignInlineVars;
+
+interface
+
+var
+  X: Integer;
+  LongerName : string;
+
+implementation
+
+procedure Test;
+var
+  First: Integer;
+  Counter: Integer;
+  Spare: Integer;
+begin
+  First := 1;
+  Counter := 2;
+  Counter := Counter + 1;
+end;
+
+end.
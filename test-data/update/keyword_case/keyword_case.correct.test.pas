@@ -0,0 +1,18 @@
+unit KeywordCaseFixture;
+
+interface
+
+implementation
+
+procedure Run;
+var
+  BeginValue: Integer;
+  EndValue: Integer;
+begin
+  BeginValue := 1;
+  EndValue := BeginValue;
+  if BeginValue = EndValue then
+    EndValue := 2;
+end;
+
+end.
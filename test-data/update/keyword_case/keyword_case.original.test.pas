@@ -0,0 +1,18 @@
+UNIT KeywordCaseFixture;
+
+INTERFACE
+
+IMPLEMENTATION
+
+PROCEDURE Run;
+VAR
+  BeginValue: Integer;
+  EndValue: Integer;
+BEGIN
+  BeginValue := 1;
+  EndValue := BeginValue;
+  IF BeginValue = EndValue THEN
+    EndValue := 2;
+End;
+
+END.
@@ -0,0 +1,22 @@
+unit PreserveRegions;
+
+interface
+
+uses
+  System.Classes,System.SysUtils;
+
+{ Keep this   block
+  exactly   as written }   
+
+
+
+implementation
+
+procedure Run;
+begin
+  Foo(a,b);
+end;
+
+end.
+
+
@@ -0,0 +1,19 @@
+unit SectionBlankLines;
+
+interface
+
+procedure Run;
+
+implementation
+
+procedure Run;
+begin
+end;
+
+initialization
+  Run;
+
+finalization
+  Run;
+
+end.
@@ -0,0 +1,20 @@
+unit SectionBlankLines;
+interface
+
+procedure Run;
+
+
+
+implementation
+
+procedure Run;
+begin
+end;
+initialization
+  Run;
+
+
+finalization
+  Run;
+
+end.
@@ -0,0 +1,7 @@
+unit LeadingBlankLines;
+
+interface
+
+implementation
+
+end.
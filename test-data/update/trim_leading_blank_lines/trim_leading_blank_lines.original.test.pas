@@ -0,0 +1,10 @@
+
+
+  
+unit LeadingBlankLines;
+
+interface
+
+implementation
+
+end.
@@ -0,0 +1,14 @@
+unit UsesWithComment;
+
+interface
+
+uses
+  System.SysUtils, { keep } System.Classes;
+
+implementation
+
+uses
+  System.Math,
+  System.StrUtils;
+
+end.
@@ -0,0 +1,13 @@
+unit UsesWithComment;
+
+interface
+
+uses
+  System.SysUtils, { keep } System.Classes;
+
+implementation
+
+uses
+  System.StrUtils, System.Math;
+
+end.
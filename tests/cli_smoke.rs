@@ -1,4 +1,5 @@
-use std::process::Command;
+use std::io::Write;
+use std::process::{Command, Stdio};
 
 use std::env;
 use std::fs;
@@ -17,6 +18,17 @@ fn test_help_output() {
     assert!(stdout.contains("Usage"));
 }
 
+#[test]
+fn test_completions_bash_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["completions", "bash"])
+        .output()
+        .expect("Failed to run dfixxer completions");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("dfixxer"));
+}
+
 #[test]
 fn test_info_logging_includes_fine_grained_performance_summary_on_stderr() {
     let source = Path::new("test-data")
@@ -60,6 +72,1095 @@ fn test_info_logging_includes_fine_grained_performance_summary_on_stderr() {
         "Expected text transformation counters section in stderr, got:\n{}",
         stderr
     );
+    assert!(
+        stderr.contains("Transformation breakdown (slowest first):"),
+        "Expected transformation breakdown section in stderr, got:\n{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_exit_code_contract() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(&config_file, "").expect("Failed to write config");
+    let clean_file = temp_dir.join("clean.pas");
+    fs::write(
+        &clean_file,
+        "unit Clean;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write clean file");
+    let changed_file = temp_dir.join("changed.pas");
+    fs::write(
+        &changed_file,
+        "unit Changed;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\n  Bar(c,d);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write changed file");
+    let broken_file = temp_dir.join("broken.pas");
+    fs::write(
+        &broken_file,
+        "unit Broken;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  x := ;\nend;\n\nend.\n",
+    )
+    .expect("Failed to write broken file");
+
+    let exit_code = |args: &[&std::ffi::OsStr]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(args)
+            .output()
+            .expect("Failed to run dfixxer")
+            .status
+            .code()
+    };
+    let config = config_file.as_os_str();
+
+    // Clean and would-change are 0 and 1, regardless of how many changes are needed
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            clean_file.as_os_str(),
+            "--config".as_ref(),
+            config
+        ]),
+        Some(0)
+    );
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            changed_file.as_os_str(),
+            "--config".as_ref(),
+            config
+        ]),
+        Some(1)
+    );
+    // Usage errors are 2
+    assert_eq!(
+        exit_code(&["check".as_ref(), "--no-such-flag".as_ref()]),
+        Some(2)
+    );
+    // IO errors are 3
+    let missing = temp_dir.join("missing.pas");
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            missing.as_os_str(),
+            "--config".as_ref(),
+            config
+        ]),
+        Some(3)
+    );
+    // Parse errors are 4; parse-debug still prints what it could parse
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            broken_file.as_os_str(),
+            "--config".as_ref(),
+            config,
+            "--strict-parse".as_ref()
+        ]),
+        Some(4)
+    );
+    assert_eq!(
+        exit_code(&["parse-debug".as_ref(), broken_file.as_os_str()]),
+        Some(0)
+    );
+    // Deprecated config keys are 5 when nothing else is reported; changes still win
+    let deprecated_config = temp_dir.join("deprecated.toml");
+    fs::write(&deprecated_config, "space_after_comma = true\n")
+        .expect("Failed to write deprecated config");
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            clean_file.as_os_str(),
+            "--config".as_ref(),
+            deprecated_config.as_os_str()
+        ]),
+        Some(5)
+    );
+    assert_eq!(
+        exit_code(&[
+            "check".as_ref(),
+            changed_file.as_os_str(),
+            "--config".as_ref(),
+            deprecated_config.as_os_str()
+        ]),
+        Some(1)
+    );
+    // Sources the configuration rejects are 6, and the error names the file
+    let policy_config = temp_dir.join("policy.toml");
+    fs::write(
+        &policy_config,
+        "[uses_section]\nuses_comment_policy = \"Error\"\n",
+    )
+    .expect("Failed to write policy config");
+    let commented_file = temp_dir.join("commented.pas");
+    fs::write(
+        &commented_file,
+        "unit Commented;\n\ninterface\n\nuses\n  Zeta, // last\n  Alpha;\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write commented file");
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("check")
+        .arg(&commented_file)
+        .arg("--config")
+        .arg(&policy_config)
+        .output()
+        .expect("Failed to run dfixxer");
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("commented.pas:6:"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_strict_parse_fails_file_with_parse_error() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(&config_file, "").expect("Failed to write config");
+    let source = "unit Partial;\n\ninterface\n\nuses\n  Zeta, Alpha;\n\nimplementation\n\nprocedure Run;\nbegin\n  x := ;\nend;\n\nend.\n";
+    let source_file = temp_dir.join("partial.pas");
+    fs::write(&source_file, source).expect("Failed to write partial file");
+
+    let run = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("update")
+            .arg(&source_file)
+            .arg("--config")
+            .arg(&config_file)
+            .args(extra)
+            .output()
+            .expect("Failed to run update")
+    };
+    let strict = run(&["--strict-parse"]);
+    assert_eq!(strict.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&strict.stderr).contains("partial.pas:12:"));
+    assert_eq!(fs::read_to_string(&source_file).unwrap(), source);
+
+    // The clean uses clause is still sorted when parsing is lenient
+    assert_eq!(run(&[]).status.code(), Some(0));
+    assert!(
+        fs::read_to_string(&source_file)
+            .unwrap()
+            .contains("uses\n  Alpha,\n  Zeta;\n")
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_invalid_config_value_fails_with_position() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(
+        &config_file,
+        "indentation = \"  \"\n\n[text_changes]\ncomma = \"Sometimes\"\n",
+    )
+    .expect("Failed to write config");
+    let source_file = temp_dir.join("unit.pas");
+    fs::write(
+        &source_file,
+        "unit Unit1;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("check")
+        .arg(&source_file)
+        .arg("--config")
+        .arg(&config_file)
+        .output()
+        .expect("Failed to run check");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("{}:4:9: ", config_file.display())),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_invalid_custom_config_fails_instead_of_using_defaults() {
+    let temp_dir = create_unique_temp_dir();
+    fs::create_dir_all(temp_dir.join("legacy")).expect("Failed to create legacy dir");
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(
+        &config_file,
+        "custom_config_patterns = [[\"legacy/*.pas\", \"legacy.toml\"]]\n",
+    )
+    .expect("Failed to write config");
+    let custom_config = temp_dir.join("legacy.toml");
+    fs::write(
+        &custom_config,
+        "indentation = \"  \"\nline_ending = \"Often\"\n",
+    )
+    .expect("Failed to write custom config");
+    let source = "unit Old;\n\ninterface\n\nimplementation\n\nend.\n";
+    let source_file = temp_dir.join("legacy").join("Old.pas");
+    fs::write(&source_file, source).expect("Failed to write source");
+
+    let check = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("check")
+        .arg(&source_file)
+        .arg("--config")
+        .arg(&config_file)
+        .output()
+        .expect("Failed to run check");
+    assert_eq!(check.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&check.stderr);
+    assert!(
+        stderr.contains(&format!("{}:2:", custom_config.display())),
+        "{}",
+        stderr
+    );
+
+    // Input from stdin is not formatted with defaults either
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg("--stdin-filename")
+        .arg(&source_file)
+        .arg("--config")
+        .arg(&config_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run update with --stdin-filename");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .expect("Failed to write stdin");
+    let update = child
+        .wait_with_output()
+        .expect("Failed to wait for dfixxer");
+    assert_eq!(update.status.code(), Some(2));
+    assert!(update.stdout.is_empty());
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_multi_warns_about_config_once() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(
+        &config_file,
+        "[uses_section]\nmodule_names_to_update = [\"SystemClasses\"]\n",
+    )
+    .expect("Failed to write config");
+    for name in ["a", "b", "c"] {
+        fs::write(
+            temp_dir.join(format!("{}.pas", name)),
+            format!("unit {};\n\ninterface\n\nimplementation\n\nend.\n", name),
+        )
+        .expect("Failed to write source");
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--multi"])
+        .arg(temp_dir.join("*.pas"))
+        .arg("--config")
+        .arg(&config_file)
+        .output()
+        .expect("Failed to run check --multi");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("'SystemClasses'").count(), 1, "{}", stderr);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_report_indentation_flags_tab_line_under_spaces_config() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(&config_file, "indentation = \"  \"\n").expect("Failed to write config");
+    let source = "unit Unit1;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo;\n\tBar;\nend;\n\nend.\n";
+    let source_file = temp_dir.join("unit.pas");
+    fs::write(&source_file, source).expect("Failed to write source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("check")
+        .arg(&source_file)
+        .arg("--config")
+        .arg(&config_file)
+        .arg("--report-indentation")
+        .output()
+        .expect("Failed to run check");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(&format!(
+            "{}:10: indented with tabs, but indentation is spaces",
+            source_file.display()
+        )),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains(":9: "), "{}", stdout);
+    // Reporting never rewrites the file
+    assert_eq!(fs::read_to_string(&source_file).unwrap(), source);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+/// Build `outer/dfixxer.toml` (which disables comma spacing) above `outer/repo/src/unit.pas`
+fn create_nested_config_tree() -> (std::path::PathBuf, std::path::PathBuf) {
+    let temp_dir = create_unique_temp_dir();
+    let outer = temp_dir.join("outer");
+    let src = outer.join("repo").join("src");
+    fs::create_dir_all(&src).expect("Failed to create nested tree");
+    fs::write(
+        outer.join("dfixxer.toml"),
+        "[text_changes]\ncomma = \"NoChange\"\n",
+    )
+    .expect("Failed to write outer config");
+    let source_file = src.join("unit.pas");
+    fs::write(
+        &source_file,
+        "unit Nested;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write nested source");
+    (temp_dir, source_file)
+}
+
+fn check_would_change(source_file: &Path, extra_args: &[&str], root_env: Option<&Path>) -> bool {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_dfixxer"));
+    command
+        .args(extra_args)
+        .args(["check", "--list-different"])
+        .arg(source_file)
+        .env_remove("DFIXXER_ROOT");
+    if let Some(root) = root_env {
+        command.env("DFIXXER_ROOT", root);
+    }
+    let status = command.status().expect("Failed to run check");
+    status.code() == Some(1)
+}
+
+#[test]
+fn test_config_search_respects_root_boundaries() {
+    let (temp_dir, source_file) = create_nested_config_tree();
+    let repo = temp_dir.join("outer").join("repo");
+
+    // Without a boundary the outer config is found and comma spacing is left alone
+    assert!(!check_would_change(&source_file, &[], None));
+
+    // The search root stops the walk before the outer config, so defaults apply
+    let root = repo.to_str().unwrap();
+    assert!(check_would_change(
+        &source_file,
+        &["--config-search-root", root],
+        None
+    ));
+    assert!(check_would_change(&source_file, &[], Some(&repo)));
+
+    // A .git directory marks the repository root by default
+    fs::create_dir_all(repo.join(".git")).expect("Failed to create .git");
+    assert!(check_would_change(&source_file, &[], None));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_color_flag_controls_escape_sequences() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("color.pas");
+    fs::write(
+        &temp_file,
+        "unit Color;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write color fixture");
+
+    let run_check = |color: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--color", color])
+            .arg(&temp_file)
+            .output()
+            .expect("Failed to run check");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let plain = run_check("never");
+    assert!(plain.contains("-  Foo(a,b);"));
+    assert!(plain.contains("+  Foo(a, b);"));
+    assert!(!plain.contains('\u{1b}'));
+
+    let colored = run_check("always");
+    assert!(colored.contains('\u{1b}'));
+    assert!(colored.contains("+  Foo(a, b);"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_diff_context_controls_hunk_context() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("context.pas");
+    fs::write(
+        &temp_file,
+        "unit Context;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write context fixture");
+
+    let run_check = |extra_args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--color", "never"])
+            .args(extra_args)
+            .arg(&temp_file)
+            .output()
+            .expect("Failed to run check");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let minimal = run_check(&["--diff-context", "0"]);
+    assert!(minimal.contains("@@ -9 +9 @@"));
+    assert!(minimal.contains("-  Foo(a,b);"));
+    assert!(!minimal.contains(" begin"));
+
+    let default = run_check(&[]);
+    assert!(default.contains("@@ -6,7 +6,7 @@"));
+    assert!(default.contains(" begin\n-  Foo(a,b);\n+  Foo(a, b);\n end;"));
+    assert_eq!(default, run_check(&["--diff-context", "3"]));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_verbose_timing_prints_summary_to_stderr() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("timing.pas");
+    fs::write(
+        &temp_file,
+        "unit Timing;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write timing fixture");
+
+    let run_check = |extra_args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("check")
+            .args(extra_args)
+            .arg(&temp_file)
+            .output()
+            .expect("Failed to run check");
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    assert!(!run_check(&[]).contains("Performance summary:"));
+    let stderr = run_check(&["--verbose-timing"]);
+    assert!(stderr.contains("Performance summary:"));
+    assert!(stderr.contains("  Total processing:"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_multi_allow_empty_accepts_unmatched_pattern() {
+    let temp_dir = create_unique_temp_dir();
+    let pattern = temp_dir.join("*.pas").to_string_lossy().to_string();
+
+    let run_check = |extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", &pattern, "--multi"])
+            .args(extra_args)
+            .output()
+            .expect("Failed to run check --multi")
+    };
+
+    let output = run_check(&[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No files found matching pattern"));
+
+    let output = run_check(&["--allow-empty"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_apply_reports_and_writes_changes() {
+    let temp_dir = create_unique_temp_dir();
+    let file = temp_dir.join("apply.pas");
+    fs::write(&file, "program P;\nbegin\n  Foo(a,b);\nend.\n").expect("Failed to write source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--apply", "--color", "never"])
+        .arg(&file)
+        .output()
+        .expect("Failed to run check --apply");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-  Foo(a,b);"), "{}", stdout);
+    assert!(stdout.contains("+  Foo(a, b);"), "{}", stdout);
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "program P;\nbegin\n  Foo(a, b);\nend.\n"
+    );
+
+    // Nothing is left to report or write
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--apply"])
+        .arg(&file)
+        .output()
+        .expect("Failed to run check --apply");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_leaves_file_with_skip_marker_unchanged() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "// Generated code\n{ dfixxer: skip }\nprogram P;\nbegin\n  Foo(a,b);\nend.\n";
+    let file = temp_dir.join("generated.pas");
+    fs::write(&file, source).expect("Failed to write source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&file)
+        .output()
+        .expect("Failed to run update");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&file).unwrap(), source);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_max_file_size_bytes_skips_larger_files() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "program P;\nbegin\n  Foo(a,b);\nend.\n";
+    let file = temp_dir.join("big.pas");
+    fs::write(&file, source).expect("Failed to write source");
+    let config = temp_dir.join("dfixxer.toml");
+
+    let check_with_limit = |limit: usize| {
+        fs::write(&config, format!("max_file_size_bytes = {}\n", limit))
+            .expect("Failed to write config");
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--config"])
+            .arg(&config)
+            .arg(&file)
+            .output()
+            .expect("Failed to run check")
+    };
+
+    // One byte over the limit: the file is left alone
+    let output = check_with_limit(source.len() - 1);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    // At the limit the file is processed as usual
+    let output = check_with_limit(source.len());
+    assert_eq!(output.status.code(), Some(1));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_and_write_flags_match_subcommands() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "program P;\nbegin\n  Foo(a,b);\nend.\n";
+    let run = |args: &[&str], path: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(args)
+            .arg(path)
+            .env_remove("DFIXXER_ROOT")
+            .output()
+            .expect("Failed to run dfixxer")
+    };
+
+    let file = temp_dir.join("foo.pas");
+    fs::write(&file, source).expect("Failed to write source");
+    let subcommand = run(&["check"], &file);
+    let flag = run(&["--check"], &file);
+    assert_eq!(flag.status.code(), subcommand.status.code());
+    assert_eq!(flag.status.code(), Some(1));
+    assert_eq!(flag.stdout, subcommand.stdout);
+    // --check takes the check options too
+    let explained = run(&["--check", "--explain", "--color", "never"], &file);
+    assert_eq!(
+        explained.stdout,
+        run(&["check", "--explain", "--color", "never"], &file).stdout
+    );
+
+    let written = temp_dir.join("written.pas");
+    fs::write(&written, source).expect("Failed to write source");
+    assert!(run(&["--write"], &written).status.success());
+    let updated = temp_dir.join("updated.pas");
+    fs::write(&updated, source).expect("Failed to write source");
+    assert!(run(&["update"], &updated).status.success());
+    assert_eq!(
+        fs::read_to_string(&written).unwrap(),
+        fs::read_to_string(&updated).unwrap()
+    );
+    assert_ne!(fs::read_to_string(&written).unwrap(), source);
+
+    // The flags need a file, and a file needs a flag or a subcommand
+    assert_eq!(run(&["--check", "--write"], &file).status.code(), Some(2));
+    assert_eq!(run(&[], &file).status.code(), Some(2));
+    // Check options do not apply to --write, and the flags do not mix with subcommands
+    assert_eq!(run(&["--write", "--explain"], &file).status.code(), Some(2));
+    assert_eq!(run(&["--check", "update"], &file).status.code(), Some(2));
+    assert_eq!(run(&["--multi", "check"], &file).status.code(), Some(2));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_response_file_lists_files_to_process() {
+    let temp_dir = create_unique_temp_dir();
+    let mut listed = Vec::new();
+    for name in ["one.pas", "two.pas", "three.pas"] {
+        let path = temp_dir.join(name);
+        fs::write(&path, "program P;\nbegin\nend.\n").expect("Failed to write source");
+        listed.push(path.to_string_lossy().to_string());
+    }
+    // A file that exists but is not listed must not be processed
+    fs::write(temp_dir.join("other.pas"), "program P;\nbegin\nend.\n")
+        .expect("Failed to write source");
+    let response_file = temp_dir.join("files.txt");
+    fs::write(&response_file, listed.join("\n") + "\n").expect("Failed to write response file");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("parse")
+        .arg(format!("@{}", response_file.display()))
+        .output()
+        .expect("Failed to run parse with a response file");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let processed: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("Processing file: "))
+        .collect();
+    assert_eq!(processed.len(), 3);
+    for name in ["one.pas", "two.pas", "three.pas"] {
+        assert!(processed.iter().any(|line| line.ends_with(name)));
+    }
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_format_json_combines_multi_file_report() {
+    let temp_dir = create_unique_temp_dir();
+    fs::write(
+        temp_dir.join("a.pas"),
+        "program A;\nbegin\n  Foo(a,b);\n  Bar(c,d);\nend.\n",
+    )
+    .expect("Failed to write source");
+    fs::write(
+        temp_dir.join("b.pas"),
+        "program B;\nbegin\n  Foo(a, b);\nend.\n",
+    )
+    .expect("Failed to write source");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--multi", "--format", "json"])
+        .arg(temp_dir.join("*.pas"))
+        .output()
+        .expect("Failed to run check --format json");
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("Output is a single JSON object");
+
+    let files = report["files"].as_array().expect("files is an array");
+    assert_eq!(files.len(), 2);
+    assert!(files[0]["path"].as_str().unwrap().ends_with("a.pas"));
+    assert_eq!(files[0]["changed"], true);
+    assert!(files[1]["path"].as_str().unwrap().ends_with("b.pas"));
+    assert_eq!(files[1]["changed"], false);
+    assert_eq!(files[1]["changes"].as_array().unwrap().len(), 0);
+
+    let changes = files[0]["changes"].as_array().unwrap();
+    assert!(!changes.is_empty());
+    assert_eq!(changes[0]["line"], 3);
+    assert_eq!(report["total_changes"], changes.len());
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_follow_includes_checks_included_file() {
+    let temp_dir = create_unique_temp_dir();
+    fs::create_dir_all(temp_dir.join("inc")).expect("Failed to create include dir");
+    let unit = temp_dir.join("Main.pas");
+    fs::write(
+        &unit,
+        "unit Main;\n\ninterface\n\nimplementation\n\n{$I inc/Body.inc}\n{$I-}\n{$INCLUDE 'Missing.inc'}\n\nend.\n",
+    )
+    .expect("Failed to write unit");
+    // The include file includes itself, which must not loop
+    fs::write(
+        temp_dir.join("inc").join("Body.inc"),
+        "{$I Body.inc}\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n",
+    )
+    .expect("Failed to write include file");
+
+    let check = |extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("check")
+            .args(extra_args)
+            .arg("--list-different")
+            .arg(&unit)
+            .output()
+            .expect("Failed to run check")
+    };
+
+    let output = check(&[]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = check(&["--follow-includes"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed: Vec<&str> = stdout.lines().collect();
+    assert_eq!(listed.len(), 1, "{}", stdout);
+    assert!(listed[0].ends_with("Body.inc"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Missing.inc"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_since_processes_only_changed_files() {
+    let temp_dir = create_unique_temp_dir();
+    for name in ["changed.pas", "unchanged.pas"] {
+        fs::write(
+            temp_dir.join(name),
+            "program P;\nbegin\n  Foo(a,b);\nend.\n",
+        )
+        .expect("Failed to write source");
+    }
+    // Stands in for the output of `git diff --name-only`
+    let change_list = temp_dir.join("changes.txt");
+    fs::write(
+        &change_list,
+        format!("{}\n", temp_dir.join("changed.pas").display()),
+    )
+    .expect("Failed to write change list");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--list-different", "--multi", "--since"])
+        .arg(format!("@{}", change_list.display()))
+        .arg(temp_dir.join("*.pas"))
+        .output()
+        .expect("Failed to run check --since");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed: Vec<&str> = stdout.lines().collect();
+    assert_eq!(listed.len(), 1, "{}", stdout);
+    assert!(listed[0].ends_with("changed.pas"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_print_config_applies_custom_config_patterns() {
+    let temp_dir = create_unique_temp_dir();
+    fs::create_dir_all(temp_dir.join("legacy")).expect("Failed to create legacy dir");
+    fs::write(
+        temp_dir.join("dfixxer.toml"),
+        "indentation = \"  \"\ncustom_config_patterns = [[\"legacy/*.pas\", \"legacy.toml\"]]\n",
+    )
+    .expect("Failed to write config");
+    fs::write(
+        temp_dir.join("legacy.toml"),
+        "indentation = \"\\t\"\nmax_file_size_bytes = 1234\n",
+    )
+    .expect("Failed to write custom config");
+
+    let print_config = |file: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("print-config")
+            .arg(temp_dir.join(file))
+            .output()
+            .expect("Failed to run print-config");
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).expect("Output is not UTF-8")
+    };
+
+    let legacy = print_config("legacy/Old.pas");
+    assert!(legacy.contains("indentation = \"\\t\""), "{}", legacy);
+    assert!(legacy.contains("max_file_size_bytes = 1234"), "{}", legacy);
+
+    let main = print_config("New.pas");
+    assert!(main.contains("indentation = \"  \""), "{}", main);
+    assert!(main.contains("max_file_size_bytes = 0"), "{}", main);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_parse_accepts_config() {
+    let temp_dir = create_unique_temp_dir();
+    let config = temp_dir.join("dfixxer.toml");
+    fs::write(&config, "[parse_debug]\nline_column_positions = true\n")
+        .expect("Failed to write config");
+    let source = temp_dir.join("small.pas");
+    fs::write(
+        &source,
+        "unit Small;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write source");
+
+    for command in ["parse", "parse-debug"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args([command, "--config"])
+            .arg(&config)
+            .arg(&source)
+            .output()
+            .expect("Failed to run parse with --config");
+        assert!(
+            output.status.success(),
+            "{} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_verify_string_literals_warns_on_unterminated_string() {
+    let temp_dir = create_unique_temp_dir();
+    fs::write(
+        temp_dir.join("dfixxer.toml"),
+        "[text_changes]\nverify_string_literals = true\n",
+    )
+    .expect("Failed to write config");
+    let run_check = |name: &str, source: &str| {
+        let temp_file = temp_dir.join(name);
+        fs::write(&temp_file, source).expect("Failed to write string fixture");
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--config"])
+            .arg(temp_dir.join("dfixxer.toml"))
+            .arg(&temp_file)
+            .output()
+            .expect("Failed to run check");
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    let clean = run_check(
+        "clean.pas",
+        "unit Clean;\n\ninterface\n\nimplementation\n\nconst\n  S = 'it''s';\n\nend.\n",
+    );
+    assert!(!clean.contains("string literal is not terminated"));
+
+    let open = run_check(
+        "open.pas",
+        "unit Open;\n\ninterface\n\nimplementation\n\nconst\n  S = 'it's';\n\nend.\n",
+    );
+    assert!(open.contains("open.pas:8:12: string literal is not terminated"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_only_uses_leaves_text_untouched() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("only.pas");
+    fs::write(
+        &temp_file,
+        "unit Only;\n\ninterface\n\nuses\n  SysUtils, Classes;\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write only fixture");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["update", "--only", "uses"])
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update --only");
+    assert!(status.success());
+
+    let updated = fs::read_to_string(&temp_file).expect("Failed to read updated file");
+    assert!(updated.contains("  Foo(a,b);"));
+    assert!(updated.contains("  System.Classes,\n  System.SysUtils;"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_skip_uses_still_applies_text_changes() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("skip.pas");
+    fs::write(
+        &temp_file,
+        "unit Skip;\n\ninterface\n\nuses\n  SysUtils, Classes;\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write skip fixture");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["update", "--skip", "uses"])
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update --skip");
+    assert!(status.success());
+
+    let updated = fs::read_to_string(&temp_file).expect("Failed to read updated file");
+    assert!(updated.contains("uses\n  SysUtils, Classes;"));
+    assert!(updated.contains("  Foo(a, b);"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["update", "--skip", "uses", "--only", "text"])
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update with --skip and --only");
+    assert_eq!(status.code(), Some(2));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_explain_prints_reasons_after_diff() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("explain.pas");
+    fs::write(
+        &temp_file,
+        "unit Explain;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n",
+    )
+    .expect("Failed to write explain fixture");
+
+    let run_check = |extra_args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--color", "never"])
+            .args(extra_args)
+            .arg(&temp_file)
+            .output()
+            .expect("Failed to run check");
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let reason_line = format!("{}:9: spacing and whitespace", temp_file.display());
+    let explained = run_check(&["--explain"]);
+    assert!(explained.contains("+  Foo(a, b);"));
+    assert!(explained.contains(&reason_line));
+
+    let plain = run_check(&[]);
+    assert!(!plain.contains(&reason_line));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+fn lsp_frame(message: serde_json::Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn parse_lsp_frames(mut output: &str) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+    while let Some((header, rest)) = output.split_once("\r\n\r\n") {
+        let length: usize = header
+            .trim_start_matches("Content-Length: ")
+            .parse()
+            .expect("Invalid Content-Length header");
+        messages.push(serde_json::from_str(&rest[..length]).expect("Invalid JSON body"));
+        output = &rest[length..];
+    }
+    messages
+}
+
+#[test]
+fn test_serve_formatting_request_returns_fixture_edits() {
+    let temp_dir = create_unique_temp_dir();
+    let config_file = temp_dir.join("dfixxer.toml");
+    fs::write(&config_file, "").expect("Failed to write config");
+    let fixture_dir = Path::new("test-data").join("serve");
+    let source = fs::read_to_string(fixture_dir.join("formatting.pas"))
+        .expect("Failed to read serve fixture");
+    let expected: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(fixture_dir.join("formatting.edits.json"))
+            .expect("Failed to read expected edits"),
+    )
+    .expect("Invalid expected edits");
+    let uri = format!("file://{}", temp_dir.join("formatting.pas").display());
+
+    let input = [
+        serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        serde_json::json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": uri, "languageId": "pascal", "version": 1, "text": source}},
+        }),
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "textDocument/formatting",
+            "params": {"textDocument": {"uri": uri}, "options": {"tabSize": 2, "insertSpaces": true}},
+        }),
+        serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+        serde_json::json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]
+    .into_iter()
+    .map(lsp_frame)
+    .collect::<String>();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("serve")
+        .arg("--config")
+        .arg(&config_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start dfixxer serve");
+    child
+        .stdin
+        .take()
+        .expect("Failed to open stdin")
+        .write_all(input.as_bytes())
+        .expect("Failed to write LSP messages");
+    let output = child
+        .wait_with_output()
+        .expect("Failed to wait for dfixxer serve");
+    assert!(output.status.success());
+
+    let responses = parse_lsp_frames(&String::from_utf8_lossy(&output.stdout));
+    let formatting = responses
+        .iter()
+        .find(|response| response["id"] == 2)
+        .expect("Missing formatting response");
+    assert_eq!(formatting["result"], expected);
+    assert!(responses.iter().any(|response| response["id"] == 3));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_init_config_refuses_to_overwrite_without_force() {
+    let temp_dir = create_unique_temp_dir();
+    let config_path = temp_dir.join("dfixxer.toml");
+    let init_config = |extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("init-config")
+            .arg(&config_path)
+            .args(extra_args)
+            .output()
+            .expect("Failed to run dfixxer init-config")
+    };
+
+    assert!(init_config(&[]).status.success());
+    fs::write(&config_path, "indentation = \"    \"\n").unwrap();
+
+    let output = init_config(&[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--force"));
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        "indentation = \"    \"\n"
+    );
+
+    assert!(init_config(&["--force"]).status.success());
+    assert!(
+        fs::read_to_string(&config_path)
+            .unwrap()
+            .contains("[uses_section]")
+    );
+
+    fs::remove_dir_all(&temp_dir).ok();
 }
 
 fn create_unique_temp_dir() -> std::path::PathBuf {
@@ -246,6 +1347,340 @@ fn test_check_multi_does_not_modify_files_and_prints_per_file_output() {
     fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
 }
 
+#[test]
+fn test_update_stdin_uses_stdin_filename_for_config_lookup() {
+    let temp_dir = create_unique_temp_dir();
+    fs::write(
+        temp_dir.join("dfixxer.toml"),
+        r#"line_ending = "Lf"
+
+[transformations]
+enable_unit_program_section = false
+enable_procedure_section = false
+
+[text_changes]
+comma = "BeforeAndAfter"
+"#,
+    )
+    .expect("Failed to write config");
+
+    // The virtual path does not need to exist; only its directory is used for config discovery.
+    let virtual_path = temp_dir.join("src").join("virtual.pas");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg("--stdin-filename")
+        .arg(&virtual_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run update with --stdin-filename");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"program P;\nbegin\n  Foo(a,b);\nend.\n")
+        .expect("Failed to write stdin");
+    let output = child
+        .wait_with_output()
+        .expect("Failed to wait for dfixxer");
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "program P;\nbegin\n  Foo(a , b);\nend.\n"
+    );
+    assert!(!virtual_path.exists());
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_range_leaves_code_outside_range_untouched() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "unit RangeFixture;\n\ninterface\n\nuses\n  SysUtils,Classes;\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n";
+    let temp_file = temp_dir.join("range.pas");
+    fs::write(&temp_file, source).expect("Failed to write range fixture");
+
+    let range_start = source.find("  Foo").unwrap();
+    let range_end = source.find("\nend;").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&temp_file)
+        .arg("--range-start")
+        .arg(range_start.to_string())
+        .arg("--range-end")
+        .arg(range_end.to_string())
+        .status()
+        .expect("Failed to run update with a range");
+    assert!(status.success());
+
+    let updated = fs::read_to_string(&temp_file).expect("Failed to read updated file");
+    assert_eq!(updated, source.replace("Foo(a,b)", "Foo(a, b)"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_preserves_byte_order_mark() {
+    let temp_dir = create_unique_temp_dir();
+    let clean_source = "\u{feff}unit Clean;\n\ninterface\n\nimplementation\n\nend.\n";
+    let clean_file = temp_dir.join("bom_clean.pas");
+    fs::write(&clean_file, clean_source).expect("Failed to write clean BOM fixture");
+    let changed_source = "\u{feff}unit Changed;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n";
+    let changed_file = temp_dir.join("bom_changed.pas");
+    fs::write(&changed_file, changed_source).expect("Failed to write changed BOM fixture");
+
+    for file in [&clean_file, &changed_file] {
+        let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .arg("update")
+            .arg(file)
+            .status()
+            .expect("Failed to run update on BOM fixture");
+        assert!(status.success());
+    }
+
+    let clean_bytes = fs::read(&clean_file).expect("Failed to read clean BOM fixture");
+    assert_eq!(clean_bytes, clean_source.as_bytes());
+    let changed = fs::read_to_string(&changed_file).expect("Failed to read changed BOM fixture");
+    assert_eq!(changed, changed_source.replace("Foo(a,b)", "Foo(a, b)"));
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+fn utf16le_with_bom(text: &str) -> Vec<u8> {
+    let mut bytes = vec![0xFF, 0xFE];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn test_update_round_trips_utf16le_source() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "unit Wide;\r\n\r\ninterface\r\n\r\nimplementation\r\n\r\nprocedure Run;\r\nbegin\r\n  Foo(a,b); // Größe\r\nend;\r\n\r\nend.\r\n";
+    let temp_file = temp_dir.join("utf16le.pas");
+    fs::write(&temp_file, utf16le_with_bom(source)).expect("Failed to write UTF-16LE fixture");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update on UTF-16LE fixture");
+    assert!(status.success());
+
+    let updated = fs::read(&temp_file).expect("Failed to read UTF-16LE fixture");
+    assert_eq!(
+        updated,
+        utf16le_with_bom(&source.replace("Foo(a,b)", "Foo(a, b)"))
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_auto_line_ending_keeps_crlf_source() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "unit CrlfFixture;\r\n\r\ninterface\r\n\r\nimplementation\r\n\r\nprocedure Run;\r\nbegin\r\n  Foo(a,b);\r\nend;\r\n\r\nend.";
+    let temp_file = temp_dir.join("crlf.pas");
+    fs::write(&temp_file, source).expect("Failed to write CRLF fixture");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update on CRLF fixture");
+    assert!(status.success());
+
+    let updated = fs::read_to_string(&temp_file).expect("Failed to read CRLF fixture");
+    let expected = format!("{}\r\n", source.replace("Foo(a,b)", "Foo(a, b)"));
+    assert_eq!(updated, expected);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_normalize_line_endings_keeps_suppressed_crlf() {
+    let temp_dir = create_unique_temp_dir();
+    fs::write(
+        temp_dir.join("dfixxer.toml"),
+        "line_ending = \"Lf\"\n\n[text_changes]\nnormalize_line_endings = true\n",
+    )
+    .expect("Failed to write config");
+    let source = "unit Endings;\r\n\r\ninterface\r\n\r\n// dfixxer:off\r\nconst\r\n  A = 1;\r\n// dfixxer:on\r\n\r\nimplementation\r\n\r\nend.\r\n";
+    let temp_file = temp_dir.join("endings.pas");
+    fs::write(&temp_file, source).expect("Failed to write line ending fixture");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update on line ending fixture");
+    assert!(status.success());
+
+    let updated = fs::read_to_string(&temp_file).expect("Failed to read line ending fixture");
+    assert_eq!(
+        updated,
+        "unit Endings;\n\ninterface\n\n// dfixxer:off\r\nconst\r\n  A = 1;\r\n// dfixxer:on\r\n\nimplementation\n\nend.\n"
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_update_does_not_rewrite_unchanged_file() {
+    let temp_dir = create_unique_temp_dir();
+    let temp_file = temp_dir.join("unchanged.pas");
+    fs::write(
+        &temp_file,
+        "unit Clean;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write clean fixture");
+    let modified_before = fs::metadata(&temp_file)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read modification time");
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let status = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("update")
+        .arg(&temp_file)
+        .status()
+        .expect("Failed to run update on clean fixture");
+    assert!(status.success());
+
+    let modified_after = fs::metadata(&temp_file)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read modification time");
+    assert_eq!(modified_before, modified_after);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_list_different_prints_only_changed_paths() {
+    let temp_dir = create_unique_temp_dir();
+    let src = Path::new("test-data")
+        .join("update")
+        .join("ex1.original.test.pas");
+    let changed_file = copy_file_to_temp_with_name(&src, &temp_dir, "list_different_1.pas");
+    fs::write(
+        temp_dir.join("list_different_2.pas"),
+        "unit Clean;\n\ninterface\n\nimplementation\n\nend.\n",
+    )
+    .expect("Failed to write clean file");
+
+    let pattern_path = temp_dir.join("*.pas");
+    let pattern = pattern_path.to_string_lossy();
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .arg("check")
+        .arg(pattern.as_ref())
+        .arg("--multi")
+        .arg("--list-different")
+        .output()
+        .expect("Failed to run check --list-different");
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed: Vec<&str> = stdout.lines().collect();
+    assert_eq!(listed, vec![changed_file.to_string_lossy().as_ref()]);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_progress_leaves_stdout_unchanged() {
+    let temp_dir = create_unique_temp_dir();
+    let src = Path::new("test-data")
+        .join("update")
+        .join("ex1.original.test.pas");
+    copy_file_to_temp_with_name(&src, &temp_dir, "progress_1.pas");
+    copy_file_to_temp_with_name(&src, &temp_dir, "progress_2.pas");
+
+    let pattern = temp_dir.join("*.pas");
+    let run = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--multi"])
+            .args(extra)
+            .arg(&pattern)
+            .output()
+            .expect("Failed to run check --multi")
+    };
+    let plain = run(&[]);
+    let with_progress = run(&["--progress"]);
+    assert_eq!(with_progress.status.code(), plain.status.code());
+    assert_eq!(
+        String::from_utf8_lossy(&with_progress.stdout),
+        String::from_utf8_lossy(&plain.stdout)
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_jobs_keeps_output_in_input_order() {
+    let temp_dir = create_unique_temp_dir();
+    let update_dir = Path::new("test-data").join("update");
+    for (index, name) in ["ex1", "ex2", "ex4", "default_comment_spacing"]
+        .iter()
+        .cycle()
+        .take(8)
+        .enumerate()
+    {
+        copy_file_to_temp_with_name(
+            &update_dir.join(format!("{}.original.test.pas", name)),
+            &temp_dir,
+            &format!("jobs_{}.pas", index),
+        );
+    }
+
+    let pattern = temp_dir.join("*.pas");
+    let run = |jobs: &str| {
+        Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+            .args(["check", "--multi", "--explain", "--jobs", jobs])
+            .arg(&pattern)
+            .output()
+            .expect("Failed to run check --jobs")
+    };
+    let sequential = run("1");
+    let parallel = run("4");
+    assert_eq!(parallel.status.code(), sequential.status.code());
+    assert_eq!(
+        String::from_utf8_lossy(&parallel.stdout),
+        String::from_utf8_lossy(&sequential.stdout)
+    );
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
+#[test]
+fn test_check_multi_merges_overlapping_patterns() {
+    let temp_dir = create_unique_temp_dir();
+    let source = "unit Multi;\n\ninterface\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n";
+    for name in ["a.pas", "b.pas", "c.pas"] {
+        fs::write(temp_dir.join(name), source).expect("Failed to write multi fixture");
+    }
+
+    let all_pattern = temp_dir.join("*.pas");
+    let brace_pattern = temp_dir.join("{a,b}.pas");
+    let output = Command::new(env!("CARGO_BIN_EXE_dfixxer"))
+        .args(["check", "--multi", "--list-different"])
+        .arg(&brace_pattern)
+        .arg(&all_pattern)
+        .output()
+        .expect("Failed to run check with several patterns");
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listed: Vec<&str> = stdout.lines().collect();
+    let expected: Vec<String> = ["a.pas", "b.pas", "c.pas"]
+        .iter()
+        .map(|name| temp_dir.join(name).to_string_lossy().to_string())
+        .collect();
+    assert_eq!(listed, expected);
+
+    fs::remove_dir_all(&temp_dir).expect("Failed to remove temp dir");
+}
+
 #[test]
 fn test_update_smoke() {
     let test_data_dir = Path::new("test-data").join("update");
@@ -0,0 +1,183 @@
+use dfixxer::{Options, TextReplacement, format_source, new_parser, parse, parse_with};
+
+const SOURCE: &str = "unit LibraryApi;\n\ninterface\n\nuses\n  System.Classes, System.SysUtils;\n\nimplementation\n\nprocedure Run;\nbegin\n  Foo(a,b);\nend;\n\nend.\n";
+
+#[test]
+fn test_format_source_matches_update_output() {
+    let formatted = format_source(SOURCE, &Options::default()).expect("Failed to format source");
+    assert!(formatted.contains("  Foo(a, b);\n"));
+    assert!(formatted.starts_with("unit LibraryApi;\n"));
+}
+
+#[test]
+fn test_format_source_is_idempotent() {
+    let options = Options::default();
+    let formatted = format_source(SOURCE, &options).expect("Failed to format source");
+    let reformatted = format_source(&formatted, &options).expect("Failed to reformat source");
+    assert_eq!(formatted, reformatted);
+}
+
+#[test]
+fn test_parse_is_exported() {
+    let parse_result = parse(SOURCE).expect("Failed to parse source");
+    assert!(!parse_result.code_sections.is_empty());
+}
+
+#[test]
+fn test_reused_parser_matches_fresh_parses() {
+    let mut sources: Vec<String> = std::fs::read_dir("test-data/update")
+        .expect("Failed to read test-data/update")
+        .map(|entry| entry.expect("Failed to read directory entry").path())
+        .filter(|path| path.to_string_lossy().ends_with(".original.test.pas"))
+        .map(|path| std::fs::read_to_string(path).expect("Failed to read test file"))
+        .collect();
+    sources.push(SOURCE.to_string());
+    assert!(sources.len() > 3);
+
+    let mut parser = new_parser().expect("Failed to create parser");
+    // Several rounds, so every file is parsed after a different predecessor
+    for _ in 0..3 {
+        for source in &sources {
+            assert_eq!(
+                format!("{:?}", parse_with(&mut parser, source)),
+                format!("{:?}", parse(source))
+            );
+        }
+    }
+}
+
+#[test]
+fn test_text_replacement_is_exported() {
+    let replacement = TextReplacement {
+        start: 0,
+        end: 4,
+        text: "UNIT".to_string(),
+        reason: None,
+        is_final: false,
+    };
+    assert_eq!(replacement.end - replacement.start, 4);
+}
+
+#[test]
+fn test_text_pass_leaves_uses_replacement_alone() {
+    let source = SOURCE.replace("Foo(a,b)", "Foo(a, b)");
+    let mut options = Options::default();
+    options.uses_section.reflow = false;
+    options.text_changes.comma = dfixxer::options::SpaceOperation::Remove;
+    let formatted = format_source(&source, &options).expect("Failed to format source");
+    // The comma rule applies to code outside the uses section only
+    assert!(formatted.contains("  System.Classes, System.SysUtils;\n"));
+    assert!(formatted.contains("  Foo(a,b);\n"));
+}
+
+#[test]
+fn test_configured_format_markers_leave_uses_unsorted() {
+    let source = "unit Markers;\n\ninterface\n\n// dfixxer: off\nuses\n  Zeta, Alpha;\n// dfixxer: on\n\nimplementation\n\nend.\n";
+    let mut options = Options {
+        format_off_marker: "dfixxer: off".to_string(),
+        format_on_marker: "dfixxer: on".to_string(),
+        ..Options::default()
+    };
+    let formatted = format_source(source, &options).expect("Failed to format source");
+    assert!(formatted.contains("uses\n  Zeta, Alpha;\n"));
+
+    // With the default markers those comments are ordinary and the uses clause is sorted
+    options = Options::default();
+    let formatted = format_source(source, &options).expect("Failed to format source");
+    assert!(formatted.contains("uses\n  Alpha,\n  Zeta;\n"));
+}
+
+/// Options with every transformation but the text changes turned off
+fn text_only_options() -> Options {
+    Options {
+        transformations: dfixxer::options::TransformationOptions {
+            enable_uses_section: false,
+            enable_unit_program_section: false,
+            enable_single_keyword_sections: false,
+            enable_procedure_section: false,
+            enable_local_routine_spacing: false,
+            enable_local_routine_indentation: false,
+            enable_inline_local_var_definitions: false,
+            enable_for_body_wrapping: false,
+            enable_while_body_wrapping: false,
+            enable_if_body_wrapping: false,
+            enable_inherited_call_expansion: false,
+            ..Default::default()
+        },
+        ..Options::default()
+    }
+}
+
+/// The narrow config that skips parsing: only text changes, and none of the text rules that
+/// read the parse tree
+fn parse_free_options() -> Options {
+    let mut options = text_only_options();
+    let text_changes = &mut options.text_changes;
+    for operation in [
+        &mut text_changes.lt,
+        &mut text_changes.gt,
+        &mut text_changes.add,
+        &mut text_changes.sub,
+        &mut text_changes.unary_sign,
+        &mut text_changes.colon,
+        &mut text_changes.label_colon,
+    ] {
+        *operation = dfixxer::options::SpaceOperation::NoChange;
+    }
+    options
+}
+
+#[test]
+fn test_text_only_config_formats_source_the_parser_rejects() {
+    let source = "unit Broken;\n\nimplementation\n\nprocedure Run;\nbegin\n  if (a,b then\n  Foo(a,b);\nend;\n";
+    // Without a parse there are no error spans, so the text changes reach the broken line too
+    let formatted = format_source(source, &parse_free_options()).expect("Failed to format source");
+    assert!(formatted.contains("  if (a, b then\n  Foo(a, b);\n"));
+}
+
+#[test]
+fn test_text_only_config_matches_parsed_text_output() {
+    let source = "unit Signs;\n\nimplementation\n\nprocedure Run;\nlabel Done;\nvar\n  L: TList<Integer>;\nbegin\n  x := a*-1;\n  if a<b then x := c>d;\n  case x of\n    1:Foo;\n  end;\nDone:\n  y := +2e-3;\nend;\n\nend.\n";
+    let options = parse_free_options();
+    // A transformation with nothing to do in this source, so only the parse differs
+    let mut parsed_options = parse_free_options();
+    parsed_options.transformations.remove_redundant_semicolons = true;
+    assert_eq!(
+        format_source(source, &options).expect("Failed to format source"),
+        format_source(source, &parsed_options).expect("Failed to format source")
+    );
+}
+
+#[test]
+fn test_strict_parse_policy_keeps_the_parse_for_text_only_config() {
+    let source =
+        "unit Partial;\n\nimplementation\n\nprocedure Run;\nbegin\n  x := ;\nend;\n\nend.\n";
+    let options = Options {
+        parse_policy: dfixxer::options::ParsePolicy::Strict,
+        ..parse_free_options()
+    };
+    let err = format_source(source, &options).expect_err("Strict parsing fails the file");
+    assert!(matches!(
+        err,
+        dfixxer::DFixxerError::SyntaxError { line: 7, .. }
+    ));
+}
+
+#[test]
+fn test_parse_policy_decides_whether_error_regions_fail_the_file() {
+    let source = "unit Partial;\n\ninterface\n\nuses\n  Zeta, Alpha;\n\nimplementation\n\nprocedure Run;\nbegin\n  x := ;\nend;\n\nend.\n";
+    let formatted =
+        format_source(source, &Options::default()).expect("Lenient parsing formats the file");
+    assert!(formatted.contains("uses\n  Alpha,\n  Zeta;\n"));
+    assert!(formatted.contains("  x := ;\n"));
+
+    let options = Options {
+        parse_policy: dfixxer::options::ParsePolicy::Strict,
+        ..Options::default()
+    };
+    let err = format_source(source, &options).expect_err("Strict parsing fails the file");
+    assert!(matches!(
+        err,
+        dfixxer::DFixxerError::SyntaxError { line: 12, .. }
+    ));
+}